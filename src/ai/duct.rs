@@ -0,0 +1,399 @@
+use std::cmp::Ordering;
+use std::ops::{Index, IndexMut};
+
+use log::debug;
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use crate::ai::mcts::{MctsConfig, Stopwatch};
+use crate::SimultaneousGame;
+
+/// Run decoupled UCT to select a turn for `player`.
+///
+/// Every player selects its own turn at each node using only its own
+/// statistics, and the resulting joint turn determines the child. Players
+/// without turns pass. Returns `None` if the game is over or `player` must
+/// pass.
+pub fn run<G: SimultaneousGame>(game: &G, player: &G::Player) -> Option<G::Turn> {
+    run_with(game, player, &MctsConfig::default())
+}
+
+/// Run decoupled UCT with a configuration to select a turn for `player`.
+///
+/// Only the budget, timer, exploration constant, and seed of the
+/// configuration are used.
+pub fn run_with<G: SimultaneousGame>(
+    game: &G,
+    player: &G::Player,
+    config: &MctsConfig,
+) -> Option<G::Turn> {
+    // Record time DUCT was started
+    let now = Stopwatch::start(config);
+    let mut rng = config.rng();
+
+    // Nothing to choose once the game is over
+    if game.over() {
+        return None;
+    }
+
+    // Create the game tree
+    let mut tree = Tree::new(game.clone(), config.explore);
+    let seat = tree[tree.root]
+        .players
+        .iter()
+        .position(|other| other == player)?;
+
+    // Return immediately if only one valid turn
+    if tree[tree.root].turns[seat].len() == 1 {
+        return Some(tree[tree.root].turns[seat][0].clone());
+    }
+
+    let mut iterations = 0;
+    while !config.budget.exhausted(&now, iterations) {
+        iterations += 1;
+
+        // Select a path ending in a new or terminal node
+        let path = tree.select();
+
+        // Simulate at the end of the path
+        let leaf = path.last().map_or(tree.root, |&(idx, _)| idx);
+        let leaf = tree.child(leaf, &path);
+        let winner = tree[leaf].simulate(&mut rng);
+
+        // Backpropagate the winner
+        tree.backprop(&path, winner);
+    }
+
+    // Find most simulated turn for the player
+    let root = &tree[tree.root];
+    debug!("idx: sims, wins%");
+    let best = root.stats[seat]
+        .iter()
+        .enumerate()
+        .inspect(|(idx, (wins, sims))| {
+            debug!(
+                "{:03}: {:4}, {:4.1}%",
+                idx,
+                sims,
+                100. * wins / *sims as f64
+            )
+        })
+        .max_by_key(|(_, (_, sims))| *sims)?
+        .0;
+
+    // Play most simulated turn
+    Some(root.turns[seat][best].clone())
+}
+
+/// The game tree of joint turns from the current position.
+#[derive(Debug)]
+struct Tree<G: SimultaneousGame> {
+    arena: Vec<Node<G>>,
+    root: usize,
+    explore: f64,
+}
+
+impl<G: SimultaneousGame> Tree<G> {
+    /// Create a new Tree initialized with a root.
+    fn new(state: G, explore: f64) -> Tree<G> {
+        Tree {
+            arena: vec![Node::new(state)],
+            root: 0,
+            explore,
+        }
+    }
+
+    /// Select joint turns from the root until reaching a new child.
+    ///
+    /// Returns the nodes visited paired with the joint turn chosen at each.
+    fn select(&self) -> Vec<(usize, Vec<usize>)> {
+        let mut path = Vec::new();
+        let mut idx = self.root;
+
+        // Stop where the game is over, or stuck with every player passing
+        while !self[idx].state.over() && !self[idx].players.is_empty() {
+            // Each player independently picks its highest priority turn
+            let node = &self[idx];
+            let joint: Vec<usize> = node
+                .stats
+                .iter()
+                .map(|stats| {
+                    stats
+                        .iter()
+                        .map(|&(wins, sims)| priority(wins, sims, node.sims, self.explore))
+                        .enumerate()
+                        .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(Ordering::Equal))
+                        .unwrap()
+                        .0
+                })
+                .collect();
+
+            // Stop once the joint turn leads somewhere new
+            let next = node.children.iter().find(|(other, _)| *other == joint);
+            path.push((idx, joint));
+            match next {
+                Some(&(_, child)) => idx = child,
+                None => break,
+            }
+        }
+
+        path
+    }
+
+    /// Get the child reached by the final joint turn of `path`, creating it
+    /// if needed.
+    fn child(&mut self, idx: usize, path: &[(usize, Vec<usize>)]) -> usize {
+        let Some((_, joint)) = path.last() else {
+            return idx;
+        };
+        if let Some(&(_, child)) = self[idx].children.iter().find(|(other, _)| other == joint) {
+            return child;
+        }
+
+        // Play the joint turn on a copy of the state
+        let node = &self[idx];
+        let turns = joint
+            .iter()
+            .zip(&node.turns)
+            .map(|(&turn, turns)| turns[turn].clone())
+            .collect();
+        let mut state = node.state.clone();
+        state.play(turns);
+
+        // Add the new child
+        let child = self.arena.len();
+        self.arena.push(Node::new(state));
+        self[idx].children.push((joint.clone(), child));
+        child
+    }
+
+    /// Backpropagate the result of a simulation along `path`.
+    fn backprop(&mut self, path: &[(usize, Vec<usize>)], winner: Option<G::Player>) {
+        for (idx, joint) in path {
+            let node = &mut self[*idx];
+            node.sims += 1;
+
+            // Update the statistics of each player's chosen turn
+            for (seat, &turn) in joint.iter().enumerate() {
+                let reward = match &winner {
+                    Some(winner) if *winner == node.players[seat] => 1.,
+                    Some(_) => 0.,
+                    None => 0.5,
+                };
+                let (wins, sims) = &mut node.stats[seat][turn];
+                *wins += reward;
+                *sims += 1;
+            }
+        }
+    }
+}
+
+impl<G: SimultaneousGame> Index<usize> for Tree<G> {
+    type Output = Node<G>;
+
+    fn index(&self, idx: usize) -> &Self::Output {
+        &self.arena[idx]
+    }
+}
+
+impl<G: SimultaneousGame> IndexMut<usize> for Tree<G> {
+    fn index_mut(&mut self, idx: usize) -> &mut Self::Output {
+        &mut self.arena[idx]
+    }
+}
+
+/// A single state in the game tree.
+#[derive(Debug)]
+struct Node<G: SimultaneousGame> {
+    // Position
+    children: Vec<(Vec<usize>, usize)>,
+    // State
+    state: G,
+    // Players who do not pass, with their turns
+    players: Vec<G::Player>,
+    turns: Vec<Vec<G::Turn>>,
+    // Statistics
+    stats: Vec<Vec<(f64, u32)>>,
+    sims: u32,
+}
+
+impl<G: SimultaneousGame> Node<G> {
+    /// Create a new Node.
+    fn new(state: G) -> Node<G> {
+        let (players, turns): (Vec<_>, Vec<_>) = state
+            .players()
+            .into_iter()
+            .map(|player| {
+                let turns = state.turns(&player);
+                (player, turns)
+            })
+            .filter(|(_, turns)| !turns.is_empty())
+            .unzip();
+        let stats = turns
+            .iter()
+            .map(|turns| vec![(0., 0); turns.len()])
+            .collect();
+        Node {
+            children: Vec::new(),
+            state,
+            players,
+            turns,
+            stats,
+            sims: 0,
+        }
+    }
+
+    /// Simulate the game from this node.
+    fn simulate<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<G::Player> {
+        // Create a copy of the current state to simulate
+        let mut state = self.state.clone();
+
+        while !state.over() {
+            // Policy: every player selects a random move, or passes
+            let turns: Vec<_> = state
+                .players()
+                .iter()
+                .filter_map(|player| state.turns(player).choose(rng).cloned())
+                .collect();
+            // Stop if every player passes
+            if turns.is_empty() {
+                break;
+            }
+            state.play(turns);
+        }
+
+        state.winner()
+    }
+}
+
+/// Calculate the UCB priority of a player's turn.
+fn priority(wins: f64, sims: u32, psims: u32, explore: f64) -> f64 {
+    let sims = sims as f64;
+    let exploit = wins / sims;
+    let explore = explore * ((psims as f64).ln() / sims).sqrt();
+    match exploit + explore {
+        x if x.is_finite() => x,
+        _ => f64::INFINITY,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fmt::{self, Display};
+
+    use super::*;
+    use crate::ai::mcts::Budget;
+
+    /// Both players pick a number; the higher number wins.
+    #[derive(Clone, Debug)]
+    struct Higher(Option<[u8; 2]>);
+
+    impl Display for Higher {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "{:?}", self.0)
+        }
+    }
+
+    impl SimultaneousGame for Higher {
+        type Player = u8;
+        type Turn = u8;
+
+        fn players(&self) -> Vec<Self::Player> {
+            vec![0, 1]
+        }
+
+        fn turns(&self, _: &Self::Player) -> Vec<Self::Turn> {
+            if self.0.is_some() {
+                Vec::new()
+            } else {
+                vec![1, 2, 3]
+            }
+        }
+
+        fn play(&mut self, turns: Vec<Self::Turn>) -> bool {
+            self.0 = Some([turns[0], turns[1]]);
+            true
+        }
+
+        fn over(&self) -> bool {
+            self.0.is_some()
+        }
+
+        fn winner(&self) -> Option<Self::Player> {
+            let [a, b] = self.0?;
+            match a.cmp(&b) {
+                Ordering::Greater => Some(0),
+                Ordering::Less => Some(1),
+                Ordering::Equal => None,
+            }
+        }
+    }
+
+    /// Player 0 picks a number while player 1 passes, then both pick; the
+    /// higher total wins.
+    #[derive(Clone, Debug, Default)]
+    struct Head(Vec<Vec<u8>>);
+
+    impl Display for Head {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "{:?}", self.0)
+        }
+    }
+
+    impl SimultaneousGame for Head {
+        type Player = u8;
+        type Turn = u8;
+
+        fn players(&self) -> Vec<Self::Player> {
+            vec![0, 1]
+        }
+
+        fn turns(&self, player: &Self::Player) -> Vec<Self::Turn> {
+            match (self.0.len(), player) {
+                (0, 0) | (1, _) => vec![1, 2, 3],
+                _ => Vec::new(),
+            }
+        }
+
+        fn play(&mut self, turns: Vec<Self::Turn>) -> bool {
+            self.0.push(turns);
+            true
+        }
+
+        fn over(&self) -> bool {
+            self.0.len() == 2
+        }
+
+        fn winner(&self) -> Option<Self::Player> {
+            let [first, second] = &self.0[..] else {
+                return None;
+            };
+            match (first[0] + second[0]).cmp(&second[1]) {
+                Ordering::Greater => Some(0),
+                Ordering::Less => Some(1),
+                Ordering::Equal => None,
+            }
+        }
+    }
+
+    #[test]
+    fn picks_dominant_turn() {
+        let config = MctsConfig {
+            budget: Budget::Iterations(2000),
+            ..Default::default()
+        };
+        assert_eq!(run_with(&Higher(None), &1, &config), Some(3));
+        assert_eq!(run_with(&Higher(Some([1, 2])), &1, &config), None);
+    }
+
+    #[test]
+    fn players_without_turns_pass() {
+        let config = MctsConfig {
+            budget: Budget::Iterations(2000),
+            ..Default::default()
+        };
+        assert_eq!(run_with(&Head::default(), &0, &config), Some(3));
+        assert_eq!(run_with(&Head::default(), &1, &config), None);
+        assert_eq!(run_with(&Head(vec![vec![1]]), &1, &config), Some(3));
+    }
+}
@@ -0,0 +1,59 @@
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use crate::Evaluate;
+
+#[cfg(feature = "entropy")]
+const EPSILON: f64 = 0.1;
+
+/// Select the best turn by heuristic, or a random turn 10% of the time.
+#[cfg(feature = "entropy")]
+pub fn run<G: Evaluate>(game: &G) -> G::Turn {
+    run_with(game, EPSILON, &mut rand::thread_rng())
+}
+
+/// Select the best turn by heuristic, or a random turn with probability
+/// `epsilon`.
+///
+/// Each turn is scored by evaluating the position it leads to for the player
+/// making it; ties go to the first turn.
+pub fn run_with<G: Evaluate, R: Rng + ?Sized>(game: &G, epsilon: f64, rng: &mut R) -> G::Turn {
+    let turns = game.turns();
+    if rng.gen_bool(epsilon.clamp(0., 1.)) {
+        return turns.choose(rng).unwrap().clone();
+    }
+
+    // Play the turn leading to the best position
+    let player = game.player();
+    let mut best = (f64::NEG_INFINITY, &turns[0]);
+    for turn in &turns {
+        let mut state = game.clone();
+        state.play(turn.clone());
+        let value = state.evaluate(&player);
+        if value > best.0 {
+            best = (value, turn);
+        }
+    }
+    best.1.clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::tests::Nim;
+
+    #[test]
+    fn plays_greedily_without_epsilon() {
+        let mut rng = StdRng::seed_from_u64(7);
+        // Taking two stones leaves a multiple of four
+        assert_eq!(run_with(&Nim::new(6), 0., &mut rng), 2);
+        // Always random at an epsilon of one
+        let turns: Vec<_> = (0..50)
+            .map(|_| run_with(&Nim::new(6), 1., &mut rng))
+            .collect();
+        assert!(turns.contains(&1) && turns.contains(&3));
+    }
+}
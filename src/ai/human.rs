@@ -0,0 +1,239 @@
+//! Turns chosen by a person at the terminal.
+//!
+//! [`run`] reads turns typed in the game's own notation with [`ParseTurn`],
+//! while [`run_menu`] works with any game by listing the legal turns to pick
+//! from by name or number. Both explain what was wrong with a turn which
+//! cannot be played and ask again, so they can be passed straight to
+//! [`Config::new`](crate::Config::new) as a player.
+//!
+//! Games which can be taken back are better played with [`play`], which
+//! runs the game as a [`Session`] so a person can type `takeback` to undo
+//! their last turn along with the replies to it.
+
+use std::io::{self, BufRead, Write};
+
+use crate::session::{Event, Session};
+use crate::{by_name, Config, Game, ParseTurn, Reversible, TurnError};
+
+/// Command which takes back a turn.
+const TAKEBACK: &str = "takeback";
+
+/// Ask for a turn on standard input, in the game's notation.
+///
+/// # Panics
+///
+/// Panics if standard input ends or cannot be read.
+pub fn run<G: ParseTurn>(game: &G) -> G::Turn {
+    let stdin = io::stdin();
+    read(game, stdin.lock(), io::stdout()).expect("could not read turn")
+}
+
+/// Ask for a turn on standard input, from a numbered list of the legal
+/// turns.
+///
+/// # Panics
+///
+/// Panics if standard input ends or cannot be read.
+pub fn run_menu<G: Game>(game: &G) -> G::Turn {
+    let stdin = io::stdin();
+    read_menu(game, stdin.lock(), io::stdout()).expect("could not read turn")
+}
+
+/// Prompt for a turn until a legal one can be read from `input`.
+///
+/// Each turn which cannot be read or played is explained before prompting
+/// again.
+pub fn read<G: ParseTurn>(
+    game: &G,
+    input: impl BufRead,
+    output: impl Write,
+) -> io::Result<G::Turn> {
+    prompt(game, input, output, |line| legal(game, line))
+}
+
+/// Play a game at the terminal, returning the final position.
+///
+/// Turns for `human` are read as by [`run`], and the rest are chosen as
+/// configured in `config`, where the human's own turn function is never
+/// called.
+///
+/// # Panics
+///
+/// Panics if standard input ends or cannot be read.
+pub fn play<G>(game: G, config: Config<G>, human: G::Player) -> G
+where
+    G: ParseTurn + Reversible,
+{
+    let stdin = io::stdin();
+    interact(game, config, human, stdin.lock(), io::stdout()).expect("could not read turn")
+}
+
+/// Play a game as a [`Session`], reading turns for `human` from `input`.
+///
+/// Typing `takeback` undoes the human's last turn, and every reply since,
+/// then asks for the turn again. Returns an error if `input` ends before
+/// the game does.
+pub fn interact<G>(
+    game: G,
+    config: Config<G>,
+    human: G::Player,
+    mut input: impl BufRead,
+    mut output: impl Write,
+) -> io::Result<G>
+where
+    G: ParseTurn + Reversible,
+{
+    let mut session = Session::new(game, config).external(human.clone());
+    loop {
+        while let Some(event) = session.poll() {
+            match event {
+                Event::TurnStarted(_) => writeln!(output, "{}", session.game())?,
+                Event::TurnPlayed { player, turn, .. } if player != human => {
+                    writeln!(output, "{player} plays {turn}")?
+                }
+                Event::Resigned(player) => writeln!(output, "{player} resigns")?,
+                Event::DrawAgreed => writeln!(output, "Drawn by agreement")?,
+                Event::TimeForfeit(player) => writeln!(output, "{player} ran out of time")?,
+                Event::GameOver(winner) => {
+                    writeln!(output, "{}", session.game())?;
+                    match winner {
+                        Some(player) => writeln!(output, "Winner: {player}")?,
+                        None => writeln!(output, "It's a tie!")?,
+                    }
+                    return Ok(session.game().clone());
+                }
+                _ => (),
+            }
+        }
+        let game = session.game();
+        let read = prompt(game, &mut input, &mut output, |line| match line {
+            TAKEBACK => Ok(None),
+            _ => legal(game, line).map(Some),
+        })?;
+        match read {
+            Some(turn) => {
+                session.submit_turn(turn);
+            }
+            None if session.take_back(&human) => writeln!(output, "{}", session.game())?,
+            None => writeln!(output, "nothing to take back")?,
+        }
+    }
+}
+
+/// Read a turn in the game's notation, checking that it is legal.
+fn legal<G: ParseTurn>(game: &G, line: &str) -> Result<G::Turn, TurnError> {
+    let turn = game.parse_turn(line)?;
+    // Never trust the parser with legality
+    let name = turn.to_string();
+    match game.turns().iter().any(|legal| legal.to_string() == name) {
+        true => Ok(turn),
+        false => Err(TurnError::Illegal(name)),
+    }
+}
+
+/// Prompt for one of the legal turns, by name or number, until one is picked
+/// from `input`.
+///
+/// Names take precedence, so games whose turns are named by numbers still
+/// read as expected.
+pub fn read_menu<G: Game>(
+    game: &G,
+    input: impl BufRead,
+    mut output: impl Write,
+) -> io::Result<G::Turn> {
+    let turns = game.turns();
+    let menu: Vec<_> = turns
+        .iter()
+        .enumerate()
+        .map(|(idx, turn)| format!("{idx}:{turn}"))
+        .collect();
+    writeln!(output, "{}", menu.join(" "))?;
+    prompt(game, input, output, |line| {
+        by_name(game, line).or_else(|err| {
+            line.parse::<usize>()
+                .ok()
+                .and_then(|idx| turns.get(idx).cloned())
+                .ok_or(err)
+        })
+    })
+}
+
+/// Prompt with the player to move until `parse` accepts a line.
+fn prompt<G, T, F>(
+    game: &G,
+    mut input: impl BufRead,
+    mut output: impl Write,
+    parse: F,
+) -> io::Result<T>
+where
+    G: Game,
+    F: Fn(&str) -> Result<T, TurnError>,
+{
+    loop {
+        write!(output, "{} to move: ", game.player())?;
+        output.flush()?;
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        match parse(line.trim()) {
+            Ok(turn) => return Ok(turn),
+            Err(err) => writeln!(output, "{err}")?,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::Nim;
+
+    #[test]
+    fn read_prompts_until_legal() {
+        let mut output = Vec::new();
+        let turn = read(&Nim::new(2), &b"x\n3\n-2\n"[..], &mut output).unwrap();
+        assert_eq!(turn, 2);
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(
+            output,
+            "0 to move: invalid turn: x is not a number\n\
+             0 to move: illegal turn: cannot take 3 stones\n\
+             0 to move: "
+        );
+        assert!(read(&Nim::new(2), &b""[..], io::sink()).is_err());
+    }
+
+    #[test]
+    fn menu_lists_legal_turns() {
+        let mut output = Vec::new();
+        let turn = read_menu(&Nim::new(2), &b"5\n0\n"[..], &mut output).unwrap();
+        assert_eq!(turn, 1);
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(
+            output,
+            "0:1 1:2\n\
+             0 to move: illegal turn: 5\n\
+             0 to move: "
+        );
+    }
+
+    #[test]
+    fn takeback_undoes_the_last_pair() {
+        let take_one = |_: &Nim| 1;
+        let config = || Config::new((0, take_one), (1, take_one));
+        let mut output = Vec::new();
+        let input = &b"takeback\n3\ntakeback\n1\n3\n"[..];
+        let game = interact(Nim::new(5), config(), 0, input, &mut output).unwrap();
+        assert_eq!(game.winner(), Some(0));
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with("5 stones, player 0 to move\n0 to move: nothing to take back\n"));
+        // Taking three and the reply are undone together, back to the start
+        assert_eq!(output.matches("5 stones").count(), 2);
+        assert_eq!(output.matches("3 stones, player 0").count(), 1);
+        assert!(output.ends_with("Winner: 0\n"));
+
+        // The game cannot be left unfinished
+        let input = &b"1\ntakeback\n"[..];
+        assert!(interact(Nim::new(5), config(), 0, input, io::sink()).is_err());
+    }
+}
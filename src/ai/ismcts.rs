@@ -0,0 +1,287 @@
+use std::cmp::Ordering;
+use std::ops::{Index, IndexMut};
+
+use log::debug;
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use crate::ai::mcts::{MctsConfig, Stopwatch};
+use crate::{Game, Hidden};
+
+const EXPLORE: f64 = 0.7;
+
+/// Run single-observer ISMCTS to select a turn.
+///
+/// Each iteration samples a determinization of the hidden state and descends
+/// only through turns which are legal in that determinization.
+pub fn run<G>(game: &G) -> G::Turn
+where
+    G: Hidden,
+    G::Turn: PartialEq,
+{
+    let config = MctsConfig {
+        explore: EXPLORE,
+        ..Default::default()
+    };
+    run_with(game, &config)
+}
+
+/// Run single-observer ISMCTS with a configuration to select a turn.
+///
+/// Only the budget, timer, exploration constant, and seed of the
+/// configuration are used.
+pub fn run_with<G>(game: &G, config: &MctsConfig) -> G::Turn
+where
+    G: Hidden,
+    G::Turn: PartialEq,
+{
+    // Record time ISMCTS was started
+    let now = Stopwatch::start(config);
+    let mut rng = config.rng();
+
+    // Return immediately if only one valid turn
+    let turns = game.turns();
+    if turns.len() == 1 {
+        return turns[0].clone();
+    }
+
+    let mut tree = Tree::<G>::new(config.explore);
+    let mut iterations = 0;
+    while !config.budget.exhausted(&now, iterations) {
+        iterations += 1;
+
+        // Determinize the hidden state at the root
+        let mut state = game.determinize(&mut rng);
+
+        // Select and expand a leaf in the determinization
+        let leaf = tree.descend(&mut state, &mut rng);
+
+        // Simulate from the determinized state
+        while !state.over() {
+            let action = state.turns().choose(&mut rng).unwrap().clone();
+            state.play(action);
+        }
+
+        // Backpropagate the winner
+        tree.backprop(leaf, state.winner());
+    }
+
+    // Find most simulated node
+    let root = &tree[tree.root];
+    debug!("idx: sims, wins%, avails");
+    root.children
+        .iter()
+        .map(|idx| &tree[*idx])
+        .inspect(|node| {
+            debug!(
+                "{:03}: {:4}, {:4.1}%, {:4}",
+                node.idx,
+                node.sims,
+                100. * node.wins / node.sims as f64,
+                node.avails,
+            )
+        })
+        .max_by_key(|node| node.sims)
+        .and_then(|node| node.action.clone())
+        .unwrap_or_else(|| turns.choose(&mut rng).unwrap().clone())
+}
+
+/// The information set tree from the current position.
+#[derive(Debug)]
+struct Tree<G: Game> {
+    arena: Vec<Node<G>>,
+    root: usize,
+    explore: f64,
+}
+
+impl<G: Game> Tree<G>
+where
+    G::Turn: PartialEq,
+{
+    /// Create a new Tree initialized with a root.
+    fn new(explore: f64) -> Tree<G> {
+        Tree {
+            arena: vec![Node::new(0, usize::MAX, None, None)],
+            root: 0,
+            explore,
+        }
+    }
+
+    /// Descend the tree by playing turns on `state`, expanding one node.
+    fn descend<R: Rng + ?Sized>(&mut self, state: &mut G, rng: &mut R) -> usize {
+        let mut idx = self.root;
+
+        while !state.over() {
+            let turns = state.turns();
+
+            // Expand a turn not yet tried from this node
+            let untried: Vec<_> = turns
+                .iter()
+                .filter(|turn| {
+                    !self[idx]
+                        .children
+                        .iter()
+                        .any(|child| self[*child].action.as_ref() == Some(*turn))
+                })
+                .collect();
+            if let Some(&action) = untried.choose(rng) {
+                let child = self.arena.len();
+                self.arena.push(Node::new(
+                    child,
+                    idx,
+                    Some(action.clone()),
+                    Some(state.player()),
+                ));
+                self[idx].children.push(child);
+                state.play(action.clone());
+                return child;
+            }
+
+            // Every child compatible with this determinization is available
+            let compatible: Vec<_> = self[idx]
+                .children
+                .iter()
+                .copied()
+                .filter(|child| {
+                    turns
+                        .iter()
+                        .any(|turn| self[*child].action.as_ref() == Some(turn))
+                })
+                .collect();
+            for child in &compatible {
+                self[*child].avails += 1;
+            }
+
+            // Descend into the child with the highest priority
+            idx = compatible
+                .into_iter()
+                .max_by(|a, b| {
+                    self[*a]
+                        .priority(self.explore)
+                        .partial_cmp(&self[*b].priority(self.explore))
+                        .unwrap_or(Ordering::Equal)
+                })
+                .unwrap();
+            state.play(self[idx].action.clone().unwrap());
+        }
+
+        idx
+    }
+
+    /// Backpropagate the result of a simulation.
+    fn backprop(&mut self, mut idx: usize, winner: Option<G::Player>) {
+        // Backpropagate until the root
+        while idx != usize::MAX {
+            let node = &mut self[idx];
+
+            // Credit the player who chose the action leading to this node
+            node.wins += match (&winner, &node.player) {
+                (Some(winner), Some(player)) if winner == player => 1.,
+                (None, _) => 0.5,
+                _ => 0.,
+            };
+            node.sims += 1;
+
+            // Ascend to parent
+            idx = node.parent;
+        }
+    }
+}
+
+impl<G: Game> Index<usize> for Tree<G> {
+    type Output = Node<G>;
+
+    fn index(&self, idx: usize) -> &Self::Output {
+        &self.arena[idx]
+    }
+}
+
+impl<G: Game> IndexMut<usize> for Tree<G> {
+    fn index_mut(&mut self, idx: usize) -> &mut Self::Output {
+        &mut self.arena[idx]
+    }
+}
+
+/// A single information set in the tree.
+#[derive(Debug)]
+struct Node<G: Game> {
+    // Position
+    idx: usize,
+    parent: usize,
+    children: Vec<usize>,
+    // Action
+    action: Option<G::Turn>,
+    player: Option<G::Player>,
+    // Statistics
+    wins: f64,
+    sims: u32,
+    avails: u32,
+}
+
+impl<G: Game> Node<G> {
+    /// Create a new Node.
+    fn new(
+        idx: usize,
+        parent: usize,
+        action: Option<G::Turn>,
+        player: Option<G::Player>,
+    ) -> Node<G> {
+        Node {
+            idx,
+            parent,
+            children: Vec::new(),
+            action,
+            player,
+            wins: 0.,
+            sims: 0,
+            avails: 1,
+        }
+    }
+
+    /// Calculate node priority using availability counts.
+    fn priority(&self, explore: f64) -> f64 {
+        let sims = self.sims as f64;
+        let exploit = self.wins / sims;
+        let explore = explore * ((self.avails as f64).ln() / sims).sqrt();
+        match exploit + explore {
+            x if x.is_finite() => x,
+            _ => f64::INFINITY,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::mcts::Budget;
+    use crate::tests::Nim;
+
+    impl Hidden for Nim {
+        fn determinize<R: Rng + ?Sized>(&self, _: &mut R) -> Self {
+            self.clone()
+        }
+    }
+
+    #[test]
+    fn finds_winning_move() {
+        let config = MctsConfig {
+            budget: Budget::Iterations(5000),
+            explore: EXPLORE,
+            ..Default::default()
+        };
+        // Taking one stone leaves a multiple of four for the opponent
+        assert_eq!(run_with(&Nim::new(5), &config), 1);
+    }
+
+    #[test]
+    fn seeded_runs_repeat() {
+        let config = MctsConfig {
+            budget: Budget::Iterations(50),
+            seed: Some(7),
+            ..Default::default()
+        };
+        let game = Nim::new(12);
+        let first = run_with(&game, &config);
+        assert!((0..4).all(|_| run_with(&game, &config) == first));
+    }
+}
@@ -1,246 +1,3725 @@
-use std::cmp::Ordering;
-use std::ops::{Index, IndexMut};
-use std::time::Instant;
+use std::cmp::{Ordering, Reverse};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fmt::{self, Debug, Display};
+use std::hash::{Hash, Hasher};
+use std::mem;
+use std::ops::{Deref, DerefMut, Index, IndexMut};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::str::FromStr;
+use std::sync::atomic::{self, AtomicBool};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
 
-use log::{debug, trace};
+use log::{debug, trace, warn};
+use rand::rngs::StdRng;
 use rand::seq::SliceRandom;
+#[cfg(feature = "rayon")]
+use rand::SeedableRng;
+use rand::{Rng, RngCore};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
 
-use crate::Game;
+use crate::{util, Evaluate, Game, Reversible, Score, TurnCoords};
 
-const DURATION: u128 = 995;
+const DURATION: Duration = Duration::from_millis(995);
 const THRESHOLD: u32 = 3;
 const EXPLORE: f64 = 1.414;
+const DEVIATION: f64 = 1.;
+const PROGRESS: Duration = Duration::from_millis(100);
+const PONDER: u32 = 100;
 
-/// Run MCTS to select a turn.
-pub fn run<G: Game>(game: &G) -> G::Turn {
-    // Record time MCTS was started
-    let now = Instant::now();
+/// Limit on how long a search runs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Budget {
+    /// Search until the time limit elapses.
+    Time(Duration),
+    /// Search for an exact number of iterations.
+    Iterations(u32),
+}
 
-    // Create the game tree
-    let game = game.clone();
-    let mut tree = Tree::new(Box::new(game));
-    tree.expand(tree.root); // expand at root
+impl Budget {
+    /// Check if the budget has been used up.
+    pub(crate) fn exhausted(&self, start: &Stopwatch, iterations: u32) -> bool {
+        match *self {
+            Budget::Time(limit) => start.elapsed() >= limit,
+            Budget::Iterations(limit) => iterations >= limit,
+        }
+    }
+}
 
-    // Return immediately if only one valid turn
-    if tree[tree.root].children.len() == 1 {
-        let root = &tree[tree.root];
-        return tree[root.children[0]].action.clone().unwrap();
+impl Default for Budget {
+    fn default() -> Self {
+        Budget::Time(DURATION)
+    }
+}
+
+/// A monotonic clock, read as the time since any fixed point.
+#[derive(Clone, Copy, Debug)]
+pub struct Timer(pub fn() -> Duration);
+
+impl PartialEq for Timer {
+    fn eq(&self, other: &Self) -> bool {
+        ptr::fn_addr_eq(self.0, other.0)
     }
+}
 
-    while now.elapsed().as_millis() < DURATION {
-        // Select a leaf node to expand
-        let mut leaf = tree.select();
+/// Time elapsed in a search, read from its timer.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Stopwatch {
+    /// Started at an instant of the system clock.
+    System(Instant),
+    /// Started at a reading of [`MctsConfig::timer`].
+    Timer(Timer, Duration),
+    /// Never read, where there is no system clock.
+    Off,
+}
 
-        // Expand `leaf` if it's been simulated more than `THRESHOLD`
-        if tree[leaf].sims > THRESHOLD {
-            tree.expand(leaf);
-            leaf = *tree[leaf]
-                .children
-                .choose(&mut rand::thread_rng())
-                .unwrap_or(&leaf);
+impl Stopwatch {
+    /// Start timing a search.
+    ///
+    /// Without a timer, searches with an iteration budget never read the
+    /// system clock on `wasm32-unknown-unknown`, where it panics.
+    pub(crate) fn start(config: &MctsConfig) -> Stopwatch {
+        let unclocked = cfg!(all(target_arch = "wasm32", target_os = "unknown"));
+        match (config.timer, config.budget) {
+            (Some(timer), _) => Stopwatch::Timer(timer, (timer.0)()),
+            (None, Budget::Iterations(_)) if unclocked => Stopwatch::Off,
+            (None, _) => Stopwatch::System(Instant::now()),
+        }
+    }
+
+    /// Get the time since the search started, or zero if it is not timed.
+    pub(crate) fn elapsed(&self) -> Duration {
+        match self {
+            Stopwatch::System(start) => start.elapsed(),
+            Stopwatch::Timer(timer, start) => (timer.0)().saturating_sub(*start),
+            Stopwatch::Off => Duration::ZERO,
+        }
+    }
+}
+
+/// Policy used to select children during search.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Selection {
+    /// Plain UCB1, scaled by the exploration constant.
+    #[default]
+    Ucb1,
+    /// UCB1-Tuned, which bounds exploration by the variance of rewards.
+    ///
+    /// The exploration constant is not used.
+    Ucb1Tuned,
+    /// PUCT, which scales exploration by each child's prior probability.
+    ///
+    /// Priors come from [`Hooks::priors`] or [`Hooks::oracle`], and are
+    /// uniform otherwise.
+    Puct,
+    /// Thompson sampling, which models each child's win rate as a Beta
+    /// distribution and selects the child with the highest sample.
+    ///
+    /// Suited to highly stochastic games. The exploration constant is not
+    /// used.
+    Thompson,
+}
+
+/// Progressive widening of the children expanded at each node.
+///
+/// A node with `n` simulations may expand up to `ceil(scale * n^exponent)`
+/// children, chosen in random order, rather than all of them at once.
+///
+/// Formats as `<scale>,<exponent>`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Widening {
+    pub scale: f64,
+    pub exponent: f64,
+}
+
+impl Widening {
+    /// Get the number of children allowed after `sims` simulations.
+    fn width(&self, sims: u32) -> usize {
+        (self.scale * (sims.max(1) as f64).powf(self.exponent)).ceil() as usize
+    }
+}
+
+impl Display for Widening {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{},{}", self.scale, self.exponent)
+    }
+}
+
+impl FromStr for Widening {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid widening: {s}");
+        let (scale, exponent) = s.split_once(',').ok_or_else(invalid)?;
+        Ok(Widening {
+            scale: scale.parse().map_err(|_| invalid())?,
+            exponent: exponent.parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+impl Default for Widening {
+    fn default() -> Self {
+        Widening {
+            scale: 1.,
+            exponent: 0.5,
+        }
+    }
+}
+
+/// Configuration for MCTS.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MctsConfig {
+    /// Limit on the search.
+    pub budget: Budget,
+    /// Exploration constant used by UCB.
+    pub explore: f64,
+    /// Simulations at a leaf before it is expanded.
+    pub threshold: u32,
+    /// Maximum number of nodes in the tree, after which expansion stops.
+    pub max_nodes: Option<usize>,
+    /// Seed for the search's random number generator.
+    ///
+    /// Combined with an iteration budget, this makes searches reproducible.
+    /// Without the `entropy` feature, as on targets without an entropy
+    /// source such as `wasm32-unknown-unknown`, unseeded searches all use the
+    /// same fixed seed, so draw one from the host instead.
+    pub seed: Option<u64>,
+    /// Rollouts simulated from each selected leaf, all of which are
+    /// backpropagated.
+    ///
+    /// They run concurrently with [`Hooks::parallel`].
+    pub rollouts: usize,
+    /// Leaves valued together by an oracle from [`Hooks::oracle`].
+    ///
+    /// While a batch is collected, a virtual loss is applied along the path
+    /// to each pending leaf so the next selections explore elsewhere.
+    pub batch: usize,
+    /// Equivalence parameter of RAVE, in simulations, such as 300.
+    ///
+    /// All-moves-as-first statistics gathered from every playout are blended
+    /// with UCT values, weighted by a beta which decays as a node is visited,
+    /// so that both count equally after this many simulations.
+    pub rave: Option<f64>,
+    /// Temperature of the MAST rollout policy.
+    ///
+    /// When set, rollouts favour turns with high average win rates across the
+    /// search via softmax, instead of choosing uniformly.
+    pub mast: Option<f64>,
+    /// Progressive widening for games with large branching factors.
+    pub widening: Option<Widening>,
+    /// Weight of progressive bias from heuristic evaluations, such as 1.
+    ///
+    /// Each child's evaluation for the player moving into it is added to its
+    /// priority, decaying as `1 / (sims + 1)`, so it guides early exploration
+    /// but not converged statistics. Needs an evaluation from
+    /// [`Hooks::evaluate`].
+    pub bias: Option<f64>,
+    /// Weight of implicit minimax backups against the win rate, such as 0.3.
+    ///
+    /// Each node also keeps a minimax value of heuristic evaluations, backed
+    /// up from its children as they are expanded, which helps in tactical
+    /// games where short forced lines are easy to miss by sampling alone.
+    /// Needs an evaluation from [`Hooks::evaluate`].
+    pub minimax: Option<f64>,
+    /// Policy used to select children.
+    pub selection: Selection,
+    /// First play urgency, the priority given to unvisited children.
+    ///
+    /// By default unvisited children always come first, so every sibling is
+    /// sampled before any is revisited. With PUCT, this replaces their value.
+    pub fpu: Option<f64>,
+    /// Prove wins and losses from terminal positions up the tree.
+    ///
+    /// Proven wins are played immediately and proven losses avoided, rather
+    /// than relying on visit counts.
+    pub solver: bool,
+    /// Maximum turns played in a rollout before scoring the position.
+    ///
+    /// Needs an evaluation from [`Hooks::evaluate`].
+    pub cutoff: Option<usize>,
+    /// Dirichlet noise mixed into the priors of the root's children.
+    ///
+    /// Priors are used by PUCT selection and to order progressive widening,
+    /// so noise varies the openings explored in self-play.
+    pub noise: Option<Noise>,
+    /// Expand one untried child per visit instead of every child at once.
+    ///
+    /// Wide games then only pay to play the turns search actually reaches.
+    /// Untried turns are expanded in random order, or by prior with a policy.
+    pub lazy: bool,
+    /// Clock timing the search, instead of [`Instant`].
+    ///
+    /// `Instant` panics on `wasm32-unknown-unknown`, so browsers should read
+    /// `performance.now()` here. Searching on the calling thread with an
+    /// iteration budget, as by [`run_with`], needs neither a timer nor
+    /// threads. Timers are not recorded in search metadata.
+    pub timer: Option<Timer>,
+}
+
+impl MctsConfig {
+    /// Create the random number generator for a search.
+    ///
+    /// Seeded from `seed` if set, otherwise from entropy.
+    pub(crate) fn rng(&self) -> StdRng {
+        util::rng(self.seed)
+    }
+}
+
+impl Default for MctsConfig {
+    fn default() -> Self {
+        MctsConfig {
+            budget: Budget::default(),
+            explore: EXPLORE,
+            threshold: THRESHOLD,
+            max_nodes: None,
+            seed: None,
+            rollouts: 1,
+            batch: 1,
+            rave: None,
+            mast: None,
+            widening: None,
+            bias: None,
+            minimax: None,
+            selection: Selection::default(),
+            fpu: None,
+            solver: false,
+            cutoff: None,
+            noise: None,
+            lazy: false,
+            timer: None,
+        }
+    }
+}
+
+/// A hook adjusting backpropagated rewards using signals from the playout.
+///
+/// Shapers are named so that shaped searches can be recorded in
+/// [`SearchMeta`](crate::debug::SearchMeta) and reproduced.
+pub trait RewardShaper<G: Game>: Debug + Send + Sync {
+    /// Get the name recorded in search metadata, without whitespace.
+    fn name(&self) -> String;
+
+    /// Adjust the reward for the player who moved into `state`.
+    ///
+    /// The unshaped reward is 1 for a win and 0 otherwise.
+    fn shape(&self, reward: f64, state: &G, playout: &Playout<G>) -> f64;
+}
+
+/// A finished playout, as seen by a [`RewardShaper`].
+#[derive(Debug)]
+pub struct Playout<'a, G: Game> {
+    /// Position the playout started from.
+    pub start: &'a G,
+    /// Terminal position reached.
+    pub end: &'a G,
+    /// Number of turns played.
+    pub plies: usize,
+    /// Winner of the playout.
+    pub winner: Option<&'a G::Player>,
+}
+
+/// Shaper which discounts wins by a factor per ply, preferring faster wins.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Discount(pub f64);
+
+impl<G: Game> RewardShaper<G> for Discount {
+    fn name(&self) -> String {
+        format!("discount:{}", self.0)
+    }
+
+    fn shape(&self, reward: f64, _: &G, playout: &Playout<G>) -> f64 {
+        reward * self.0.powi(playout.plies as i32)
+    }
+}
+
+/// A policy choosing turns during rollouts.
+///
+/// Light domain knowledge, such as preferring captures, usually makes
+/// rollouts far more informative than uniformly random play.
+pub trait RolloutPolicy<G: Game>: Debug + Send + Sync {
+    /// Choose the index of the turn to play from `turns`, which is never empty.
+    fn choose(&self, game: &G, turns: &[G::Turn], rng: &mut dyn RngCore) -> usize;
+}
+
+/// A model giving prior probabilities of turns and the value of positions,
+/// such as a neural network.
+pub trait Oracle<G: Game>: Debug + Send + Sync {
+    /// Infer prior probabilities of turns from a position, along with its
+    /// value for the player to move from -1 for a loss to 1 for a win.
+    fn infer(&self, game: &G) -> (Vec<(G::Turn, f32)>, f32);
+
+    /// Infer priors and values for a batch of positions.
+    ///
+    /// Defaults to inferring each position in turn; models which are faster
+    /// in batches should override this.
+    fn infer_batch(&self, games: &[&G]) -> Vec<Inference<G>> {
+        games.iter().map(|game| self.infer(game)).collect()
+    }
+}
+
+/// Priors of turns and the value of a position, as inferred by an [`Oracle`].
+pub type Inference<G> = (Vec<(<G as Game>::Turn, f32)>, f32);
+
+/// Dirichlet noise mixed into the priors at the root, to explore in
+/// self-play.
+///
+/// Formats as `<alpha>,<fraction>`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Noise {
+    /// Concentration of the Dirichlet distribution.
+    pub alpha: f64,
+    /// Fraction of each prior replaced by noise.
+    pub fraction: f64,
+}
+
+impl Noise {
+    /// Mix noise into priors.
+    fn apply<R: Rng + ?Sized>(&self, priors: &mut [f64], rng: &mut R) {
+        let samples: Vec<_> = priors.iter().map(|_| gamma(rng, self.alpha)).collect();
+        let total: f64 = samples.iter().sum();
+        if total <= 0. {
+            return;
+        }
+        for (prior, sample) in priors.iter_mut().zip(samples) {
+            *prior = (1. - self.fraction) * *prior + self.fraction * sample / total;
+        }
+    }
+}
+
+impl Display for Noise {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{},{}", self.alpha, self.fraction)
+    }
+}
+
+impl FromStr for Noise {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid noise: {s}");
+        let (alpha, fraction) = s.split_once(',').ok_or_else(invalid)?;
+        Ok(Noise {
+            alpha: alpha.parse().map_err(|_| invalid())?,
+            fraction: fraction.parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+impl Default for Noise {
+    fn default() -> Self {
+        Noise {
+            alpha: 0.3,
+            fraction: 0.25,
         }
+    }
+}
 
-        // Simulate at `leaf`
-        let winner = tree[leaf].simulate();
+/// Rollout policy which chooses turns uniformly at random.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Uniform;
 
-        // Backpropagate the winner
-        tree.backprop(leaf, winner);
+impl<G: Game> RolloutPolicy<G> for Uniform {
+    fn choose(&self, _: &G, turns: &[G::Turn], rng: &mut dyn RngCore) -> usize {
+        rng.gen_range(0..turns.len())
     }
+}
+
+/// Run MCTS to select a turn.
+pub fn run<G: Game>(game: &G) -> G::Turn {
+    run_with(game, &MctsConfig::default())
+}
+
+/// Run MCTS with a configuration to select a turn.
+pub fn run_with<G: Game>(game: &G, config: &MctsConfig) -> G::Turn {
+    let mut lines = grow(game, config).lines();
+
+    // Play most simulated node
+    lines.swap_remove(0).pv.swap_remove(0)
+}
 
-    // Find most simulated node
+/// Run MCTS to estimate the win probability of each player.
+///
+/// Estimates come from the most simulated root turn, for the player to move
+/// and the player replying to them. Ties count towards the player to move.
+/// Finished games give the winner a probability of 1.
+pub fn evaluate<G: Game>(game: &G, config: &MctsConfig) -> Vec<(G::Player, f64)> {
+    if game.over() {
+        return game
+            .winner()
+            .map(|player| (player, 1.))
+            .into_iter()
+            .collect();
+    }
+    let tree = survey(game, config);
     let root = &tree[tree.root];
-    debug!("idx: sims, wins%, priority");
-    let best = &tree[*root
+    let best = root
         .children
         .iter()
-        .map(|idx| (idx, &tree[*idx]))
-        .inspect(|(idx, node)| {
-            debug!(
-                "{:03}: {:4}, {:4.1}%, {:.6}",
-                idx,
-                node.sims,
-                100. * (node.wins as f64) / (node.sims as f64),
-                node.priority(tree[node.parent].sims),
-            )
+        .copied()
+        .max_by_key(|&idx| tree[idx].sims)
+        .unwrap();
+    let rate = tree[best].wins / tree[best].sims.max(1) as f64;
+
+    let mut players = vec![(game.player(), rate)];
+    let next = tree.cold[best].player.clone();
+    if next != game.player() {
+        players.push((next, 1. - rate));
+    }
+    players
+}
+
+/// Run MCTS with a configuration, then sample a turn by visit count.
+///
+/// See [`sample`] for how `temperature` is used.
+pub fn run_sampled<G: Game>(game: &G, config: &MctsConfig, temperature: f64) -> G::Turn {
+    let mut tree = grow(game, config);
+    let visits: Vec<_> = tree
+        .root_stats()
+        .into_iter()
+        .map(|(turn, sims, _)| (turn, sims))
+        .collect();
+    sample(&visits, temperature, &mut tree.rng)
+}
+
+/// Sample a turn with probability proportional to `visits^(1/temperature)`.
+///
+/// A temperature of 1 samples in proportion to visits, higher temperatures
+/// approach uniform, and a temperature of 0 plays the most visited turn.
+pub fn sample<T: Clone, R: Rng + ?Sized>(visits: &[(T, u32)], temperature: f64, rng: &mut R) -> T {
+    let most = visits
+        .iter()
+        .map(|&(_, sims)| sims)
+        .max()
+        .unwrap_or_default();
+    if temperature <= 0. || most == 0 {
+        return visits
+            .iter()
+            .find(|&&(_, sims)| sims == most)
+            .unwrap()
+            .0
+            .clone();
+    }
+    // Scale by the most visits to keep weights finite
+    visits
+        .choose_weighted(rng, |&(_, sims)| {
+            (sims as f64 / most as f64).powf(1. / temperature)
         })
-        .max_by(|(_, a), (_, b)| a.sims.partial_cmp(&b.sims).unwrap_or(Ordering::Equal))
         .unwrap()
-        .0];
+        .0
+        .clone()
+}
 
-    // Play most simulated node
-    best.action.clone().unwrap()
+/// A temperature schedule for sampling turns, by ply.
+///
+/// Uses the `start` temperature for the first `plies` plies, then the `end`
+/// temperature, as in AlphaZero self-play.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Schedule {
+    /// Temperature of early plies.
+    pub start: f64,
+    /// Temperature after the first `plies` plies.
+    pub end: f64,
+    /// Number of plies to use the `start` temperature.
+    pub plies: usize,
 }
 
-/// The game tree from the current position.
-#[derive(Debug)]
-struct Tree<G: Game> {
-    arena: Vec<Node<G>>,
-    root: usize,
+impl Schedule {
+    /// Create a schedule with a constant temperature.
+    pub fn constant(temperature: f64) -> Schedule {
+        Schedule {
+            start: temperature,
+            end: temperature,
+            plies: 0,
+        }
+    }
+
+    /// Get the temperature at a ply.
+    pub fn temperature(&self, ply: usize) -> f64 {
+        if ply < self.plies {
+            self.start
+        } else {
+            self.end
+        }
+    }
 }
 
-impl<G: Game> Tree<G> {
-    /// Create a new Tree initialized with a root.
-    fn new(state: Box<G>) -> Tree<G> {
-        Tree {
-            arena: vec![Node::new(0, usize::MAX, state, None)],
-            root: 0,
+impl Default for Schedule {
+    fn default() -> Self {
+        Schedule {
+            start: 1.,
+            end: 0.,
+            plies: 10,
         }
     }
+}
 
-    /// Explore the game tree.
-    fn select(&self) -> usize {
-        let mut node = &self[self.root]; // start at the root
+/// Snapshot of a search in progress.
+#[derive(Clone, Debug)]
+pub struct SearchProgress<G: Game> {
+    /// Most simulated root turn so far.
+    pub best: G::Turn,
+    /// Simulations run from the root so far.
+    pub sims: u32,
+    /// Time spent searching so far.
+    pub elapsed: Duration,
+}
 
-        // Loop until `node` has no children
-        while !node.children.is_empty() {
-            // Get the child with the highest priority
-            trace!("idx: priority");
-            node = &self[*node
-                .children
+/// Run MCTS with a configuration to select a turn, reporting progress.
+///
+/// `progress` is called about every 100ms while searching and once more when
+/// the search finishes, so long searches can show what they're thinking.
+pub fn run_with_progress<G: Game>(
+    game: &G,
+    config: &MctsConfig,
+    mut progress: impl FnMut(&SearchProgress<G>),
+) -> G::Turn {
+    let mut tree = Tree::new(Box::new(game.clone()), config.clone());
+    tree.grow_with(&mut progress);
+
+    // Play most simulated node
+    tree.lines().swap_remove(0).pv.swap_remove(0)
+}
+
+/// Statistics of a search along with the turn it chose.
+#[derive(Clone, Debug)]
+pub struct SearchReport<G: Game> {
+    /// Chosen turn.
+    pub turn: G::Turn,
+    /// Simulations and win rate of each root turn, best first.
+    pub turns: Vec<(G::Turn, u32, f64)>,
+    /// Simulations run from the root.
+    pub sims: u32,
+    /// Nodes in the tree.
+    pub nodes: usize,
+    /// Approximate bytes used by the tree.
+    pub memory: usize,
+    /// Time spent searching.
+    pub elapsed: Duration,
+}
+
+/// Run MCTS with a configuration and report its statistics.
+pub fn analyze<G: Game>(game: &G, config: &MctsConfig) -> SearchReport<G> {
+    let start = Stopwatch::start(config);
+    let tree = survey(game, config);
+    let lines = tree.lines();
+    SearchReport {
+        turn: lines[0].pv[0].clone(),
+        turns: lines
+            .iter()
+            .map(|line| {
+                let rate = line.wins / line.sims.max(1) as f64;
+                (line.pv[0].clone(), line.sims, rate)
+            })
+            .collect(),
+        sims: tree[tree.root].sims,
+        nodes: tree.len(),
+        memory: tree.memory_usage(),
+        elapsed: start.elapsed(),
+    }
+}
+
+/// Visits of each cell of a grid game's root turns.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Heatmap {
+    /// Number of rows in the grid.
+    pub rows: usize,
+    /// Number of columns in the grid.
+    pub cols: usize,
+    /// Simulations of the turns on each cell, row by row.
+    pub visits: Vec<u32>,
+}
+
+impl Heatmap {
+    /// Get the simulations of turns on a cell.
+    pub fn get(&self, row: usize, col: usize) -> u32 {
+        self.visits[row * self.cols + col]
+    }
+
+    /// Format the heatmap as JSON.
+    ///
+    /// The object holds `rows`, `cols`, and `visits` as an array of rows,
+    /// such as `{"rows":1,"cols":2,"visits":[[10,90]]}`.
+    pub fn to_json(&self) -> String {
+        let rows: Vec<_> = self
+            .visits
+            .chunks(self.cols.max(1))
+            .map(|row| {
+                let row: Vec<_> = row.iter().map(ToString::to_string).collect();
+                format!("[{}]", row.join(","))
+            })
+            .collect();
+        format!(
+            r#"{{"rows":{},"cols":{},"visits":[{}]}}"#,
+            self.rows,
+            self.cols,
+            rows.join(",")
+        )
+    }
+}
+
+impl Display for Heatmap {
+    /// Shade each cell from ` ` for no visits to `@` for the most visited.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const SHADES: &[u8] = b" .:-=+*#%@";
+        let max = self.visits.iter().copied().max().unwrap_or_default().max(1);
+        for row in self.visits.chunks(self.cols.max(1)) {
+            let line: String = row
                 .iter()
-                .map(|idx| {
-                    (
-                        idx,
-                        Node::priority(&self[*idx], self[self[*idx].parent].sims),
-                    )
+                .map(|&n| {
+                    let shade = (n as usize * (SHADES.len() - 1)).div_ceil(max as usize);
+                    SHADES[shade] as char
                 })
-                .inspect(|(idx, priority)| trace!("{:03}: {:.6}", idx, priority))
-                .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(Ordering::Equal))
-                .unwrap()
-                .0];
-            trace!("{:03} selected", node.idx);
+                .collect();
+            writeln!(f, "{line}")?;
         }
+        Ok(())
+    }
+}
 
-        node.idx
+/// Run MCTS and map the simulations of each root turn onto the grid.
+///
+/// Turns without a cell are left out, and turns sharing a cell add up.
+pub fn heatmap<G: TurnCoords>(game: &G, config: &MctsConfig) -> Heatmap {
+    let tree = survey(game, config);
+    let (rows, cols) = game.dimensions();
+    let mut visits = vec![0; rows * cols];
+    for &child in tree[tree.root].children.iter() {
+        let action = tree.cold[child].action.as_ref();
+        let cell = action.and_then(|turn| game.coords(turn));
+        if let Some((row, col)) = cell.filter(|&(row, col)| row < rows && col < cols) {
+            visits[row * cols + col] += tree[child].sims;
+        }
     }
+    Heatmap { rows, cols, visits }
+}
 
-    /// Expand a node to create children in the game tree.
-    fn expand(&mut self, idx: usize) {
-        // Iterate through actions to create children
-        for action in self[idx].state.turns() {
-            // Clone state and play action
-            let mut state: G = *self[idx].state.clone();
-            state.play(action.clone());
-
-            // Add the new child
-            self.arena.push(Node::new(
-                self.arena.len(),
-                idx,
-                Box::new(state),
-                Some(action),
-            ));
-            // Parent stores index of child
-            let child = self.arena.last().unwrap().idx;
-            self[idx].children.push(child);
+/// Run MCTS and format the visited tree as a Graphviz DOT graph, up to
+/// `depth` plies from the root.
+///
+/// Each node is labelled with the turn leading to it, its simulations, and
+/// its win rate for the player who made that turn. Render the graph with
+/// `dot -Tsvg` to see why the engine prefers a turn.
+pub fn dot<G: Game>(game: &G, config: &MctsConfig, depth: usize) -> String {
+    let tree = survey(game, config);
+    tree.to_dot(tree.root, depth)
+}
+
+/// A policy giving prior probabilities of turns from a position.
+pub type PriorFn<G> = fn(&G) -> Vec<(<G as Game>::Turn, f32)>;
+
+/// Knowledge of a game guiding a search, beyond what [`Game`] provides.
+///
+/// Hooks supply what an [`MctsConfig`] cannot name, such as a heuristic,
+/// while the configuration chooses how the search uses them. By
+/// default there are none, and turns are compared by name.
+#[derive(Clone, Debug)]
+pub struct Hooks<G: Game> {
+    same: fn(&G::Turn, &G::Turn) -> bool,
+    evaluate: Option<fn(&G, &G::Player) -> f64>,
+    score: Option<fn(&G, &G::Player) -> f64>,
+    priors: Option<PriorFn<G>>,
+    oracle: Option<Arc<dyn Oracle<G>>>,
+    rollout: Option<Arc<dyn RolloutPolicy<G>>>,
+    memo: Option<fn() -> Memo<G>>,
+    shaper: Option<Arc<dyn RewardShaper<G>>>,
+    simulate: Option<Rollouts<G>>,
+    unplay: Option<fn(&mut G, &G::Turn)>,
+}
+
+impl<G: Game> Hooks<G> {
+    /// Create new Hooks, which supply nothing.
+    pub fn new() -> Hooks<G> {
+        Hooks {
+            same: |a, b| a.to_string() == b.to_string(),
+            evaluate: None,
+            score: None,
+            priors: None,
+            oracle: None,
+            rollout: None,
+            memo: None,
+            shaper: None,
+            simulate: None,
+            unplay: None,
         }
     }
 
-    /// Backpropagate the result of a simulation.
-    fn backprop(&mut self, mut idx: usize, winner: Option<G::Player>) {
-        let winner = winner.unwrap_or_else(|| self[self.root].state.player());
+    /// Compare turns with `same`, such as [`PartialEq::eq`], instead of by
+    /// name.
+    pub fn same(mut self, same: fn(&G::Turn, &G::Turn) -> bool) -> Hooks<G> {
+        self.same = same;
+        self
+    }
 
-        // Backpropagate until the root
-        let null = self[self.root].parent;
-        while idx != null {
-            let node = &mut self[idx];
+    /// Evaluate positions with the game's [`Evaluate`] heuristic.
+    ///
+    /// Evaluations score rollouts stopped at the
+    /// [`cutoff`](MctsConfig::cutoff), and are used by progressive
+    /// [`bias`](MctsConfig::bias) and [`minimax`](MctsConfig::minimax)
+    /// backups.
+    pub fn evaluate(mut self) -> Hooks<G>
+    where
+        G: Evaluate,
+    {
+        self.evaluate = Some(Evaluate::evaluate);
+        self
+    }
 
-            // Update statistics of node
-            // NOTE: The game state stores the next player, but in MCTS, each
-            //       node represents the current player.
-            if winner != node.state.player() {
-                node.wins += 1;
-            }
-            node.sims += 1;
+    /// Back up the [`Score`] of finished games.
+    ///
+    /// Terminal scores are backed up as bounds on the achievable margin,
+    /// which prune dominated children. Once the outcome is decided the engine
+    /// plays for the best margin, such as a larger win or a draw rather than
+    /// a loss.
+    ///
+    /// Single-player games use SP-MCTS instead, backing up the score of each
+    /// playout and adding a bonus for its deviation to the exploration term, so
+    /// that the expected score is maximized. The exploration constant should be
+    /// scaled to the range of scores.
+    pub fn score(mut self) -> Hooks<G>
+    where
+        G: Score,
+    {
+        self.score = Some(Score::score);
+        self
+    }
 
-            // Ascend to parent
-            idx = node.parent;
+    /// Give prior probabilities of turns with a policy.
+    ///
+    /// Turns it leaves out get none, and priors are normalized over the legal
+    /// turns. Priors guide [`Selection::Puct`].
+    pub fn priors(mut self, priors: PriorFn<G>) -> Hooks<G> {
+        self.priors = Some(priors);
+        self
+    }
+
+    /// Give priors and value leaves with an oracle.
+    ///
+    /// The oracle gives the priors of turns from each expanded node, and its
+    /// value replaces the rollout at each non-terminal leaf, as in AlphaZero
+    /// with [`Selection::Puct`]. Leaves are valued in batches of
+    /// [`batch`](MctsConfig::batch), and a threshold of 0 expands every leaf
+    /// on its second visit, once the oracle has valued it.
+    pub fn oracle(mut self, oracle: Arc<dyn Oracle<G>>) -> Hooks<G> {
+        self.oracle = Some(oracle);
+        self
+    }
+
+    /// Choose the turns of rollouts with a policy.
+    pub fn rollout(mut self, policy: Arc<dyn RolloutPolicy<G>>) -> Hooks<G> {
+        self.rollout = Some(policy);
+        self
+    }
+
+    /// Cache the outcomes of terminal positions, keyed by their hash.
+    ///
+    /// Playouts stop as soon as they reach a previously seen terminal
+    /// position, which pays off in games with many transpositions near the
+    /// end.
+    pub fn memo(mut self) -> Hooks<G>
+    where
+        G: Hash,
+    {
+        self.memo = Some(Memo::new);
+        self
+    }
+
+    /// Walk a single state through the tree instead of storing one in each
+    /// node.
+    ///
+    /// Turns are played on the way down and taken back with [`Reversible`]
+    /// on the way up, which saves memory and time when states are large.
+    pub fn reversible(mut self) -> Hooks<G>
+    where
+        G: Reversible,
+    {
+        self.unplay = Some(Reversible::unplay);
+        self
+    }
+
+    /// Adjust backpropagated rewards with a shaper.
+    pub fn shaper(mut self, shaper: Arc<dyn RewardShaper<G>>) -> Hooks<G> {
+        self.shaper = Some(shaper);
+        self
+    }
+
+    /// Get the name of the reward shaper, if any.
+    pub(crate) fn shaper_name(&self) -> Option<String> {
+        self.shaper.as_ref().map(|shaper| shaper.name())
+    }
+
+    /// Get the name of a hook which a configuration needs but is not
+    /// supplied, if any.
+    pub(crate) fn missing(&self, config: &MctsConfig) -> Option<&'static str> {
+        let evaluated = (config.cutoff, config.bias, config.minimax) != (None, None, None);
+        if evaluated && self.evaluate.is_none() {
+            return Some("evaluate");
         }
+        let guided = self.priors.is_some() || self.oracle.is_some();
+        if config.selection == Selection::Puct && !guided {
+            return Some("priors");
+        }
+        None
     }
-}
 
-impl<G: Game> Index<usize> for Tree<G> {
-    type Output = Node<G>;
+    /// Simulate the [`rollouts`](MctsConfig::rollouts) of each leaf
+    /// concurrently with rayon.
+    ///
+    /// The tree stays single-threaded, and the rollouts do not use the
+    /// [`memo`](Hooks::memo).
+    #[cfg(feature = "rayon")]
+    pub fn parallel(mut self) -> Hooks<G>
+    where
+        G: Send + Sync,
+        G::Turn: Send + Sync,
+        G::Player: Send + Sync,
+    {
+        self.simulate = Some(|node, seeds, rollout| {
+            seeds
+                .par_iter()
+                .map(|&seed| node.simulate(&mut StdRng::seed_from_u64(seed), None, rollout))
+                .collect()
+        });
+        self
+    }
+}
 
-    fn index(&self, idx: usize) -> &Self::Output {
-        &self.arena[idx]
+impl<G: Game> Default for Hooks<G> {
+    fn default() -> Self {
+        Hooks::new()
     }
 }
 
-impl<G: Game> IndexMut<usize> for Tree<G> {
-    fn index_mut(&mut self, idx: usize) -> &mut Self::Output {
-        &mut self.arena[idx]
+/// Run MCTS with a configuration and game-specific hooks to select a turn.
+pub fn run_with_hooks<G: Game>(game: &G, config: &MctsConfig, hooks: &Hooks<G>) -> G::Turn {
+    let mut tree = Tree::with_hooks(Box::new(game.clone()), config.clone(), hooks);
+    tree.grow();
+    if let Some(memo) = &tree.memo {
+        debug!("memo: {} positions, {} hits", memo.len(), memo.hits);
     }
+
+    // Play most simulated node
+    tree.lines().swap_remove(0).pv.swap_remove(0)
+}
+
+/// Run MCTS with PUCT selection guided by an oracle, as with
+/// [`Hooks::oracle`], and count the visits of each root turn.
+///
+/// Visit counts are the policy targets of AlphaZero-style training.
+pub fn oracle_visits<G: Game>(
+    game: &G,
+    config: &MctsConfig,
+    oracle: Arc<dyn Oracle<G>>,
+) -> Vec<(G::Turn, u32)>
+where
+    G::Turn: PartialEq,
+{
+    let config = MctsConfig {
+        selection: Selection::Puct,
+        ..config.clone()
+    };
+    let hooks = Hooks::new().oracle(oracle).same(PartialEq::eq);
+    let mut tree = Tree::with_hooks(Box::new(game.clone()), config, &hooks);
+    tree.grow();
+    tree.root_stats()
+        .into_iter()
+        .map(|(turn, sims, _)| (turn, sims))
+        .collect()
 }
 
-/// A single state in the game tree.
+/// Run MCTS with independent trees on `threads` threads to select a turn.
+///
+/// Each tree searches for the full budget, then root visit counts are merged
+/// to choose the turn. When seeded, thread `i` uses the seed plus `i`.
+pub fn run_parallel<G>(game: &G, config: &MctsConfig, threads: usize) -> G::Turn
+where
+    G: Game + Sync,
+    G::Turn: Send,
+{
+    let roots: Vec<_> = thread::scope(|scope| {
+        let handles: Vec<_> = (0..threads.max(1) as u64)
+            .map(|i| {
+                let config = MctsConfig {
+                    seed: config.seed.map(|seed| seed.wrapping_add(i)),
+                    ..config.clone()
+                };
+                scope.spawn(move || grow(game, &config).root_stats())
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect()
+    });
+
+    merge::<G>(roots)
+}
+
+/// A job run on a [`ParallelMcts`] thread.
+type Job = Box<dyn FnOnce() + Send>;
+
+/// A root-parallel MCTS agent which keeps its threads between searches.
+///
+/// [`run_parallel`] spawns a thread per tree on every call, whose startup
+/// cost dominates short time controls. Here the threads are spawned once
+/// and each search is handed to them. Dropping the agent waits for running
+/// searches to finish and stops its threads.
 #[derive(Debug)]
-struct Node<G: Game> {
-    // Position
-    idx: usize,
-    parent: usize,
-    children: Vec<usize>,
-    // State
-    state: Box<G>,
-    action: Option<G::Turn>,
-    // Statistics
-    wins: u32,
-    sims: u32,
+pub struct ParallelMcts {
+    jobs: Option<mpsc::Sender<Job>>,
+    handles: Vec<JoinHandle<()>>,
 }
 
-impl<G: Game> Node<G> {
-    /// Create a new Node.
-    fn new(idx: usize, parent: usize, state: Box<G>, action: Option<G::Turn>) -> Node<G> {
-        Node {
-            idx,
-            parent,
-            children: Vec::new(),
-            state,
-            action,
-            wins: 0,
-            sims: 0,
+impl ParallelMcts {
+    /// Create a new ParallelMcts with `threads` threads.
+    pub fn new(threads: usize) -> ParallelMcts {
+        let (jobs, queue) = mpsc::channel::<Job>();
+        let queue = Arc::new(Mutex::new(queue));
+        let handles = (0..threads.max(1))
+            .map(|_| {
+                let queue = queue.clone();
+                thread::spawn(move || loop {
+                    // Only hold the lock while waiting for a job
+                    let job = queue.lock().unwrap().recv();
+                    match job {
+                        // Keep the thread alive if a search panics
+                        Ok(job) => drop(panic::catch_unwind(AssertUnwindSafe(job))),
+                        Err(_) => return,
+                    }
+                })
+            })
+            .collect();
+        ParallelMcts {
+            jobs: Some(jobs),
+            handles,
         }
     }
 
-    /// Simulate the game from this node.
-    fn simulate(&self) -> Option<G::Player> {
-        // Create a copy of the current state to simulate
-        let mut state = self.state.clone();
+    /// Get the number of threads.
+    pub fn threads(&self) -> usize {
+        self.handles.len()
+    }
 
-        while !state.over() {
-            // Policy: select a random move
-            let action = state
-                .turns()
-                .choose(&mut rand::thread_rng())
-                .unwrap()
-                .clone();
-            state.play(action);
+    /// Run MCTS with an independent tree on each thread to select a turn.
+    ///
+    /// Searches match [`run_parallel`] with as many threads.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any of the searches panics.
+    pub fn run<G>(&self, game: &G, config: &MctsConfig) -> G::Turn
+    where
+        G: Game + Send + 'static,
+        G::Turn: Send,
+    {
+        let (results, roots) = mpsc::channel();
+        let jobs = self.jobs.as_ref().unwrap();
+        for i in 0..self.threads() as u64 {
+            let game = game.clone();
+            let config = MctsConfig {
+                seed: config.seed.map(|seed| seed.wrapping_add(i)),
+                ..config.clone()
+            };
+            let results = results.clone();
+            jobs.send(Box::new(move || {
+                let stats = grow(&game, &config).root_stats();
+                results.send((i, stats)).ok();
+            }))
+            .unwrap();
         }
+        drop(results);
 
-        state.winner()
+        // Merge in thread order so seeded searches repeat
+        let mut roots: Vec<_> = roots.iter().collect();
+        assert_eq!(roots.len(), self.threads(), "parallel search panicked");
+        roots.sort_by_key(|&(i, _)| i);
+        merge::<G>(roots.into_iter().map(|(_, stats)| stats).collect())
     }
+}
 
-    /// Calculate node priority
-    fn priority(&self, psims: u32) -> f64 {
-        // Extract UCB
-        let wins = self.wins as f64;
-        let sims = self.sims as f64;
-        let psims = psims as f64;
-        // Calculate UCB
-        let exploit = wins / sims;
-        let explore = EXPLORE * (psims.ln() / sims).sqrt();
-        // Return priority
-        match exploit + explore {
-            x if x.is_finite() => x,
-            _ => f64::INFINITY,
+impl Drop for ParallelMcts {
+    fn drop(&mut self) {
+        // Closing the queue stops each thread after its current job
+        drop(self.jobs.take());
+        for handle in self.handles.drain(..) {
+            handle.join().ok();
         }
     }
 }
 
-#[cfg(test)]
-mod tests {
-    #[test]
-    fn it_works() {
-        let result = 2 + 2;
-        assert_eq!(result, 4);
+/// Merge the root statistics of independent trees and pick the most
+/// simulated turn.
+///
+/// Trees may expand their children in different orders, or not at all, so
+/// children are matched by the turn they play.
+fn merge<G: Game>(roots: Vec<Vec<(G::Turn, u32, f64)>>) -> G::Turn {
+    // Merge visit counts of each root child
+    let mut names = Vec::new();
+    let mut merged: Vec<(G::Turn, u32, f64)> = Vec::new();
+    for (turn, more, won) in roots.into_iter().flatten() {
+        let name = turn.to_string();
+        match names.iter().position(|other| *other == name) {
+            Some(idx) => {
+                merged[idx].1 += more;
+                merged[idx].2 += won;
+            }
+            None => {
+                names.push(name);
+                merged.push((turn, more, won));
+            }
+        }
+    }
+    for (turn, sims, wins) in &merged {
+        debug!("{}: {:5}, {:4.1}%", turn, sims, 100. * wins / *sims as f64);
+    }
+
+    // Play most simulated turn
+    merged
+        .into_iter()
+        .max_by_key(|&(_, sims, _)| sims)
+        .unwrap()
+        .0
+}
+
+/// Statistics for the line starting at a root child.
+#[derive(Clone, Debug)]
+pub(crate) struct Line<G: Game> {
+    /// Principal variation, starting with the root child's turn.
+    pub(crate) pv: Vec<G::Turn>,
+    pub(crate) sims: u32,
+    pub(crate) wins: f64,
+}
+
+/// Run MCTS and collect the line of every root child, most simulated first.
+///
+/// Also returns the number of nodes in the tree.
+#[cfg(any(feature = "batch", feature = "record", test))]
+pub(crate) fn search<G: Game>(game: &G, config: &MctsConfig) -> (Vec<Line<G>>, usize) {
+    let tree = survey(game, config);
+    (tree.lines(), tree.len())
+}
+
+/// Run MCTS and collect the root lines along with the tree formatted up to
+/// `depth` plies.
+pub(crate) fn trace<G: Game>(
+    game: &G,
+    config: &MctsConfig,
+    hooks: &Hooks<G>,
+    depth: usize,
+) -> (Vec<Line<G>>, String) {
+    let mut tree = Tree::with_hooks(Box::new(game.clone()), config.clone(), hooks);
+    tree.grow();
+    let mut dump = String::new();
+    tree.dump(tree.root, depth, 0, &mut dump);
+    (tree.lines(), dump)
+}
+
+/// Grow a game tree from the current position within the budget.
+fn grow<G: Game>(game: &G, config: &MctsConfig) -> Tree<G> {
+    let mut tree = Tree::new(Box::new(game.clone()), config.clone());
+    tree.grow();
+    tree
+}
+
+/// Grow a game tree from the current position within the budget, searching
+/// even a forced turn so that its statistics can be reported.
+fn survey<G: Game>(game: &G, config: &MctsConfig) -> Tree<G> {
+    let mut tree = Tree::new(Box::new(game.clone()), config.clone());
+    tree.hurry = false;
+    tree.grow();
+    tree
+}
+
+/// An MCTS agent which keeps its tree between turns.
+///
+/// After each turn played, by either player, the tree is re-rooted at the
+/// resulting child so the next search continues from its statistics.
+#[derive(Debug)]
+pub struct Mcts<G: Game> {
+    config: MctsConfig,
+    tree: Option<Tree<G>>,
+    ponder: Option<Ponder<G>>,
+}
+
+impl<G: Game + PartialEq> Mcts<G>
+where
+    G::Turn: PartialEq,
+{
+    /// Create a new Mcts agent.
+    pub fn new(config: MctsConfig) -> Mcts<G> {
+        Mcts {
+            config,
+            tree: None,
+            ponder: None,
+        }
+    }
+
+    /// Get the configuration.
+    pub fn config(&self) -> &MctsConfig {
+        &self.config
+    }
+
+    /// Search the position to select a turn, then advance past it.
+    ///
+    /// The existing tree is reused if its root is the same as `game`;
+    /// otherwise a new tree is started.
+    pub fn run(&mut self, game: &G) -> G::Turn {
+        self.stop();
+        let reuse = matches!(
+            &self.tree,
+            Some(tree) if tree.cold[tree.root].state() == game
+        );
+        if !reuse {
+            self.tree = Some(Tree::new(Box::new(game.clone()), self.config.clone()));
+        }
+
+        // Continue growing the tree
+        let tree = self.tree.as_mut().unwrap();
+        tree.grow();
+        let turn = tree.lines().swap_remove(0).pv.swap_remove(0);
+
+        self.advance(&turn);
+        turn
+    }
+
+    /// Re-root the tree at the child reached by a played turn.
+    ///
+    /// Nodes no longer reachable from the new root are freed. The tree is
+    /// discarded if the turn was never expanded.
+    pub fn advance(&mut self, turn: &G::Turn) {
+        if let Some(predicted) = self.stop() {
+            // Keep the pondered tree on a hit
+            if predicted == *turn {
+                if let Some(tree) = &mut self.tree {
+                    tree.compact();
+                }
+                return;
+            }
+            // Otherwise return to the position before the predicted reply
+            if let Some(tree) = &mut self.tree {
+                tree.root = tree[tree.root].parent;
+            }
+        }
+        let Some(tree) = &mut self.tree else {
+            return;
+        };
+        let child = tree[tree.root]
+            .children
+            .iter()
+            .copied()
+            .find(|&child| tree.cold[child].action.as_ref() == Some(turn));
+        match child {
+            Some(child) => {
+                tree.root = child;
+                tree.compact();
+            }
+            None => self.tree = None,
+        }
+    }
+
+    /// Get the turn predicted for the opponent while pondering.
+    pub fn pondering(&self) -> Option<&G::Turn> {
+        self.ponder.as_ref().map(|ponder| &ponder.turn)
+    }
+
+    /// Get the number of nodes in the tree.
+    ///
+    /// The tree is unavailable while pondering, so this is 0.
+    pub fn len(&self) -> usize {
+        self.tree.as_ref().map_or(0, Tree::len)
+    }
+
+    /// Get the approximate bytes used by the tree.
+    ///
+    /// Like [`len`](Mcts::len), this is 0 while pondering.
+    pub fn memory_usage(&self) -> usize {
+        self.tree.as_ref().map_or(0, Tree::memory_usage)
+    }
+
+    /// Check if there is no tree.
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_none() && self.ponder.is_none()
+    }
+
+    /// Discard the tree, stopping any pondering.
+    pub fn reset(&mut self) {
+        self.tree = None;
+        self.ponder = None;
+    }
+
+    /// Stop pondering and take back the tree.
+    ///
+    /// Returns the predicted turn if the agent was pondering.
+    fn stop(&mut self) -> Option<G::Turn> {
+        let mut ponder = self.ponder.take()?;
+        ponder.stop.store(true, atomic::Ordering::Relaxed);
+        self.tree = ponder.handle.take().map(|handle| handle.join().unwrap());
+        Some(ponder.turn.clone())
+    }
+}
+
+impl<G> Mcts<G>
+where
+    G: Game + PartialEq + Send + 'static,
+    G::Turn: PartialEq + Send,
+    G::Player: Send,
+{
+    /// Search the opponent's predicted reply in the background.
+    ///
+    /// Call this after [`run`](Mcts::run). The opponent is predicted to play
+    /// their most simulated turn, and the resulting position is searched
+    /// until the agent [advances](Mcts::advance) or runs again. On a hit,
+    /// the next search continues from the pondered tree; on a miss, the tree
+    /// falls back to the statistics from the previous search.
+    pub fn ponder(&mut self) {
+        self.stop();
+        let Some(mut tree) = self.tree.take() else {
+            return;
+        };
+        let root = &tree[tree.root];
+        let best = root
+            .children
+            .iter()
+            .copied()
+            .max_by_key(|&idx| tree[idx].sims);
+        let Some(best) = best.filter(|_| !tree.cold[tree.root].state().over()) else {
+            self.tree = Some(tree);
+            return;
+        };
+        let turn = tree.cold[best].action.clone().unwrap();
+        tree.root = best;
+
+        // Grow in small batches until stopped
+        let stop = Arc::new(AtomicBool::new(false));
+        let flag = stop.clone();
+        let handle = thread::spawn(move || {
+            let budget = tree.config.budget;
+            tree.config.budget = Budget::Iterations(PONDER);
+            while !flag.load(atomic::Ordering::Relaxed) {
+                let sims = tree[tree.root].sims;
+                tree.grow();
+                // Stop early if there's nothing left to search
+                if tree[tree.root].sims == sims {
+                    break;
+                }
+            }
+            tree.config.budget = budget;
+            tree
+        });
+        self.ponder = Some(Ponder {
+            turn,
+            stop,
+            handle: Some(handle),
+        });
+    }
+}
+
+/// A background search on the opponent's predicted reply.
+///
+/// Dropping it signals the search to stop.
+#[derive(Debug)]
+struct Ponder<G: Game> {
+    turn: G::Turn,
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<Tree<G>>>,
+}
+
+impl<G: Game> Drop for Ponder<G> {
+    fn drop(&mut self) {
+        self.stop.store(true, atomic::Ordering::Relaxed);
+    }
+}
+
+/// Table of outcomes for terminal positions, keyed by position hash.
+#[derive(Debug)]
+struct Memo<G: Game> {
+    hash: fn(&G) -> u64,
+    outcomes: HashMap<u64, Option<G::Player>>,
+    hits: usize,
+}
+
+impl<G: Game> Memo<G> {
+    /// Create a new, empty Memo.
+    fn new() -> Memo<G>
+    where
+        G: Hash,
+    {
+        Memo {
+            hash: |game| {
+                let mut hasher = DefaultHasher::new();
+                game.hash(&mut hasher);
+                hasher.finish()
+            },
+            outcomes: HashMap::new(),
+            hits: 0,
+        }
+    }
+
+    /// Get the number of positions stored.
+    fn len(&self) -> usize {
+        self.outcomes.len()
+    }
+}
+
+/// The result of a playout.
+#[derive(Debug)]
+struct Outcome<G: Game> {
+    end: G,
+    plies: usize,
+    winner: Option<G::Player>,
+    /// Turns played along with their players, if recorded.
+    moves: Vec<(G::Player, G::Turn)>,
+    /// Whether the playout was cut off before the end of the game.
+    cutoff: bool,
+}
+
+impl<G: Game> Outcome<G> {
+    /// Create the outcome of a position which is already decided.
+    fn decided(end: G, winner: Option<G::Player>) -> Outcome<G> {
+        Outcome {
+            end,
+            plies: 0,
+            winner,
+            moves: Vec::new(),
+            cutoff: false,
+        }
+    }
+}
+
+/// Options shared by every rollout in a search.
+#[derive(Debug)]
+struct Rollout<'a, G: Game> {
+    /// Policy choosing turns, if not uniform.
+    policy: Option<&'a dyn RolloutPolicy<G>>,
+    /// Statistics for the MAST policy, if enabled.
+    mast: Option<&'a Mast>,
+    /// Whether to record turns played.
+    record: bool,
+    /// Turns after which to stop.
+    cutoff: Option<usize>,
+}
+
+/// Simulate from a node once per seed.
+type Rollouts<G> = fn(&Cold<G>, &[u64], &Rollout<G>) -> Vec<Outcome<G>>;
+
+/// A policy giving prior probabilities for PUCT.
+#[derive(Debug)]
+struct Policy<G: Game> {
+    /// Get prior probabilities of turns from a position.
+    priors: PriorFn<G>,
+    /// Check if two turns are the same.
+    same: fn(&G::Turn, &G::Turn) -> bool,
+}
+
+/// An oracle giving priors and values for PUCT.
+#[derive(Debug)]
+struct Network<G: Game> {
+    /// Infer priors and values from a position.
+    oracle: Arc<dyn Oracle<G>>,
+    /// Check if two turns are the same.
+    same: fn(&G::Turn, &G::Turn) -> bool,
+    /// Leaves valued together.
+    batch: usize,
+}
+
+/// Settings for progressive bias.
+#[derive(Debug)]
+struct Bias<G: Game> {
+    /// Evaluate a position for a player.
+    evaluate: fn(&G, &G::Player) -> f64,
+    /// Scale of the prior term.
+    weight: f64,
+}
+
+/// Settings for implicit minimax backups.
+#[derive(Debug)]
+struct Minimax<G: Game> {
+    /// Evaluate a position for a player.
+    evaluate: fn(&G, &G::Player) -> f64,
+    /// Weight of the minimax value against the win rate.
+    alpha: f64,
+}
+
+/// Average win rate of each turn across the search, for MAST rollouts.
+///
+/// Turns are keyed by their display names along with their player.
+#[derive(Debug)]
+struct Mast {
+    temperature: f64,
+    stats: HashMap<(String, String), (f64, u32)>,
+}
+
+impl Mast {
+    /// Create a new, empty Mast.
+    fn new(temperature: f64) -> Mast {
+        Mast {
+            temperature,
+            stats: HashMap::new(),
+        }
+    }
+
+    /// Choose a turn by softmax over average win rates.
+    ///
+    /// Unseen turns are treated optimistically as wins.
+    fn choose<'a, P, T, R>(&self, player: &P, turns: &'a [T], rng: &mut R) -> &'a T
+    where
+        P: Display,
+        T: Display,
+        R: Rng + ?Sized,
+    {
+        let player = player.to_string();
+        turns
+            .choose_weighted(rng, |turn| {
+                let rate = match self.stats.get(&(player.clone(), turn.to_string())) {
+                    Some(&(wins, sims)) => wins / sims as f64,
+                    None => 1.,
+                };
+                (rate / self.temperature).exp()
+            })
+            .unwrap()
+    }
+
+    /// Credit the turns of a playout given its winner.
+    fn update<G: Game>(&mut self, winner: &G::Player, moves: &[(G::Player, G::Turn)]) {
+        for (player, turn) in moves {
+            let stats = self
+                .stats
+                .entry((player.to_string(), turn.to_string()))
+                .or_default();
+            stats.0 += if player == winner { 1. } else { 0. };
+            stats.1 += 1;
+        }
+    }
+}
+
+/// Settings for rapid action value estimation.
+#[derive(Debug)]
+struct Rave<G: Game> {
+    /// Check if two turns are the same.
+    same: fn(&G::Turn, &G::Turn) -> bool,
+    /// Simulations at which UCT and AMAF values are weighted equally.
+    k: f64,
+}
+
+/// The game tree from the current position.
+#[derive(Debug)]
+struct Tree<G: Game> {
+    arena: Arena<Node>,
+    cold: Arena<Cold<G>>,
+    root: u32,
+    config: MctsConfig,
+    rng: StdRng,
+    memo: Option<Memo<G>>,
+    shaper: Option<Arc<dyn RewardShaper<G>>>,
+    simulate: Option<Rollouts<G>>,
+    rave: Option<Rave<G>>,
+    mast: Option<Mast>,
+    bias: Option<Bias<G>>,
+    minimax: Option<Minimax<G>>,
+    policy: Option<Policy<G>>,
+    network: Option<Network<G>>,
+    score: Option<fn(&G, &G::Player) -> f64>,
+    solo: Option<fn(&G, &G::Player) -> f64>,
+    evaluate: Option<fn(&G, &G::Player) -> f64>,
+    rollout: Option<Arc<dyn RolloutPolicy<G>>>,
+    // Take back a turn, walking a single state through the tree
+    unplay: Option<fn(&mut G, &G::Turn)>,
+    // Node holding the walked state
+    at: u32,
+    // Whether to return at once when the root has a single turn
+    hurry: bool,
+}
+
+impl<G: Game> Tree<G> {
+    /// Create a new Tree initialized with a root.
+    fn new(state: Box<G>, config: MctsConfig) -> Tree<G> {
+        Tree::with_hooks(state, config, &Hooks::new())
+    }
+
+    /// Create a new Tree initialized with a root, searching with hooks as
+    /// configured.
+    fn with_hooks(state: Box<G>, config: MctsConfig, hooks: &Hooks<G>) -> Tree<G> {
+        let rave = config.rave;
+        let batch = config.batch.max(1);
+        let rng = config.rng();
+        let mast = config.mast.map(Mast::new);
+        if hooks.evaluate.is_none()
+            && (config.cutoff, config.bias, config.minimax) != (None, None, None)
+        {
+            warn!("cutoff, bias, and minimax need an evaluation");
+        }
+        // A lone player maximizes their score rather than bounding it
+        let (score, solo) = match state.players() {
+            1 => (None, hooks.score),
+            _ => (hooks.score, None),
+        };
+        let bias = hooks
+            .evaluate
+            .zip(config.bias)
+            .map(|(evaluate, weight)| Bias { evaluate, weight });
+        let minimax = hooks
+            .evaluate
+            .zip(config.minimax)
+            .map(|(evaluate, alpha)| Minimax { evaluate, alpha });
+        Tree {
+            arena: Arena(vec![Node::new(VACANT)]),
+            cold: Arena(vec![Cold::new(state, None)]),
+            root: 0,
+            config,
+            rng,
+            memo: hooks.memo.map(|memo| memo()),
+            shaper: hooks.shaper.clone(),
+            simulate: hooks.simulate,
+            rave: rave.map(|k| Rave {
+                same: hooks.same,
+                k,
+            }),
+            mast,
+            bias,
+            minimax,
+            policy: hooks.priors.map(|priors| Policy {
+                priors,
+                same: hooks.same,
+            }),
+            network: hooks.oracle.clone().map(|oracle| Network {
+                oracle,
+                same: hooks.same,
+                batch,
+            }),
+            score,
+            solo,
+            evaluate: hooks.evaluate,
+            rollout: hooks.rollout.clone(),
+            unplay: hooks.unplay,
+            at: 0,
+            hurry: true,
+        }
+    }
+
+    /// Grow the tree from its root within the budget.
+    fn grow(&mut self) {
+        self.grow_with(&mut |_| ());
+    }
+
+    /// Grow the tree, calling `progress` periodically and once when done.
+    fn grow_with(&mut self, progress: &mut dyn FnMut(&SearchProgress<G>)) {
+        // Record time MCTS was started
+        let now = Stopwatch::start(&self.config);
+        let mut reported = Duration::ZERO;
+
+        // Expand at root
+        self.visit(self.root);
+        if self[self.root].children.is_empty() {
+            self.expand(self.root);
+        }
+
+        // Return immediately if only one valid turn
+        let forced = self[self.root].children.len() == 1 && self.cold[self.root].untried.is_empty();
+        if self.hurry && forced {
+            self.report(&now, progress);
+            return;
+        }
+
+        let mut pending = Vec::new();
+        let mut iterations = 0;
+        while !self.config.budget.exhausted(&now, iterations) {
+            iterations += 1;
+
+            // Report progress periodically
+            let elapsed = now.elapsed();
+            if elapsed >= reported + PROGRESS {
+                self.report(&now, progress);
+                reported = elapsed;
+            }
+
+            // Stop once the root is solved
+            if self[self.root].proof.is_some() || self[self.root].exact().is_some() {
+                debug!("solved after {iterations} iterations");
+                break;
+            }
+
+            // Select a leaf node to expand
+            let mut leaf = self.select();
+
+            // Expand `leaf` if it's been simulated more than the threshold
+            if self[leaf].sims > self.config.threshold {
+                self.expand(leaf);
+                leaf = *self.arena[leaf]
+                    .children
+                    .choose(&mut self.rng)
+                    .unwrap_or(&leaf);
+                self.visit(leaf);
+            }
+
+            // Backpropagate the proven result of `leaf` without simulating
+            if let Some(proof) = self[leaf].proof {
+                let winner = match proof {
+                    Proof::Win => self.cold[self[leaf].parent].player.clone(),
+                    Proof::Loss => self.cold[leaf].player.clone(),
+                };
+                let outcome = Outcome::decided(self.cold[leaf].state().clone(), Some(winner));
+                self.backprop(leaf, &outcome);
+                continue;
+            }
+            if let Some(score) = self[leaf].exact() {
+                let winner = match score.partial_cmp(&0.) {
+                    Some(Ordering::Greater) => Some(self.cold[self[leaf].parent].player.clone()),
+                    Some(Ordering::Less) => Some(self.cold[leaf].player.clone()),
+                    _ => None,
+                };
+                let outcome = Outcome::decided(self.cold[leaf].state().clone(), winner);
+                self.backprop(leaf, &outcome);
+                continue;
+            }
+
+            // Queue `leaf` to be valued by the oracle instead of simulating
+            if let (Some(network), false) = (&self.network, self.cold[leaf].state().over()) {
+                let batch = network.batch;
+                self.virtual_loss(leaf, 1);
+                // The walked state moves on, so keep a copy until valued
+                let state = self.unplay.map(|_| self.cold[leaf].state().clone());
+                pending.push((leaf, state));
+                if pending.len() >= batch {
+                    self.flush(&mut pending);
+                }
+                continue;
+            }
+
+            // Simulate at `leaf`
+            let rollouts = self.config.rollouts.max(1);
+            let rollout = Rollout {
+                policy: self.rollout.as_deref(),
+                mast: self.mast.as_ref(),
+                record: self.rave.is_some() || self.mast.is_some(),
+                cutoff: self.evaluate.and(self.config.cutoff),
+            };
+            let outcomes = match self.simulate {
+                Some(simulate) => {
+                    let seeds: Vec<u64> = (0..rollouts).map(|_| self.rng.gen()).collect();
+                    simulate(&self.cold[leaf], &seeds, &rollout)
+                }
+                None => {
+                    let node = &self.cold[leaf];
+                    (0..rollouts)
+                        .map(|_| node.simulate(&mut self.rng, self.memo.as_mut(), &rollout))
+                        .collect()
+                }
+            };
+
+            // Backpropagate the results
+            for outcome in outcomes {
+                self.backprop(leaf, &outcome);
+                if outcome.cutoff {
+                    continue;
+                }
+                if let Some(mast) = &mut self.mast {
+                    let winner = outcome
+                        .winner
+                        .clone()
+                        .unwrap_or_else(|| self.cold[self.root].player.clone());
+                    mast.update::<G>(&winner, &outcome.moves);
+                }
+                if self.rave.is_some() {
+                    self.amaf(leaf, outcome.winner.as_ref(), outcome.moves);
+                }
+            }
+        }
+        self.flush(&mut pending);
+        self.visit(self.root);
+        self.report(&now, progress);
+    }
+
+    /// Value pending leaves with the oracle in one batch.
+    fn flush(&mut self, pending: &mut Vec<(u32, Option<G>)>) {
+        let Some(network) = &self.network else {
+            return;
+        };
+        if pending.is_empty() {
+            return;
+        }
+        let games: Vec<_> = pending
+            .iter()
+            .map(|(leaf, state)| state.as_ref().unwrap_or_else(|| self.cold[*leaf].state()))
+            .collect();
+        let values: Vec<_> = network
+            .oracle
+            .infer_batch(&games)
+            .into_iter()
+            .map(|(_, value)| (value as f64 + 1.) / 2.)
+            .collect();
+        for ((leaf, _), value) in pending.drain(..).zip(values) {
+            self.virtual_loss(leaf, -1);
+            self.back_value(leaf, value);
+        }
+    }
+
+    /// Add or remove a virtual loss on the path from the root to a leaf.
+    ///
+    /// A virtual loss counts as a visit without a win.
+    fn virtual_loss(&mut self, leaf: u32, sign: i32) {
+        let null = self[self.root].parent;
+        let mut idx = leaf;
+        while idx != null {
+            let node = &mut self[idx];
+            node.sims = node.sims.wrapping_add_signed(sign);
+            idx = node.parent;
+        }
+    }
+
+    /// Report the progress of a search started at `start`.
+    fn report(&self, start: &Stopwatch, progress: &mut dyn FnMut(&SearchProgress<G>)) {
+        let root = &self[self.root];
+        let Some(&best) = root.children.iter().max_by_key(|&&idx| self[idx].sims) else {
+            return;
+        };
+        progress(&SearchProgress {
+            best: self.cold[best].action.clone().unwrap(),
+            sims: root.sims,
+            elapsed: start.elapsed(),
+        });
+    }
+
+    /// Explore the game tree.
+    ///
+    /// Nodes along the way are widened if progressive widening allows.
+    fn select(&mut self) -> u32 {
+        let mut idx = self.root; // start at the root
+        self.visit(idx);
+
+        // Loop until `node` has no children
+        while !self[idx].children.is_empty() {
+            // Stop at the first new child when expanding lazily
+            match self.widen(idx) {
+                Some(child) if self.config.lazy => {
+                    self.visit(child);
+                    return child;
+                }
+                _ => (),
+            }
+
+            // Skip children whose best score is below another's worst
+            let arena = &self.arena;
+            let node = &arena[idx];
+            let floor = match self.score {
+                Some(_) => node
+                    .children
+                    .iter()
+                    .map(|&child| arena[child].bounds.0)
+                    .fold(f64::NEG_INFINITY, f64::max),
+                None => f64::NEG_INFINITY,
+            };
+
+            // Get the child with the highest priority
+            trace!("idx: priority");
+            let weights = self.weights();
+            let rng = &mut self.rng;
+            idx = *node
+                .children
+                .iter()
+                .filter(|&&child| arena[child].bounds.1 >= floor)
+                .map(|idx| {
+                    let child = &arena[*idx];
+                    let priority = match weights.selection {
+                        Selection::Thompson => child.sample(rng),
+                        _ => child.priority(node.sims, &weights),
+                    };
+                    (idx, priority)
+                })
+                .inspect(|(idx, priority)| trace!("{:03}: {:.6}", idx, priority))
+                .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(Ordering::Equal))
+                .unwrap()
+                .0;
+            trace!("{:03} selected", idx);
+            self.visit(idx);
+        }
+
+        idx
+    }
+
+    /// Get the number of nodes in the tree.
+    fn len(&self) -> usize {
+        self.arena.len()
+    }
+
+    /// Get the approximate bytes used by the tree's nodes.
+    ///
+    /// Counts the capacity of both arenas, each stored state, and each list of
+    /// children and untried turns, but not heap data owned by the states or
+    /// turns themselves.
+    fn memory_usage(&self) -> usize {
+        let arenas = self.arena.capacity() * mem::size_of::<Node>()
+            + self.cold.capacity() * mem::size_of::<Cold<G>>();
+        let children: usize = self
+            .arena
+            .iter()
+            .map(|node| match &node.children {
+                Children::Inline(_) => 0,
+                Children::Heap(children) => children.capacity() * mem::size_of::<u32>(),
+            })
+            .sum();
+        let cold: usize = self
+            .cold
+            .iter()
+            .map(|cold| {
+                cold.state.as_ref().map_or(0, |_| mem::size_of::<G>())
+                    + cold.untried.capacity() * mem::size_of::<(G::Turn, f64)>()
+            })
+            .sum();
+        arenas + children + cold
+    }
+
+    /// Expand a node to create children in the game tree.
+    ///
+    /// Does nothing if the children would not fit under the node limit,
+    /// unless expanding the root. With progressive widening, only as many
+    /// children as allowed are created and the other turns are kept untried.
+    fn expand(&mut self, idx: u32) {
+        let turns = self.cold[idx].state().turns();
+        let mut priors = self.priors(idx, &turns);
+        if let (Some(noise), true) = (self.config.noise, idx == self.root) {
+            noise.apply(&mut priors, &mut self.rng);
+        }
+        let mut turns: Vec<_> = turns.into_iter().zip(priors).collect();
+        if self.config.widening.is_some() || self.config.lazy {
+            // Widen in order of prior with a policy, otherwise randomly
+            match self.policy.is_some() || self.network.is_some() {
+                true => turns.sort_by(|(_, a), (_, b)| b.total_cmp(a)),
+                false => turns.shuffle(&mut self.rng),
+            }
+        }
+        if let Some(widening) = self.config.widening {
+            let width = widening.width(self[idx].sims).min(turns.len());
+            self.cold[idx].untried = turns.split_off(width);
+            self.cold[idx].untried.reverse();
+        }
+        if self.config.lazy {
+            // Leave all but the first turn for later visits
+            let rest = turns.split_off(turns.len().min(1));
+            self.cold[idx].untried.extend(rest.into_iter().rev());
+        }
+        if let Some(max) = self.config.max_nodes {
+            if idx != self.root && self.len() + turns.len() > max {
+                self.cold[idx].untried.clear();
+                return;
+            }
+        }
+
+        // Iterate through actions to create children
+        let children: Vec<_> = turns
+            .into_iter()
+            .map(|(action, policy)| self.push(idx, action, policy))
+            .collect();
+        for child in children {
+            self.prove(child);
+            self.bound(child);
+            self.back_up(child);
+        }
+    }
+
+    /// Get the normalized prior probability of each turn from a node.
+    fn priors(&self, idx: u32, turns: &[G::Turn]) -> Vec<f64> {
+        let uniform = vec![1. / turns.len().max(1) as f64; turns.len()];
+        let state = self.cold[idx].state();
+        let (priors, same) = match (&self.network, &self.policy) {
+            (Some(network), _) => (network.oracle.infer(state).0, network.same),
+            (None, Some(policy)) => ((policy.priors)(state), policy.same),
+            (None, None) => return uniform,
+        };
+        let found: Vec<_> = turns
+            .iter()
+            .map(|turn| {
+                priors
+                    .iter()
+                    .find(|(other, _)| same(other, turn))
+                    .map_or(0., |&(_, prior)| prior.max(0.) as f64)
+            })
+            .collect();
+        let total: f64 = found.iter().sum();
+        if total > 0. {
+            found.into_iter().map(|prior| prior / total).collect()
+        } else {
+            uniform
+        }
+    }
+
+    /// Add an untried child to a node if progressive widening or lazy
+    /// expansion allows, returning its index.
+    fn widen(&mut self, idx: u32) -> Option<u32> {
+        let node = &self[idx];
+        let width = match self.config.widening {
+            Some(widening) => widening.width(node.sims),
+            None if self.config.lazy => usize::MAX,
+            None => return None,
+        };
+        if self.cold[idx].untried.is_empty() || node.children.len() >= width {
+            return None;
+        }
+        if let Some(max) = self.config.max_nodes {
+            if self.len() >= max {
+                return None;
+            }
+        }
+        let (action, policy) = self.cold[idx].untried.pop().unwrap();
+        let child = self.push(idx, action, policy);
+        self.prove(child);
+        self.bound(child);
+        self.back_up(child);
+        Some(child)
+    }
+
+    /// Create the child reached by playing `action` from a node.
+    ///
+    /// Returns the index of the child, which the solver marks as proven if
+    /// its position is terminal.
+    fn push(&mut self, idx: u32, action: G::Turn, policy: f64) -> u32 {
+        // Play action on the walked state, or else on a clone
+        let mut state = match self.unplay {
+            Some(_) => self.cold[idx].state.take().unwrap(),
+            None => self.cold[idx].state.clone().unwrap(),
+        };
+        state.play(action.clone());
+
+        // Add the new child
+        let player = &self.cold[idx].player;
+        let mut child = Node::new(idx);
+        child.policy = policy;
+        if self.config.solver && state.over() {
+            child.proof = match state.winner() {
+                Some(winner) if winner == *player => Some(Proof::Win),
+                Some(_) => Some(Proof::Loss),
+                None => None,
+            };
+        }
+        if let (Some(score), true) = (self.score, state.over()) {
+            let score = score(&state, player);
+            child.bounds = (score, score);
+        }
+        if let Some(bias) = &self.bias {
+            child.prior = (bias.evaluate)(&state, player);
+        }
+        if let Some(minimax) = &self.minimax {
+            child.minimax = match (state.over(), state.winner()) {
+                (true, Some(winner)) if winner == *player => 1.,
+                (true, Some(_)) => 0.,
+                (true, None) => 0.5,
+                (false, _) => (minimax.evaluate)(&state, player),
+            };
+        }
+        let child = self.arena.push(child);
+        let mut cold = Cold::new(state, Some(action));
+        if let Some(unplay) = self.unplay {
+            // Take the action back to return the state to the parent
+            let mut state = cold.state.take().unwrap();
+            unplay(&mut state, cold.action.as_ref().unwrap());
+            self.cold[idx].state = Some(state);
+        }
+        self.cold.push(cold);
+        // Parent stores index of child
+        self[idx].children.push(child);
+        child
+    }
+
+    /// Back up score bounds from a child to its ancestors.
+    ///
+    /// The player to move picks the child with the best score, and the
+    /// opponent's score is its negation.
+    fn bound(&mut self, mut idx: u32) {
+        if self.score.is_none() {
+            return;
+        }
+        while idx != self.root {
+            let parent = self[idx].parent;
+            let node = &self[parent];
+            let best = |bound: fn(&Node) -> f64| {
+                node.children
+                    .iter()
+                    .map(|&child| bound(&self[child]))
+                    .fold(f64::NEG_INFINITY, f64::max)
+            };
+            let pess = best(|node| node.bounds.0);
+            let opti = match self.cold[parent].untried.is_empty() {
+                true => best(|node| node.bounds.1),
+                false => f64::INFINITY,
+            };
+            let bounds = (-opti, -pess);
+            if bounds == node.bounds {
+                return;
+            }
+            self[parent].bounds = bounds;
+            idx = parent;
+        }
+    }
+
+    /// Back up minimax values from a child to its ancestors.
+    ///
+    /// The player to move picks the child with the best value, and the
+    /// opponent's value is its complement.
+    fn back_up(&mut self, mut idx: u32) {
+        if self.minimax.is_none() {
+            return;
+        }
+        while idx != self.root {
+            let parent = self[idx].parent;
+            let best = self[parent]
+                .children
+                .iter()
+                .map(|&child| self[child].minimax)
+                .fold(f64::NEG_INFINITY, f64::max);
+            if self[parent].minimax == 1. - best {
+                return;
+            }
+            self[parent].minimax = 1. - best;
+            idx = parent;
+        }
+    }
+
+    /// Propagate a proven child's value to its ancestors.
+    ///
+    /// A node is lost for the player who moved into it if any child is a
+    /// proven win for its player to move, and won if every child is lost.
+    fn prove(&mut self, mut idx: u32) {
+        while self[idx].proof.is_some() && idx != self.root {
+            let parent = self[idx].parent;
+            let node = &self[parent];
+            let proven = |proof| {
+                node.children
+                    .iter()
+                    .filter(|&&child| self[child].proof == Some(proof))
+                    .count()
+            };
+            let proof = if proven(Proof::Win) > 0 {
+                Proof::Loss
+            } else if self.cold[parent].untried.is_empty()
+                && proven(Proof::Loss) == node.children.len()
+            {
+                Proof::Win
+            } else {
+                return;
+            };
+            self[parent].proof = Some(proof);
+            idx = parent;
+        }
+    }
+
+    /// Get the weights used to calculate node priority.
+    fn weights(&self) -> Weights {
+        Weights {
+            explore: self.config.explore,
+            selection: self.config.selection,
+            fpu: self.config.fpu,
+            rave: self.rave.as_ref().map(|rave| rave.k),
+            bias: self.bias.as_ref().map(|bias| bias.weight),
+            minimax: self.minimax.as_ref().map(|minimax| minimax.alpha),
+            solo: self.solo.is_some(),
+        }
+    }
+
+    /// Collect the turn, simulations, and wins of every root child in order.
+    fn root_stats(&self) -> Vec<(G::Turn, u32, f64)> {
+        self[self.root]
+            .children
+            .iter()
+            .map(|&idx| {
+                let node = &self[idx];
+                let action = self.cold[idx].action.clone().unwrap();
+                (action, node.sims, node.wins)
+            })
+            .collect()
+    }
+
+    /// Collect the line of every root child, most simulated first.
+    ///
+    /// Proven wins are placed first and proven losses last.
+    fn lines(&self) -> Vec<Line<G>> {
+        let root = &self[self.root];
+        debug!("idx: sims, wins%, priority");
+        let mut lines: Vec<_> = root
+            .children
+            .iter()
+            .map(|&idx| {
+                let node = &self[idx];
+                let line = Line {
+                    pv: self.pv(idx),
+                    sims: node.sims,
+                    wins: node.wins,
+                };
+                debug!(
+                    "{:03}: {:4}, {:4.1}%, {:.6}",
+                    idx,
+                    line.sims,
+                    100. * line.wins / line.sims as f64,
+                    node.priority(root.sims, &self.weights()),
+                );
+                let rank = match node.proof {
+                    Some(Proof::Win) => 0,
+                    None => 1,
+                    Some(Proof::Loss) => 2,
+                };
+                // Prefer the best guaranteed score once the root is decided
+                let score = root.exact().map_or(0., |_| node.bounds.0);
+                (rank, score, line)
+            })
+            .collect();
+        lines.sort_by(|(rank, score, line), (other, best, next)| {
+            rank.cmp(other)
+                .then(best.total_cmp(score))
+                .then(next.sims.cmp(&line.sims))
+        });
+        lines.into_iter().map(|(_, _, line)| line).collect()
+    }
+
+    /// Format the visited subtree below a node, most simulated first.
+    fn dump(&self, idx: u32, depth: usize, indent: usize, out: &mut String) {
+        let node = &self[idx];
+        let action = self.cold[idx]
+            .action
+            .as_ref()
+            .map_or("root".to_string(), ToString::to_string);
+        out.push_str(&format!(
+            "{:indent$}{}: {} sims, {:.1}%\n",
+            "",
+            action,
+            node.sims,
+            100. * node.wins / node.sims.max(1) as f64,
+            indent = 2 * indent,
+        ));
+        if indent == depth {
+            return;
+        }
+        let mut children = node.children.to_vec();
+        children.sort_by_key(|&child| Reverse(self[child].sims));
+        for child in children.into_iter().filter(|&child| self[child].sims > 0) {
+            self.dump(child, depth, indent + 1, out);
+        }
+    }
+
+    /// Free every node outside the subtree of the root.
+    ///
+    /// The remaining nodes are renumbered from the root at index 0, so a
+    /// re-rooted tree does not keep growing its arena with stale nodes.
+    fn compact(&mut self) {
+        // Keep the walked state at the root
+        self.visit(self.root);
+
+        // Collect the subtree breadth first
+        let mut order = vec![self.root];
+        let mut next = 0;
+        while let Some(&idx) = order.get(next) {
+            order.extend_from_slice(&self[idx].children);
+            next += 1;
+        }
+        if order.len() == self.arena.len() && self.root == 0 {
+            return;
+        }
+
+        let mut remap = Arena(vec![VACANT; self.arena.len()]);
+        for (new, &old) in order.iter().enumerate() {
+            remap[old] = new as u32;
+        }
+        self.arena.keep(&order);
+        self.cold.keep(&order);
+        for node in self.arena.iter_mut() {
+            node.parent = match node.parent {
+                VACANT => VACANT,
+                parent => remap[parent],
+            };
+            for child in node.children.iter_mut() {
+                *child = remap[*child];
+            }
+        }
+        // The old parent of the root is unreachable
+        self.arena[0].parent = VACANT;
+        self.root = 0;
+        self.at = 0;
+    }
+
+    /// Move the walked state to a node.
+    ///
+    /// Turns are taken back up to the nearest common ancestor, then played
+    /// down to the node. Does nothing unless searching with
+    /// [`reversible`](Hooks::reversible) hooks.
+    fn visit(&mut self, idx: u32) {
+        let Some(unplay) = self.unplay else {
+            return;
+        };
+        if idx == self.at {
+            return;
+        }
+        let mut state = self.cold[self.at].state.take().unwrap();
+        if self[idx].parent == self.at {
+            // Descend to a child
+            state.play(self.cold[idx].action.clone().unwrap());
+        } else {
+            // Collect the path from the node up to the top of the tree
+            let mut path = vec![idx];
+            let mut node = idx;
+            while self[node].parent != VACANT {
+                node = self[node].parent;
+                path.push(node);
+            }
+            // Take turns back until reaching the path
+            while !path.contains(&self.at) {
+                unplay(&mut state, self.cold[self.at].action.as_ref().unwrap());
+                self.at = self[self.at].parent;
+            }
+            // Play turns down to the node
+            let top = path.iter().position(|&node| node == self.at).unwrap();
+            for &node in path[..top].iter().rev() {
+                state.play(self.cold[node].action.clone().unwrap());
+            }
+        }
+        self.cold[idx].state = Some(state);
+        self.at = idx;
+    }
+
+    /// Format the visited tree below a node as a Graphviz DOT graph, up to
+    /// `depth` plies.
+    fn to_dot(&self, idx: u32, depth: usize) -> String {
+        let mut out = String::from("digraph mcts {\n    node [shape=box];\n");
+        let mut stack = vec![(idx, 0)];
+        while let Some((idx, ply)) = stack.pop() {
+            let node = &self[idx];
+            let action = self.cold[idx]
+                .action
+                .as_ref()
+                .map_or("root".to_string(), ToString::to_string)
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"");
+            out.push_str(&format!(
+                "    n{idx} [label=\"{action}\\n{} sims\\n{:.1}%\"];\n",
+                node.sims,
+                100. * node.wins / node.sims.max(1) as f64,
+            ));
+            if ply == depth {
+                continue;
+            }
+            for &child in node.children.iter().rev() {
+                if self[child].sims > 0 {
+                    out.push_str(&format!("    n{idx} -> n{child};\n"));
+                    stack.push((child, ply + 1));
+                }
+            }
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    /// Follow the most simulated children from a node.
+    fn pv(&self, mut idx: u32) -> Vec<G::Turn> {
+        let mut pv = Vec::new();
+        loop {
+            let node = &self[idx];
+            pv.extend(self.cold[idx].action.clone());
+            match node.children.iter().max_by_key(|&&child| self[child].sims) {
+                Some(&child) if self[child].sims > 0 => idx = child,
+                _ => return pv,
+            }
+        }
+    }
+
+    /// Backpropagate the result of a simulation.
+    ///
+    /// Rollouts which were cut off are rewarded by the evaluation of their
+    /// final position.
+    fn backprop(&mut self, leaf: u32, outcome: &Outcome<G>) {
+        let mut idx = leaf;
+        let credited = outcome
+            .winner
+            .clone()
+            .unwrap_or_else(|| self.cold[self.root].player.clone());
+        let evaluate = self.evaluate.filter(|_| outcome.cutoff);
+        // Shaping needs the state at each node, so a walked state is moved
+        // up from a copy of the leaf's
+        let start = match (&self.shaper, self.unplay) {
+            (Some(_), Some(_)) => {
+                self.visit(leaf);
+                Some(self.cold[leaf].state().clone())
+            }
+            _ => None,
+        };
+
+        // Backpropagate until the root
+        let null = self[self.root].parent;
+        while idx != null {
+            // Update statistics of node
+            // NOTE: The game state stores the next player, but in MCTS, each
+            //       node represents the current player.
+            let mut reward = match (evaluate, self.solo) {
+                (Some(evaluate), _) => 1. - evaluate(&outcome.end, &self.cold[idx].player),
+                // A lone player is rewarded by their score
+                (None, Some(solo)) => solo(&outcome.end, &self.cold[idx].player),
+                (None, None) if credited != self.cold[idx].player => 1.,
+                (None, None) => 0.,
+            };
+            if self.shaper.is_some() {
+                self.visit(idx);
+            }
+            if let Some(shaper) = &self.shaper {
+                let playout = Playout {
+                    start: start.as_ref().unwrap_or_else(|| self.cold[leaf].state()),
+                    end: &outcome.end,
+                    plies: outcome.plies,
+                    winner: outcome.winner.as_ref(),
+                };
+                reward = shaper.shape(reward, self.cold[idx].state(), &playout);
+            }
+            let node = &mut self[idx];
+            node.wins += reward;
+            node.squares += reward * reward;
+            node.sims += 1;
+
+            // Ascend to parent
+            idx = node.parent;
+        }
+    }
+
+    /// Backpropagate the value of a leaf for its player to move.
+    fn back_value(&mut self, leaf: u32, value: f64) {
+        let player = self.cold[leaf].player.clone();
+        let null = self[self.root].parent;
+        let mut idx = leaf;
+        while idx != null {
+            let mover = self.cold[idx].player.clone();
+            let node = &mut self[idx];
+            // Nodes are credited for the player who moved into them
+            let reward = match mover == player {
+                true => 1. - value,
+                false => value,
+            };
+            node.wins += reward;
+            node.squares += reward * reward;
+            node.sims += 1;
+            idx = node.parent;
+        }
+    }
+
+    /// Update all-moves-as-first statistics from a leaf to the root.
+    ///
+    /// Each child is credited whenever its turn was played later in the
+    /// iteration by the same player, whether in the tree or the playout.
+    fn amaf(
+        &mut self,
+        leaf: u32,
+        winner: Option<&G::Player>,
+        mut moves: Vec<(G::Player, G::Turn)>,
+    ) {
+        let Some(same) = self.rave.as_ref().map(|rave| rave.same) else {
+            return;
+        };
+        let credited = winner
+            .cloned()
+            .unwrap_or_else(|| self.cold[self.root].player.clone());
+
+        let null = self[self.root].parent;
+        let mut idx = leaf;
+        while idx != null {
+            let player = self.cold[idx].player.clone();
+            for child in self[idx].children.clone().iter().copied() {
+                let Some(action) = self.cold[child].action.clone() else {
+                    continue;
+                };
+                let played = moves
+                    .iter()
+                    .any(|(by, turn)| *by == player && same(turn, &action));
+                if played {
+                    let win = credited != self.cold[child].player;
+                    let node = &mut self[child];
+                    node.amaf_wins += if win { 1. } else { 0. };
+                    node.amaf_sims += 1;
+                }
+            }
+
+            // Record the turn into this node, then ascend to parent
+            let parent = self[idx].parent;
+            if parent != null {
+                let player = self.cold[parent].player.clone();
+                moves.push((player, self.cold[idx].action.clone().unwrap()));
+            }
+            idx = parent;
+        }
+    }
+}
+
+impl<G: Game> Index<u32> for Tree<G> {
+    type Output = Node;
+
+    fn index(&self, idx: u32) -> &Self::Output {
+        &self.arena[idx]
+    }
+}
+
+impl<G: Game> IndexMut<u32> for Tree<G> {
+    fn index_mut(&mut self, idx: u32) -> &mut Self::Output {
+        &mut self.arena[idx]
+    }
+}
+
+/// Storage for one field group of every node, indexed by `u32`.
+///
+/// Indices are half the size of a `usize`, and searches never come close to
+/// four billion nodes.
+#[derive(Debug)]
+struct Arena<T>(Vec<T>);
+
+impl<T> Arena<T> {
+    /// Add an item, returning its index.
+    fn push(&mut self, item: T) -> u32 {
+        self.0.push(item);
+        (self.0.len() - 1) as u32
+    }
+
+    /// Keep only the items at `order`, renumbered in that order.
+    fn keep(&mut self, order: &[u32]) {
+        let mut old: Vec<_> = mem::take(&mut self.0).into_iter().map(Some).collect();
+        self.0 = order
+            .iter()
+            .map(|&idx| old[idx as usize].take().unwrap())
+            .collect();
+    }
+}
+
+impl<T> Deref for Arena<T> {
+    type Target = Vec<T>;
+
+    fn deref(&self) -> &Vec<T> {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Arena<T> {
+    fn deref_mut(&mut self) -> &mut Vec<T> {
+        &mut self.0
+    }
+}
+
+impl<T> Index<u32> for Arena<T> {
+    type Output = T;
+
+    fn index(&self, idx: u32) -> &T {
+        &self.0[idx as usize]
+    }
+}
+
+impl<T> IndexMut<u32> for Arena<T> {
+    fn index_mut(&mut self, idx: u32) -> &mut T {
+        &mut self.0[idx as usize]
+    }
+}
+
+/// A value proven by the solver, for the player who moved into a node.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Proof {
+    Win,
+    Loss,
+}
+
+/// Marks an unused inline slot of [`Children`].
+const VACANT: u32 = u32::MAX;
+
+/// Indices of a node's children, kept inline while there are at most four.
+///
+/// Leaves and nodes with few expanded children need no allocation, and the
+/// list is no larger than a `Vec`.
+#[derive(Clone, Debug)]
+enum Children {
+    Inline([u32; 4]),
+    Heap(Vec<u32>),
+}
+
+impl Children {
+    /// Add a child.
+    fn push(&mut self, idx: u32) {
+        match self {
+            Children::Inline(slots) => match slots.iter_mut().find(|slot| **slot == VACANT) {
+                Some(slot) => *slot = idx,
+                None => {
+                    let mut children = slots.to_vec();
+                    children.push(idx);
+                    *self = Children::Heap(children);
+                }
+            },
+            Children::Heap(children) => children.push(idx),
+        }
+    }
+}
+
+impl Default for Children {
+    fn default() -> Self {
+        Children::Inline([VACANT; 4])
+    }
+}
+
+impl Deref for Children {
+    type Target = [u32];
+
+    fn deref(&self) -> &[u32] {
+        match self {
+            Children::Inline(slots) => {
+                let len = slots.iter().take_while(|&&slot| slot != VACANT).count();
+                &slots[..len]
+            }
+            Children::Heap(children) => children,
+        }
+    }
+}
+
+impl DerefMut for Children {
+    fn deref_mut(&mut self) -> &mut [u32] {
+        match self {
+            Children::Inline(slots) => {
+                let len = slots.iter().take_while(|&&slot| slot != VACANT).count();
+                &mut slots[..len]
+            }
+            Children::Heap(children) => children,
+        }
+    }
+}
+
+/// Weights of each term in node priority.
+#[derive(Clone, Copy, Debug)]
+struct Weights {
+    /// Exploration constant used by UCB.
+    explore: f64,
+    /// Policy used to select children.
+    selection: Selection,
+    /// First play urgency.
+    fpu: Option<f64>,
+    /// RAVE equivalence parameter.
+    rave: Option<f64>,
+    /// Scale of the progressive bias term.
+    bias: Option<f64>,
+    /// Weight of minimax values against win rates.
+    minimax: Option<f64>,
+    /// Add the SP-MCTS deviation bonus.
+    solo: bool,
+}
+
+/// Data of a node in the game tree used only to expand and simulate it.
+#[derive(Debug)]
+struct Cold<G: Game> {
+    state: Option<Box<G>>,
+    player: G::Player,
+    action: Option<G::Turn>,
+    untried: Vec<(G::Turn, f64)>,
+}
+
+impl<G: Game> Cold<G> {
+    /// Create new Cold data for a node.
+    fn new(state: Box<G>, action: Option<G::Turn>) -> Cold<G> {
+        Cold {
+            player: state.player(),
+            state: Some(state),
+            action,
+            untried: Vec::new(),
+        }
+    }
+
+    /// Get the state of this node.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the state is walked elsewhere in a reversible search.
+    fn state(&self) -> &G {
+        self.state.as_deref().expect("state should be at the node")
+    }
+
+    /// Simulate the game from this node.
+    ///
+    /// Stops early after the rollout's cutoff, if any.
+    fn simulate<R: Rng>(
+        &self,
+        rng: &mut R,
+        mut memo: Option<&mut Memo<G>>,
+        rollout: &Rollout<G>,
+    ) -> Outcome<G> {
+        // Create a copy of the current state to simulate
+        let mut state = self.state().clone();
+        let mut moves = Vec::new();
+
+        let mut plies = 0;
+        loop {
+            // Stop early at a known terminal position
+            let key = memo.as_deref_mut().map(|memo| (memo.hash)(&state));
+            if let (Some(memo), Some(key)) = (memo.as_deref_mut(), key) {
+                if let Some(winner) = memo.outcomes.get(&key) {
+                    memo.hits += 1;
+                    return Outcome {
+                        end: state,
+                        plies,
+                        winner: winner.clone(),
+                        moves,
+                        cutoff: false,
+                    };
+                }
+            }
+
+            if state.over() {
+                let winner = state.winner();
+                if let (Some(memo), Some(key)) = (memo, key) {
+                    memo.outcomes.insert(key, winner.clone());
+                }
+                return Outcome {
+                    end: state,
+                    plies,
+                    winner,
+                    moves,
+                    cutoff: false,
+                };
+            }
+
+            // Stop to evaluate instead after the cutoff
+            if rollout.cutoff.is_some_and(|cutoff| plies >= cutoff) {
+                return Outcome {
+                    end: state,
+                    plies,
+                    winner: None,
+                    moves,
+                    cutoff: true,
+                };
+            }
+
+            // Policy: select a random move, unless guided by a policy or MAST
+            let turns = state.turns();
+            let action = match (rollout.policy, rollout.mast) {
+                (Some(policy), _) => &turns[policy.choose(&state, &turns, rng)],
+                (None, Some(mast)) => mast.choose(&state.player(), &turns, rng),
+                (None, None) => turns.choose(rng).unwrap(),
+            }
+            .clone();
+            if rollout.record {
+                moves.push((state.player(), action.clone()));
+            }
+            state.play(action);
+            plies += 1;
+        }
+    }
+}
+
+/// Links and statistics of a node in the game tree.
+///
+/// These are read on every step of selection, so they are kept apart from
+/// the node's [`Cold`] data to pack more nodes into each cache line.
+#[derive(Debug)]
+struct Node {
+    // Position
+    parent: u32,
+    children: Children,
+    // Statistics
+    wins: f64,
+    squares: f64,
+    sims: u32,
+    amaf_wins: f64,
+    amaf_sims: u32,
+    prior: f64,
+    policy: f64,
+    /// Minimax value of heuristic evaluations for the player who moved into it.
+    minimax: f64,
+    proof: Option<Proof>,
+    /// Pessimistic and optimistic score for the player who moved into it.
+    bounds: (f64, f64),
+}
+
+impl Node {
+    /// Create a new Node.
+    fn new(parent: u32) -> Node {
+        Node {
+            parent,
+            children: Children::default(),
+            wins: 0.,
+            squares: 0.,
+            sims: 0,
+            amaf_wins: 0.,
+            amaf_sims: 0,
+            prior: 0.,
+            policy: 1.,
+            minimax: 0.,
+            proof: None,
+            bounds: (f64::NEG_INFINITY, f64::INFINITY),
+        }
+    }
+
+    /// Sample a win rate from the Beta posterior of the node's rewards.
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> f64 {
+        match self.proof {
+            Some(Proof::Win) => return f64::INFINITY,
+            Some(Proof::Loss) => return f64::NEG_INFINITY,
+            None => (),
+        }
+        let losses = (self.sims as f64 - self.wins).max(0.);
+        beta(rng, self.wins.max(0.) + 1., losses + 1.)
+    }
+
+    /// Get the score if its bounds have met.
+    fn exact(&self) -> Option<f64> {
+        let (pess, opti) = self.bounds;
+        (pess == opti).then_some(pess)
+    }
+
+    /// Calculate node priority
+    ///
+    /// With a RAVE equivalence parameter, the exploitation term is blended
+    /// with the AMAF win rate. With progressive bias, the prior is added and
+    /// decays with visits.
+    fn priority(&self, psims: u32, weights: &Weights) -> f64 {
+        // Proven children are always or never chosen
+        match self.proof {
+            Some(Proof::Win) => return f64::INFINITY,
+            Some(Proof::Loss) => return f64::NEG_INFINITY,
+            None => (),
+        }
+        // Extract UCB
+        let wins = self.wins;
+        let sims = self.sims as f64;
+        let psims = psims as f64;
+        // Calculate UCB
+        let mut exploit = match (weights.selection, self.sims, weights.fpu) {
+            // PUCT values unvisited children by their prior alone
+            (Selection::Puct, 0, fpu) => fpu.unwrap_or(0.),
+            (_, 0, Some(fpu)) => return fpu,
+            _ => wins / sims,
+        };
+        if let (Some(k), true) = (weights.rave, self.amaf_sims > 0) {
+            let beta = (k / (3. * sims + k)).sqrt();
+            let amaf = self.amaf_wins / self.amaf_sims as f64;
+            exploit = (1. - beta) * exploit + beta * amaf;
+        }
+        if let Some(alpha) = weights.minimax {
+            exploit = (1. - alpha) * exploit + alpha * self.minimax;
+        }
+        let explore = match weights.selection {
+            Selection::Ucb1 => weights.explore * (psims.ln() / sims).sqrt(),
+            Selection::Ucb1Tuned => {
+                let mean = wins / sims;
+                let variance = self.squares / sims - mean * mean + (2. * psims.ln() / sims).sqrt();
+                (psims.ln() / sims * variance.min(0.25)).sqrt()
+            }
+            Selection::Puct => weights.explore * self.policy * psims.sqrt() / (1. + sims),
+            // Sampling explores by itself
+            Selection::Thompson => 0.,
+        };
+        let deviation = match weights.solo {
+            true => (self.squares / sims - (wins / sims).powi(2) + DEVIATION / sims).sqrt(),
+            false => 0.,
+        };
+        let bias = weights
+            .bias
+            .map_or(0., |weight| weight * self.prior / (sims + 1.));
+        // Return priority
+        match exploit + explore + deviation + bias {
+            x if x.is_finite() => x,
+            _ => f64::INFINITY,
+        }
+    }
+}
+
+/// Sample from a Beta distribution.
+fn beta<R: Rng + ?Sized>(rng: &mut R, a: f64, b: f64) -> f64 {
+    let x = gamma(rng, a);
+    let y = gamma(rng, b);
+    x / (x + y)
+}
+
+/// Sample from a Gamma distribution.
+///
+/// Uses the method of Marsaglia and Tsang, boosting shapes below 1.
+fn gamma<R: Rng + ?Sized>(rng: &mut R, shape: f64) -> f64 {
+    if shape < 1. {
+        let u = 1. - rng.gen::<f64>();
+        return gamma(rng, shape + 1.) * u.powf(1. / shape);
+    }
+    let d = shape - 1. / 3.;
+    let c = 1. / (9. * d).sqrt();
+    loop {
+        // Sample a standard normal with the Box-Muller transform
+        let (u, v): (f64, f64) = (1. - rng.gen::<f64>(), rng.gen());
+        let z = (-2. * u.ln()).sqrt() * (std::f64::consts::TAU * v).cos();
+        let t = (1. + c * z).powi(3);
+        if t <= 0. {
+            continue;
+        }
+        let w = 1. - rng.gen::<f64>();
+        if w.ln() < 0.5 * z * z + d - d * t + d * t.ln() {
+            return d * t;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicU64;
+
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::tests::{Nim, Steps};
+
+    // Wins by the first player count double
+    impl Score for Nim {
+        fn score(&self, player: &u8) -> f64 {
+            let margin = 1. + self.player as f64;
+            if self.winner() == Some(*player) {
+                margin
+            } else {
+                -margin
+            }
+        }
+    }
+
+    #[test]
+    fn it_works() {
+        let result = 2 + 2;
+        assert_eq!(result, 4);
+    }
+
+    #[test]
+    fn timer_replaces_system_clock() {
+        // Each reading advances a millisecond
+        static TICKS: AtomicU64 = AtomicU64::new(0);
+        let tick = || Duration::from_millis(TICKS.fetch_add(1, atomic::Ordering::Relaxed));
+        let config = MctsConfig {
+            budget: Budget::Time(Duration::from_millis(40)),
+            seed: Some(7),
+            timer: Some(Timer(tick)),
+            ..Default::default()
+        };
+        let report = analyze(&Nim::new(9), &config);
+        // The budget and progress checks read the timer every iteration
+        assert_eq!(report.sims, 20);
+        assert!(report.elapsed >= Duration::from_millis(40));
+    }
+
+    #[test]
+    fn analyze_reports_root_turns() {
+        let config = MctsConfig {
+            budget: Budget::Iterations(1000),
+            seed: Some(7),
+            ..Default::default()
+        };
+        let report = analyze(&Nim::new(5), &config);
+        assert_eq!(report.turn, 1);
+        assert_eq!(report.turns.len(), 3);
+        assert_eq!(report.turns[0].0, 1);
+        assert!(report.turns.iter().all(|&(_, sims, _)| sims <= report.sims));
+        let total: u32 = report.turns.iter().map(|&(_, sims, _)| sims).sum();
+        assert!(total <= report.sims);
+        // Every node takes at least its hot and cold data
+        let node = mem::size_of::<Node>() + mem::size_of::<Cold<Nim>>();
+        assert!(report.memory >= report.nodes * node);
+    }
+
+    #[test]
+    fn heatmap_maps_root_visits() {
+        let config = MctsConfig {
+            budget: Budget::Iterations(500),
+            seed: Some(7),
+            ..Default::default()
+        };
+        let map = heatmap(&Nim::new(5), &config);
+        assert_eq!((map.rows, map.cols), (1, 3));
+        // Taking one stone leaves a multiple of four
+        assert!(map.get(0, 0) > map.get(0, 1) && map.get(0, 0) > map.get(0, 2));
+        assert!(map.visits.iter().sum::<u32>() <= 500);
+        assert!(map.to_string().starts_with('@'));
+        let json = map.to_json();
+        assert!(json.starts_with(r#"{"rows":1,"cols":3,"visits":[["#));
+    }
+
+    #[test]
+    fn dot_graphs_visited_nodes() {
+        let config = MctsConfig {
+            budget: Budget::Iterations(200),
+            seed: Some(7),
+            ..Default::default()
+        };
+        let graph = dot(&Nim::new(5), &config, 1);
+        assert!(graph.starts_with("digraph mcts {"));
+        assert!(graph.contains("n0 [label=\"root\\n200 sims"));
+        // The root and its three children, without deeper nodes
+        assert_eq!(graph.matches("label=").count(), 4);
+        assert_eq!(graph.matches(" -> ").count(), 3);
+        assert!(graph.trim_end().ends_with('}'));
+    }
+
+    #[test]
+    fn run_with_progress_reports() {
+        let config = MctsConfig {
+            budget: Budget::Time(Duration::from_millis(250)),
+            ..Default::default()
+        };
+        let mut reports = Vec::new();
+        let turn = run_with_progress(&Nim::new(5), &config, |progress| {
+            reports.push((progress.sims, progress.elapsed));
+        });
+        assert_eq!(turn, 1);
+        assert!(reports.len() >= 2);
+        assert!(reports
+            .windows(2)
+            .all(|w| w[0].0 <= w[1].0 && w[0].1 <= w[1].1));
+    }
+
+    #[test]
+    fn evaluate_estimates_win_rates() {
+        let config = MctsConfig {
+            budget: Budget::Iterations(2000),
+            seed: Some(7),
+            ..Default::default()
+        };
+        let eval = evaluate(&Nim::new(5), &config);
+        assert_eq!(eval.len(), 2);
+        assert_eq!((eval[0].0, eval[1].0), (0, 1));
+        assert!(eval[0].1 > 0.8);
+        assert!((eval[0].1 + eval[1].1 - 1.).abs() < 1e-9);
+        assert_eq!(evaluate(&Nim::new(0), &config), [(1, 1.)]);
+
+        // Forced turns are still searched
+        let eval = evaluate(&Nim::new(1), &config);
+        assert_eq!(eval[0], (0, 1.));
+        let report = analyze(&Nim::new(1), &config);
+        assert_eq!(report.sims, 2000);
+        assert_eq!(run_with(&Nim::new(1), &config), 1);
+    }
+
+    #[test]
+    fn sample_follows_temperature() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let visits = [(1, 10), (2, 80), (3, 10)];
+        let count = |temperature, rng: &mut StdRng| {
+            (0..200)
+                .filter(|_| sample(&visits, temperature, rng) == 2)
+                .count()
+        };
+        assert_eq!(count(0., &mut rng), 200);
+        assert!(count(0.1, &mut rng) > 190);
+        assert!(count(100., &mut rng) < 100);
+
+        let schedule = Schedule::default();
+        assert_eq!(
+            (schedule.temperature(9), schedule.temperature(10)),
+            (1., 0.)
+        );
+        assert_eq!(Schedule::constant(0.5).temperature(0), 0.5);
+    }
+
+    #[test]
+    fn run_with_short_budget() {
+        let config = MctsConfig {
+            budget: Budget::Time(Duration::from_millis(100)),
+            ..Default::default()
+        };
+        // Taking one stone leaves a multiple of four for the opponent
+        assert_eq!(run_with(&Nim::new(5), &config), 1);
+    }
+
+    #[test]
+    fn search_exact_iterations() {
+        let config = MctsConfig {
+            budget: Budget::Iterations(500),
+            ..Default::default()
+        };
+        let (lines, _) = search(&Nim::new(9), &config);
+        assert_eq!(lines.iter().map(|line| line.sims).sum::<u32>(), 500);
+    }
+
+    #[test]
+    fn mcts_reuses_tree() {
+        let config = MctsConfig {
+            budget: Budget::Iterations(1000),
+            ..Default::default()
+        };
+        let mut mcts = Mcts::new(config);
+        let mut game = Nim::new(9);
+
+        // Play our turn, then let the opponent reply
+        let turn = mcts.run(&game);
+        assert_eq!(turn, 1);
+        game.play(turn);
+        mcts.advance(&1);
+        game.play(1);
+
+        // The new root carries statistics from the previous search
+        let tree = mcts.tree.as_ref().unwrap();
+        assert!(tree[tree.root].sims > 0);
+        assert_eq!(tree.cold[tree.root].state().stones, game.stones);
+        assert_eq!(mcts.run(&game), 3);
+    }
+
+    #[test]
+    fn children_stay_small() {
+        assert_eq!(mem::size_of::<Children>(), mem::size_of::<Vec<u32>>());
+        let mut children = Children::default();
+        assert!(children.is_empty());
+        for idx in 0..6 {
+            children.push(idx);
+            assert_eq!(*children, (0..=idx).collect::<Vec<_>>());
+        }
+        children.reverse();
+        assert_eq!(*children, [5, 4, 3, 2, 1, 0]);
+    }
+
+    #[test]
+    fn compact_frees_unreachable_nodes() {
+        let config = MctsConfig {
+            budget: Budget::Iterations(500),
+            seed: Some(7),
+            ..Default::default()
+        };
+        let mut tree = grow(&Nim::new(9), &config);
+        let child = tree[tree.root].children[0];
+        let sims = tree[child].sims;
+        let mut size = 0;
+        let mut stack = vec![child];
+        while let Some(idx) = stack.pop() {
+            size += 1;
+            stack.extend_from_slice(&tree[idx].children);
+        }
+        tree.root = child;
+        tree.compact();
+        assert!(size < 500);
+        assert_eq!(tree.len(), size);
+        assert_eq!((tree.root, tree[0].sims, tree[0].parent), (0, sims, VACANT));
+        for (idx, node) in (0..).zip(tree.arena.iter()) {
+            assert!(node.children.iter().all(|&child| tree[child].parent == idx));
+        }
+        // The compacted tree keeps growing
+        tree.grow();
+        assert_eq!(tree[0].sims, sims + 500);
+    }
+
+    #[test]
+    fn reversible_search_matches_cloning() {
+        let config = MctsConfig {
+            budget: Budget::Iterations(500),
+            seed: Some(7),
+            rave: Some(300.),
+            solver: true,
+            lazy: true,
+            ..Default::default()
+        };
+        let hooks = Hooks::new().score().shaper(Arc::new(Discount(0.9)));
+        let reversible = hooks.clone().reversible();
+        let mut cloning = Tree::with_hooks(Box::new(Nim::new(9)), config.clone(), &hooks);
+        let mut walking = Tree::with_hooks(Box::new(Nim::new(9)), config.clone(), &reversible);
+        cloning.grow();
+        walking.grow();
+        let sims = |tree: &Tree<Nim>| tree.arena.iter().map(|node| node.sims).collect::<Vec<_>>();
+        assert_eq!(sims(&walking), sims(&cloning));
+
+        // Only the root keeps a state
+        let stored = walking.cold.iter().filter(|cold| cold.state.is_some());
+        assert_eq!(stored.count(), 1);
+        assert_eq!(walking.cold[walking.root].state().stones, 9);
+        assert!(walking.memory_usage() < cloning.memory_usage());
+
+        // Re-rooting walks the state to the new root
+        let child = walking[walking.root].children[0];
+        let stones = cloning.cold[child].state().stones;
+        walking.root = child;
+        walking.compact();
+        assert_eq!(walking.cold[0].state().stones, stones);
+        assert_eq!(run_with_hooks(&Nim::new(9), &config, &reversible), 1);
+    }
+
+    #[test]
+    fn mcts_ponders_predicted_reply() {
+        let config = MctsConfig {
+            budget: Budget::Iterations(1000),
+            seed: Some(7),
+            ..Default::default()
+        };
+        let mut mcts = Mcts::new(config);
+        let mut game = Nim::new(9);
+        let turn = mcts.run(&game);
+        game.play(turn);
+
+        // A hit keeps the pondered tree, which has grown meanwhile
+        let tree = mcts.tree.as_ref().unwrap();
+        let before = tree[tree.root]
+            .children
+            .iter()
+            .map(|&child| tree[child].sims)
+            .max();
+        mcts.ponder();
+        let predicted = *mcts.pondering().unwrap();
+        thread::sleep(Duration::from_millis(20));
+        mcts.advance(&predicted);
+        assert!(mcts.pondering().is_none());
+        let tree = mcts.tree.as_ref().unwrap();
+        assert_eq!(tree.cold[tree.root].action, Some(predicted));
+        assert!(Some(tree[tree.root].sims) > before);
+        // Only the subtree of the reply is kept
+        assert_eq!((tree.root, tree[0].parent), (0, VACANT));
+
+        // A miss falls back to the previous search
+        game.play(predicted);
+        game.play(mcts.run(&game));
+        mcts.ponder();
+        let predicted = *mcts.pondering().unwrap();
+        let other = (1..=3).find(|&turn| turn != predicted).unwrap();
+        mcts.advance(&other);
+        if let Some(tree) = &mcts.tree {
+            assert_eq!(tree.cold[tree.root].action, Some(other));
+        }
+    }
+
+    #[test]
+    fn memo_caches_outcomes() {
+        let config = MctsConfig {
+            budget: Budget::Iterations(2000),
+            seed: Some(1),
+            ..Default::default()
+        };
+        let hooks = Hooks::new().memo();
+        assert_eq!(run_with_hooks(&Nim::new(5), &config, &hooks), 1);
+
+        let root = Cold::new(Box::new(Nim::new(1)), None);
+        let mut memo = Memo::new();
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..10 {
+            let rollout = Rollout {
+                policy: None,
+                mast: None,
+                record: false,
+                cutoff: None,
+            };
+            root.simulate(&mut rng, Some(&mut memo), &rollout);
+        }
+        assert_eq!(memo.len(), 1);
+        assert_eq!(memo.hits, 9);
+    }
+
+    #[test]
+    fn run_parallel_merges_trees() {
+        let config = MctsConfig {
+            budget: Budget::Iterations(500),
+            seed: Some(3),
+            ..Default::default()
+        };
+        assert_eq!(run_parallel(&Nim::new(5), &config, 4), 1);
+        assert_eq!(run_parallel(&Nim::new(2), &config, 1), 2);
+
+        // Widened trees expand children in their own order
+        let config = MctsConfig {
+            budget: Budget::Iterations(3000),
+            seed: Some(1),
+            widening: Some(Widening::default()),
+            ..Default::default()
+        };
+        assert_eq!(run_parallel(&Nim::new(5), &config, 4), 1);
+        let roots = vec![vec![(1, 10, 5.), (2, 5, 1.)], vec![(2, 8, 4.), (3, 1, 0.)]];
+        assert_eq!(merge::<Nim>(roots), 2);
+    }
+
+    #[test]
+    fn parallel_mcts_reuses_threads() {
+        let config = MctsConfig {
+            budget: Budget::Iterations(500),
+            seed: Some(3),
+            ..Default::default()
+        };
+        let mcts = ParallelMcts::new(4);
+        assert_eq!(mcts.threads(), 4);
+        for stones in [5, 9, 6] {
+            let game = Nim::new(stones);
+            assert_eq!(mcts.run(&game, &config), run_parallel(&game, &config, 4));
+        }
+    }
+
+    #[test]
+    fn shaper_discounts_wins() {
+        let config = MctsConfig {
+            budget: Budget::Iterations(2000),
+            seed: Some(5),
+            ..Default::default()
+        };
+        let hooks = Hooks::new().shaper(Arc::new(Discount(0.5)));
+        assert_eq!(run_with_hooks(&Nim::new(5), &config, &hooks), 1);
+
+        let end = Nim {
+            stones: 0,
+            player: 1,
+        };
+        let playout = Playout {
+            start: &Nim::new(3),
+            end: &end,
+            plies: 2,
+            winner: Some(&0),
+        };
+        assert_eq!(Discount(0.5).shape(1., &end, &playout), 0.25);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn parallel_rollouts_multiply_sims() {
+        let config = MctsConfig {
+            budget: Budget::Iterations(300),
+            seed: Some(2),
+            rollouts: 4,
+            ..Default::default()
+        };
+        let hooks = Hooks::new().parallel();
+        assert_eq!(run_with_hooks(&Nim::new(5), &config, &hooks), 1);
+        let mut tree = Tree::with_hooks(Box::new(Nim::new(5)), config, &hooks);
+        tree.grow();
+        assert_eq!(tree[tree.root].sims, 1200);
+    }
+
+    #[test]
+    fn rave_tracks_amaf() {
+        let config = MctsConfig {
+            budget: Budget::Iterations(2000),
+            seed: Some(4),
+            rave: Some(300.),
+            ..Default::default()
+        };
+        assert_eq!(run_with(&Nim::new(5), &config), 1);
+
+        let mut tree = Tree::new(Box::new(Nim::new(6)), config);
+        tree.expand(tree.root);
+        let leaf = tree[tree.root].children[0];
+        // Taking one stone twice more credits the same root child as first move
+        tree.amaf(leaf, Some(&0), vec![(1, 1), (0, 1)]);
+        assert_eq!(tree[leaf].amaf_sims, 1);
+        assert_eq!(tree[tree[tree.root].children[1]].amaf_sims, 0);
+    }
+
+    #[test]
+    fn mast_prefers_winning_turns() {
+        let config = MctsConfig {
+            budget: Budget::Iterations(2000),
+            seed: Some(6),
+            mast: Some(0.2),
+            ..Default::default()
+        };
+        assert_eq!(run_with(&Nim::new(5), &config), 1);
+
+        let mut mast = Mast::new(0.1);
+        mast.update::<Nim>(&0, &[(0, 3), (1, 1), (0, 3)]);
+        assert_eq!(mast.stats[&("0".to_string(), "3".to_string())], (2., 2));
+        assert_eq!(mast.stats[&("1".to_string(), "1".to_string())], (0., 1));
+
+        // Losing turns are rarely chosen at low temperature
+        let mut rng = StdRng::seed_from_u64(0);
+        let picks = (0..100)
+            .filter(|_| *mast.choose(&1, &[1, 2], &mut rng) == 1)
+            .count();
+        assert!(picks < 5);
+    }
+
+    #[test]
+    fn lazy_expands_one_child_per_visit() {
+        let config = MctsConfig {
+            budget: Budget::Iterations(1),
+            seed: Some(8),
+            lazy: true,
+            ..Default::default()
+        };
+        let mut tree = Tree::new(Box::new(Nim::new(5)), config);
+        tree.expand(tree.root);
+        assert_eq!(tree[tree.root].children.len(), 1);
+        assert_eq!(tree.cold[tree.root].untried.len(), 2);
+        // Selection stops at each new child until the root is full
+        for len in 2..=3 {
+            let leaf = tree.select();
+            assert_eq!(tree[leaf].parent, tree.root);
+            assert_eq!(tree[tree.root].children.len(), len);
+        }
+        assert!(tree.cold[tree.root].untried.is_empty());
+
+        let config = MctsConfig {
+            budget: Budget::Iterations(2000),
+            seed: Some(8),
+            lazy: true,
+            ..Default::default()
+        };
+        assert_eq!(run_with(&Nim::new(9), &config), 1);
+    }
+
+    #[test]
+    fn widening_grows_with_visits() {
+        let config = MctsConfig {
+            budget: Budget::Iterations(1),
+            seed: Some(8),
+            widening: Some(Widening::default()),
+            ..Default::default()
+        };
+        let mut tree = Tree::new(Box::new(Nim::new(5)), config);
+        tree.expand(tree.root);
+        assert_eq!(tree[tree.root].children.len(), 1);
+        assert_eq!(tree.cold[tree.root].untried.len(), 2);
+
+        // Four visits allow a second child
+        let root = tree.root;
+        tree[root].sims = 4;
+        tree.select();
+        assert_eq!(tree[tree.root].children.len(), 2);
+
+        let config = MctsConfig {
+            budget: Budget::Iterations(3000),
+            seed: Some(8),
+            widening: Some(Widening::default()),
+            ..Default::default()
+        };
+        assert_eq!(run_with(&Nim::new(5), &config), 1);
+    }
+
+    #[test]
+    fn bias_uses_priors() {
+        let config = MctsConfig {
+            budget: Budget::Iterations(200),
+            seed: Some(9),
+            bias: Some(1.),
+            ..Default::default()
+        };
+        let hooks = Hooks::new().evaluate();
+        assert_eq!(run_with_hooks(&Nim::new(9), &config, &hooks), 1);
+
+        let mut tree = Tree::with_hooks(Box::new(Nim::new(5)), config, &hooks);
+        tree.expand(tree.root);
+        let priors: Vec<_> = tree[tree.root]
+            .children
+            .iter()
+            .map(|&child| tree[child].prior)
+            .collect();
+        assert_eq!(priors, [1., 0., 0.]);
+    }
+
+    #[test]
+    fn minimax_backs_up_evaluations() {
+        let config = MctsConfig {
+            budget: Budget::Iterations(200),
+            seed: Some(7),
+            minimax: Some(0.3),
+            ..Default::default()
+        };
+        let hooks = Hooks::new().evaluate();
+        let mut tree = Tree::with_hooks(Box::new(Nim::new(5)), config.clone(), &hooks);
+        tree.expand(tree.root);
+        // Taking one stone leaves a multiple of four
+        let values: Vec<_> = tree[tree.root]
+            .children
+            .iter()
+            .map(|&child| tree[child].minimax)
+            .collect();
+        assert_eq!(values, [1., 0., 0.]);
+        assert_eq!(tree[tree.root].minimax, 0.);
+
+        assert_eq!(run_with_hooks(&Nim::new(9), &config, &hooks), 1);
+    }
+
+    #[test]
+    fn ucb1_tuned_bounds_exploration() {
+        let mut node = Node::new(0);
+        node.sims = 10;
+        node.wins = 5.;
+        node.squares = 5.;
+        let weights = |selection| Weights {
+            explore: EXPLORE,
+            selection,
+            fpu: None,
+            rave: None,
+            bias: None,
+            minimax: None,
+            solo: false,
+        };
+        let ucb1 = node.priority(100, &weights(Selection::Ucb1));
+        let tuned = node.priority(100, &weights(Selection::Ucb1Tuned));
+        assert!(0.5 < tuned && tuned < ucb1);
+
+        let config = MctsConfig {
+            budget: Budget::Iterations(2000),
+            seed: Some(10),
+            selection: Selection::Ucb1Tuned,
+            ..Default::default()
+        };
+        assert_eq!(run_with(&Nim::new(5), &config), 1);
+    }
+
+    #[test]
+    fn puct_follows_priors() {
+        fn priors(game: &Nim) -> Vec<(u32, f32)> {
+            let best = game.stones % 4;
+            vec![(best.max(1), 0.8), (3, 0.1)]
+        }
+
+        let config = MctsConfig {
+            budget: Budget::Iterations(300),
+            seed: Some(11),
+            selection: Selection::Puct,
+            ..Default::default()
+        };
+        let hooks = Hooks::new().priors(priors);
+        assert_eq!(run_with_hooks(&Nim::new(6), &config, &hooks), 2);
+
+        let mut tree = Tree::with_hooks(Box::new(Nim::new(6)), config, &hooks);
+        let found = tree.priors(tree.root, &[1, 2, 3]);
+        assert_eq!(found[0], 0.);
+        assert!((found[1] - 8. / 9.).abs() < 1e-6);
+        assert!((found.iter().sum::<f64>() - 1.).abs() < 1e-9);
+        tree.policy = None;
+        assert_eq!(tree.priors(tree.root, &[1, 2]), [0.5, 0.5]);
+    }
+
+    #[test]
+    fn thompson_samples_posterior() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut node = Node::new(0);
+        node.sims = 100;
+        node.wins = 80.;
+        let mean = (0..1000).map(|_| node.sample(&mut rng)).sum::<f64>() / 1000.;
+        assert!((mean - 81. / 102.).abs() < 0.02);
+
+        let config = MctsConfig {
+            budget: Budget::Iterations(2000),
+            selection: Selection::Thompson,
+            seed: Some(7),
+            ..Default::default()
+        };
+        assert_eq!(run_with(&Nim::new(5), &config), 1);
+    }
+
+    #[test]
+    fn noise_mixes_root_priors() {
+        let config = MctsConfig {
+            budget: Budget::Iterations(1),
+            seed: Some(7),
+            noise: Some(Noise {
+                alpha: 0.3,
+                fraction: 0.5,
+            }),
+            ..Default::default()
+        };
+        let mut tree = Tree::new(Box::new(Nim::new(9)), config);
+        tree.expand(tree.root);
+        let policies: Vec<_> = tree[tree.root]
+            .children
+            .iter()
+            .map(|&child| tree[child].policy)
+            .collect();
+        // Priors stay normalized, but are no longer uniform
+        assert!((policies.iter().sum::<f64>() - 1.).abs() < 1e-9);
+        assert!(policies
+            .iter()
+            .any(|&policy| (policy - 1. / 3.).abs() > 1e-3));
+
+        assert_eq!("0.3,0.25".parse(), Ok(Noise::default()));
+        assert_eq!(Noise::default().to_string(), "0.3,0.25");
+    }
+
+    #[test]
+    fn fpu_limits_unvisited_priority() {
+        let node = Node::new(0);
+        let weights = Weights {
+            explore: EXPLORE,
+            selection: Selection::Ucb1,
+            fpu: Some(0.6),
+            rave: None,
+            bias: None,
+            minimax: None,
+            solo: false,
+        };
+        assert_eq!(node.priority(10, &weights), 0.6);
+        let weights = Weights {
+            fpu: None,
+            ..weights
+        };
+        assert_eq!(node.priority(10, &weights), f64::INFINITY);
+
+        let config = MctsConfig {
+            budget: Budget::Iterations(2000),
+            seed: Some(12),
+            fpu: Some(1.),
+            ..Default::default()
+        };
+        assert_eq!(run_with(&Nim::new(5), &config), 1);
+    }
+
+    #[test]
+    fn solver_plays_forced_wins() {
+        let config = MctsConfig {
+            budget: Budget::Iterations(100_000),
+            seed: Some(13),
+            solver: true,
+            ..Default::default()
+        };
+        // Taking the last three stones wins at once
+        let (lines, nodes) = search(&Nim::new(3), &config);
+        assert_eq!(lines[0].pv, [3]);
+        assert!(nodes < 10);
+
+        // Every reply to taking one stone from five loses
+        let mut tree = Tree::new(Box::new(Nim::new(5)), config);
+        tree.grow();
+        assert_eq!(tree[tree.root].proof, Some(Proof::Loss));
+        assert_eq!(tree.lines()[0].pv[0], 1);
+    }
+
+    // Knows to leave a multiple of four stones
+    #[derive(Debug)]
+    struct Perfect;
+
+    impl Oracle<Nim> for Perfect {
+        fn infer(&self, game: &Nim) -> (Vec<(u32, f32)>, f32) {
+            let good = game.stones % 4;
+            let priors = game
+                .turns()
+                .into_iter()
+                .map(|turn| (turn, if turn == good { 0.9 } else { 0.05 }))
+                .collect();
+            (priors, if good == 0 { -1. } else { 1. })
+        }
+    }
+
+    #[test]
+    fn oracle_evaluates_leaves() {
+        let config = MctsConfig {
+            budget: Budget::Iterations(50),
+            seed: Some(7),
+            threshold: 0,
+            selection: Selection::Puct,
+            ..Default::default()
+        };
+        let hooks = Hooks::new().oracle(Arc::new(Perfect)).same(PartialEq::eq);
+        assert_eq!(run_with_hooks(&Nim::new(9), &config, &hooks), 1);
+        assert_eq!(run_with_hooks(&Nim::new(7), &config, &hooks), 3);
+    }
+
+    #[test]
+    fn oracle_batches_pending_leaves() {
+        let config = MctsConfig {
+            budget: Budget::Iterations(64),
+            seed: Some(7),
+            threshold: 0,
+            batch: 8,
+            ..Default::default()
+        };
+        let hooks = Hooks::new().oracle(Arc::new(Perfect));
+        let mut tree = Tree::with_hooks(Box::new(Nim::new(9)), config, &hooks);
+        tree.grow();
+        // Virtual losses are all removed once every batch is valued
+        let root = &tree[tree.root];
+        let total: u32 = root.children.iter().map(|&idx| tree[idx].sims).sum();
+        assert_eq!(root.sims, 64);
+        assert_eq!(total, 64);
+        assert_eq!(tree.lines()[0].pv[0], 1);
+
+        // A threshold of 0 expands each leaf on its second visit
+        let config = MctsConfig {
+            budget: Budget::Iterations(200),
+            seed: Some(7),
+            threshold: 0,
+            ..Default::default()
+        };
+        let mut tree = Tree::with_hooks(Box::new(Nim::new(9)), config, &hooks);
+        tree.grow();
+        for (node, cold) in tree.arena.iter().zip(tree.cold.iter()).skip(1) {
+            if !cold.state().over() {
+                assert_eq!(node.children.is_empty(), node.sims < 2);
+            }
+        }
+    }
+
+    #[test]
+    fn score_maximizes_margin() {
+        let config = MctsConfig {
+            budget: Budget::Iterations(5000),
+            seed: Some(14),
+            ..Default::default()
+        };
+        let hooks = Hooks::new().score();
+        assert_eq!(run_with_hooks(&Nim::new(2), &config, &hooks), 2);
+
+        let mut tree = Tree::with_hooks(Box::new(Nim::new(5)), config, &hooks);
+        tree.grow();
+        assert_eq!(tree[tree.root].exact(), Some(-2.));
+        assert_eq!(tree.lines()[0].pv[0], 1);
+    }
+
+    #[test]
+    fn score_plays_puzzles() {
+        let config = MctsConfig {
+            budget: Budget::Iterations(5000),
+            seed: Some(7),
+            // Scores range up to 4
+            explore: 3.,
+            ..Default::default()
+        };
+        let mut game = Steps::default();
+        while !game.over() {
+            game.play(run_with_hooks(&game, &config, &Hooks::new().score()));
+        }
+        assert_eq!(game.score(&0), 4.);
+    }
+
+    #[test]
+    fn cutoff_scores_with_heuristic() {
+        let config = MctsConfig {
+            budget: Budget::Iterations(300),
+            seed: Some(15),
+            cutoff: Some(0),
+            ..Default::default()
+        };
+        // With no rollouts at all, the heuristic alone finds the best turn
+        let hooks = Hooks::new().evaluate();
+        assert_eq!(run_with_hooks(&Nim::new(13), &config, &hooks), 1);
+
+        let node = Cold::new(Box::new(Nim::new(13)), None);
+        let rollout = Rollout {
+            policy: None,
+            mast: None,
+            record: false,
+            cutoff: Some(2),
+        };
+        let outcome = node.simulate(&mut StdRng::seed_from_u64(0), None, &rollout);
+        assert!(outcome.cutoff);
+        assert_eq!(outcome.plies, 2);
+    }
+
+    #[test]
+    fn rollout_policy_guides_playouts() {
+        /// Always take as many stones as possible.
+        #[derive(Debug)]
+        struct Greedy;
+
+        impl RolloutPolicy<Nim> for Greedy {
+            fn choose(&self, _: &Nim, turns: &[u32], _: &mut dyn RngCore) -> usize {
+                turns.len() - 1
+            }
+        }
+
+        let node = Cold::new(Box::new(Nim::new(7)), None);
+        let rollout = Rollout {
+            policy: Some(&Greedy),
+            mast: None,
+            record: true,
+            cutoff: None,
+        };
+        let outcome = node.simulate(&mut StdRng::seed_from_u64(0), None, &rollout);
+        let turns: Vec<_> = outcome.moves.iter().map(|&(_, turn)| turn).collect();
+        assert_eq!(turns, [3, 3, 1]);
+        assert_eq!(outcome.winner, Some(0));
+
+        let config = MctsConfig {
+            budget: Budget::Iterations(2000),
+            seed: Some(16),
+            ..Default::default()
+        };
+        let hooks = Hooks::new().rollout(Arc::new(Uniform));
+        assert_eq!(run_with_hooks(&Nim::new(5), &config, &hooks), 1);
+    }
+
+    #[test]
+    fn search_respects_node_limit() {
+        let config = MctsConfig {
+            budget: Budget::Iterations(2000),
+            max_nodes: Some(50),
+            ..Default::default()
+        };
+        let (lines, nodes) = search(&Nim::new(30), &config);
+        assert!(nodes <= 50);
+        assert_eq!(lines.iter().map(|line| line.sims).sum::<u32>(), 2000);
     }
 }
@@ -1,2 +1,20 @@
+#[cfg(feature = "duct")]
+pub mod duct;
+pub mod greedy;
+#[cfg(feature = "std")]
+pub mod human;
+#[cfg(feature = "ismcts")]
+pub mod ismcts;
+#[cfg(feature = "mcts")]
 pub mod mcts;
+#[cfg(feature = "nmcs")]
+pub mod nmcs;
+#[cfg(feature = "qlearn")]
+pub mod qlearn;
 pub mod rand;
+#[cfg(feature = "td")]
+pub mod td;
+#[cfg(feature = "tune")]
+pub mod tune;
+#[cfg(feature = "std")]
+pub mod weighted;
@@ -0,0 +1,109 @@
+use alloc::vec::Vec;
+
+#[cfg(feature = "entropy")]
+use log::debug;
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use crate::Score;
+
+#[cfg(feature = "entropy")]
+const LEVEL: usize = 2;
+
+/// Run level 2 nested Monte Carlo search to select a turn.
+#[cfg(feature = "entropy")]
+pub fn run<G: Score>(game: &G) -> G::Turn {
+    run_with(game, LEVEL)
+}
+
+/// Run nested Monte Carlo search at a level to select a turn.
+///
+/// Each level tries every legal turn followed by a search one level lower,
+/// so the cost grows by about the branching factor times the game length per
+/// level. Level 0 is a single random playout.
+#[cfg(feature = "entropy")]
+pub fn run_with<G: Score>(game: &G, level: usize) -> G::Turn {
+    let (score, seq) = solve(game, level, &mut rand::thread_rng());
+    debug!("best sequence scores {score}");
+    seq.into_iter().next().unwrap()
+}
+
+/// Search for the best sequence of turns to the end of a single-player game.
+///
+/// Returns the best score found for the player to move together with the
+/// sequence of turns reaching it. Passing a seeded generator makes the search
+/// reproducible.
+pub fn solve<G: Score, R: Rng + ?Sized>(
+    game: &G,
+    level: usize,
+    rng: &mut R,
+) -> (f64, Vec<G::Turn>) {
+    let player = game.player();
+    nested(game.clone(), level, &player, rng)
+}
+
+/// Play out a position at a level, memorizing the best sequence found.
+fn nested<G: Score, R: Rng + ?Sized>(
+    mut state: G,
+    level: usize,
+    player: &G::Player,
+    rng: &mut R,
+) -> (f64, Vec<G::Turn>) {
+    // Play randomly at the lowest level
+    if level == 0 {
+        let mut seq = Vec::new();
+        while !state.over() {
+            let turn = state.turns().choose(rng).unwrap().clone();
+            state.play(turn.clone());
+            seq.push(turn);
+        }
+        return (state.score(player), seq);
+    }
+
+    let mut best = (f64::NEG_INFINITY, Vec::new());
+    let mut played = Vec::new();
+    while !state.over() {
+        // Search each turn a level lower
+        for turn in state.turns() {
+            let mut child = state.clone();
+            child.play(turn.clone());
+            let (score, seq) = nested(child, level - 1, player, rng);
+            if score > best.0 {
+                let mut line = played.clone();
+                line.push(turn);
+                line.extend(seq);
+                best = (score, line);
+            }
+        }
+
+        // Follow the best sequence found so far
+        let turn = best.1[played.len()].clone();
+        state.play(turn.clone());
+        played.push(turn);
+    }
+    if played.is_empty() {
+        best.0 = state.score(player);
+    }
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::tests::Steps;
+
+    #[test]
+    fn solve_finds_best_sequence() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let (score, seq) = solve(&Steps::default(), 4, &mut rng);
+        assert_eq!(score, 4.);
+        assert_eq!(seq, [0, 1, 2, 3, 4]);
+
+        // A random playout still reaches the end
+        let (_, seq) = solve(&Steps::default(), 0, &mut rng);
+        assert_eq!(seq.len(), 5);
+    }
+}
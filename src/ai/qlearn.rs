@@ -0,0 +1,178 @@
+//! Tabular Q-learning through self-play.
+//!
+//! A [`QTable`] learns the value of each turn from each position it has seen,
+//! keyed by the position's hash, so it suits games small enough to visit most
+//! positions, like Nim or tic-tac-toe.
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use crate::util::hash;
+use crate::Game;
+
+const ALPHA: f64 = 0.5;
+const GAMMA: f64 = 0.9;
+const EPSILON: f64 = 0.2;
+
+/// A table of learned turn values.
+///
+/// Values are from the view of the player making the turn, from -1 for a
+/// certain loss to 1 for a certain win.
+#[derive(Clone, Debug, PartialEq)]
+pub struct QTable {
+    /// Learning rate of each update.
+    pub alpha: f64,
+    /// Discount applied to the value of the next position.
+    pub gamma: f64,
+    /// Probability of exploring a random turn while training.
+    pub epsilon: f64,
+    values: HashMap<(u64, String), f64>,
+}
+
+impl QTable {
+    /// Create a new, empty QTable.
+    pub fn new() -> QTable {
+        QTable::default()
+    }
+
+    /// Get the learned value of a turn from a position.
+    ///
+    /// Unseen turns have a value of 0.
+    pub fn value<G: Game>(&self, game: &G, turn: &G::Turn) -> f64 {
+        self.values
+            .get(&(hash(&game.to_string()), turn.to_string()))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Get the number of learned values.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Check if nothing has been learned.
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Select the turn with the highest learned value.
+    ///
+    /// Ties go to the first turn.
+    pub fn run<G: Game>(&self, game: &G) -> G::Turn {
+        let turns = game.turns();
+        let mut best = (f64::NEG_INFINITY, &turns[0]);
+        for turn in &turns {
+            let value = self.value(game, turn);
+            if value > best.0 {
+                best = (value, turn);
+            }
+        }
+        best.1.clone()
+    }
+
+    /// Learn from `episodes` games of self-play starting from `game`.
+    pub fn train<G: Game, R: Rng + ?Sized>(&mut self, game: &G, episodes: usize, rng: &mut R) {
+        for _ in 0..episodes {
+            let mut state = game.clone();
+            while !state.over() {
+                // Explore a random turn or exploit the best one
+                let turn = match rng.gen_bool(self.epsilon.clamp(0., 1.)) {
+                    true => state.turns().choose(rng).unwrap().clone(),
+                    false => self.run(&state),
+                };
+                let player = state.player();
+                let key = (hash(&state.to_string()), turn.to_string());
+                state.play(turn);
+
+                // Update towards the reward or the value of the next position
+                let target = if state.over() {
+                    match state.winner() {
+                        Some(winner) if winner == player => 1.,
+                        Some(_) => -1.,
+                        None => 0.,
+                    }
+                } else {
+                    let best = state
+                        .turns()
+                        .iter()
+                        .map(|turn| self.value(&state, turn))
+                        .fold(f64::NEG_INFINITY, f64::max);
+                    // The opponent's gain is the player's loss
+                    let sign = if state.player() == player { 1. } else { -1. };
+                    sign * self.gamma * best
+                };
+                let value = self.values.entry(key).or_default();
+                *value += self.alpha * (target - *value);
+            }
+        }
+    }
+
+    /// Write the learned values, one per line.
+    pub fn save(&self, mut output: impl Write) -> io::Result<()> {
+        let mut entries: Vec<_> = self.values.iter().collect();
+        entries.sort_by(|a, b| a.0.cmp(b.0));
+        for ((hash, turn), value) in entries {
+            writeln!(output, "{hash:016x} {value} {turn}")?;
+        }
+        Ok(())
+    }
+
+    /// Read values written by [`save`](QTable::save) into a new QTable.
+    pub fn load(input: impl BufRead) -> io::Result<QTable> {
+        let mut table = QTable::new();
+        for line in input.lines() {
+            let line = line?;
+            let invalid =
+                || io::Error::new(io::ErrorKind::InvalidData, format!("invalid entry: {line}"));
+            let mut fields = line.splitn(3, ' ');
+            let (Some(hash), Some(value), Some(turn)) =
+                (fields.next(), fields.next(), fields.next())
+            else {
+                return Err(invalid());
+            };
+            let hash = u64::from_str_radix(hash, 16).map_err(|_| invalid())?;
+            let value = value.parse().map_err(|_| invalid())?;
+            table.values.insert((hash, turn.to_string()), value);
+        }
+        Ok(table)
+    }
+}
+
+impl Default for QTable {
+    fn default() -> Self {
+        QTable {
+            alpha: ALPHA,
+            gamma: GAMMA,
+            epsilon: EPSILON,
+            values: HashMap::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::tests::Nim;
+
+    #[test]
+    fn learns_nim_and_round_trips() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut table = QTable::new();
+        table.train(&Nim::new(9), 3000, &mut rng);
+        // Leave a multiple of four stones
+        assert_eq!(table.run(&Nim::new(9)), 1);
+        assert_eq!(table.run(&Nim::new(6)), 2);
+
+        let mut saved = Vec::new();
+        table.save(&mut saved).unwrap();
+        let loaded = QTable::load(&saved[..]).unwrap();
+        assert_eq!(loaded, table);
+        assert!(QTable::load(&b"zz 1 1\n"[..]).is_err());
+    }
+}
@@ -1,11 +1,18 @@
 use rand::seq::SliceRandom;
+use rand::Rng;
 
 use crate::Game;
 
 /// Randomly select a turn.
+#[cfg(feature = "entropy")]
 pub fn run<G: Game>(game: &G) -> G::Turn {
-    game.turns()
-        .choose(&mut rand::thread_rng())
-        .unwrap()
-        .clone()
+    run_with(game, &mut rand::thread_rng())
+}
+
+/// Randomly select a turn using a random number generator.
+///
+/// Passing a seeded generator makes games between random agents
+/// reproducible.
+pub fn run_with<G: Game, R: Rng + ?Sized>(game: &G, rng: &mut R) -> G::Turn {
+    game.turns().choose(rng).unwrap().clone()
 }
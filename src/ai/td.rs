@@ -0,0 +1,211 @@
+//! Training value functions with TD(λ) through self-play.
+//!
+//! A [`ValueFunction`] estimates how good a position is with a set of
+//! weights, which [`train`] tunes from the games it plays against itself.
+//! The result is a good start for an [`Evaluate`](crate::Evaluate)
+//! implementation.
+
+use std::fmt::{self, Debug};
+
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use crate::Game;
+
+const ALPHA: f64 = 0.1;
+const LAMBDA: f64 = 0.7;
+const EPSILON: f64 = 0.1;
+
+/// A parametric estimate of the value of positions.
+pub trait ValueFunction<G: Game> {
+    /// Estimate the value of a position for a player, from 0 to 1.
+    fn value(&self, game: &G, player: &G::Player) -> f64;
+
+    /// Get the gradient of the value with respect to each weight.
+    fn gradient(&self, game: &G, player: &G::Player) -> Vec<f64>;
+
+    /// Get the weights.
+    fn weights(&self) -> &[f64];
+
+    /// Get the weights to update them.
+    fn weights_mut(&mut self) -> &mut [f64];
+}
+
+/// A value function which squashes a weighted sum of features with the
+/// logistic function.
+pub struct Linear<G: Game> {
+    /// Extract the features of a position for a player.
+    pub features: fn(&G, &G::Player) -> Vec<f64>,
+    /// Weight of each feature.
+    pub weights: Vec<f64>,
+}
+
+impl<G: Game> Linear<G> {
+    /// Create a new Linear value function with `len` features, all unweighted.
+    pub fn new(features: fn(&G, &G::Player) -> Vec<f64>, len: usize) -> Linear<G> {
+        Linear {
+            features,
+            weights: vec![0.; len],
+        }
+    }
+}
+
+impl<G: Game> Debug for Linear<G> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Linear")
+            .field("weights", &self.weights)
+            .finish()
+    }
+}
+
+impl<G: Game> ValueFunction<G> for Linear<G> {
+    fn value(&self, game: &G, player: &G::Player) -> f64 {
+        let sum: f64 = (self.features)(game, player)
+            .iter()
+            .zip(&self.weights)
+            .map(|(x, w)| x * w)
+            .sum();
+        1. / (1. + (-sum).exp())
+    }
+
+    fn gradient(&self, game: &G, player: &G::Player) -> Vec<f64> {
+        let value = self.value(game, player);
+        (self.features)(game, player)
+            .into_iter()
+            .map(|x| value * (1. - value) * x)
+            .collect()
+    }
+
+    fn weights(&self) -> &[f64] {
+        &self.weights
+    }
+
+    fn weights_mut(&mut self) -> &mut [f64] {
+        &mut self.weights
+    }
+}
+
+/// Configuration for TD(λ) training.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TdConfig {
+    /// Games of self-play to learn from.
+    pub episodes: usize,
+    /// Learning rate of each update.
+    pub alpha: f64,
+    /// Decay of eligibility traces, from 0 for TD(0) to 1 for Monte Carlo.
+    pub lambda: f64,
+    /// Probability of exploring a random turn.
+    pub epsilon: f64,
+}
+
+impl Default for TdConfig {
+    fn default() -> Self {
+        TdConfig {
+            episodes: 1000,
+            alpha: ALPHA,
+            lambda: LAMBDA,
+            epsilon: EPSILON,
+        }
+    }
+}
+
+/// Train a value function through self-play starting from `game`.
+///
+/// Players pick the turn leading to the best value for themselves, or a
+/// random turn with probability `epsilon`. Values are learned for the player
+/// to move in `game`, with finished games worth 1 for a win, 0 for a loss,
+/// and 0.5 for a tie. Returns the trained weights.
+pub fn train<G, V, R>(game: &G, value: &mut V, config: &TdConfig, rng: &mut R) -> Vec<f64>
+where
+    G: Game,
+    V: ValueFunction<G>,
+    R: Rng + ?Sized,
+{
+    let player = game.player();
+    for _ in 0..config.episodes {
+        let mut state = game.clone();
+        let mut traces = vec![0.; value.weights().len()];
+        while !state.over() {
+            let before = value.value(&state, &player);
+            let gradient = value.gradient(&state, &player);
+
+            // Play the next turn
+            let turn = choose(&state, value, config.epsilon, rng);
+            state.play(turn);
+
+            // Learn from the difference to the next value
+            let after = match (state.over(), state.winner()) {
+                (true, Some(winner)) if winner == player => 1.,
+                (true, Some(_)) => 0.,
+                (true, None) => 0.5,
+                (false, _) => value.value(&state, &player),
+            };
+            let error = after - before;
+            for ((trace, grad), weight) in traces
+                .iter_mut()
+                .zip(gradient)
+                .zip(value.weights_mut().iter_mut())
+            {
+                *trace = config.lambda * *trace + grad;
+                *weight += config.alpha * error * *trace;
+            }
+        }
+    }
+    value.weights().to_vec()
+}
+
+/// Choose the turn leading to the best value for the player to move.
+fn choose<G, V, R>(game: &G, value: &V, epsilon: f64, rng: &mut R) -> G::Turn
+where
+    G: Game,
+    V: ValueFunction<G>,
+    R: Rng + ?Sized,
+{
+    let turns = game.turns();
+    if rng.gen_bool(epsilon.clamp(0., 1.)) {
+        return turns.choose(rng).unwrap().clone();
+    }
+    let player = game.player();
+    let mut best = (f64::NEG_INFINITY, &turns[0]);
+    for turn in &turns {
+        let mut state = game.clone();
+        state.play(turn.clone());
+        let value = value.value(&state, &player);
+        if value > best.0 {
+            best = (value, turn);
+        }
+    }
+    best.1.clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::tests::Nim;
+
+    // Stones left modulo four, signed by whether the player is to move
+    fn features(game: &Nim, player: &u8) -> Vec<f64> {
+        let sign = if game.player == *player { 1. } else { -1. };
+        let mut features = vec![0.; 4];
+        features[game.stones as usize % 4] = sign;
+        features
+    }
+
+    #[test]
+    fn learns_losing_positions() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut value = Linear::new(features, 4);
+        let config = TdConfig {
+            episodes: 2000,
+            ..Default::default()
+        };
+        let weights = train(&Nim::new(10), &mut value, &config, &mut rng);
+        assert_eq!(weights, value.weights);
+        // Facing a multiple of four stones is bad for the player to move
+        assert!(weights[1..].iter().all(|&w| w > weights[0]));
+        assert!(value.value(&Nim::new(8), &0) < 0.5);
+    }
+}
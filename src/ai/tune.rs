@@ -0,0 +1,346 @@
+//! Tuning engine parameters with SPSA.
+//!
+//! Simultaneous perturbation stochastic approximation nudges every parameter
+//! at once: each iteration plays a match between two copies of the current
+//! values, one shifted up and one down by a random sign per parameter, and
+//! moves towards whichever did better. Only match results are needed, so
+//! anything from exploration constants to evaluation weights can be tuned.
+//!
+//! For rugged parameter spaces, [`evolve`] instead evolves a population of
+//! candidates, playing [round-robins](tournament::round_robin) between them
+//! to pick parents.
+
+use log::debug;
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use crate::arena::tournament::{self, Agent};
+use crate::arena::{self, MatchConfig};
+use crate::Game;
+
+const RATE: f64 = 1.;
+const ALPHA: f64 = 0.602;
+const GAMMA: f64 = 0.101;
+const POPULATION: usize = 16;
+const TOURNAMENT: usize = 3;
+const CROSSOVER: f64 = 0.5;
+const MUTATION: f64 = 0.2;
+const GAMES: usize = 10;
+
+/// A parameter being tuned.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Parameter {
+    /// Current value.
+    pub value: f64,
+    /// Size of the initial perturbation.
+    pub step: f64,
+    /// Smallest allowed value.
+    pub min: f64,
+    /// Largest allowed value.
+    pub max: f64,
+}
+
+impl Parameter {
+    /// Create an unbounded parameter.
+    pub fn new(value: f64, step: f64) -> Parameter {
+        Parameter {
+            value,
+            step,
+            min: f64::NEG_INFINITY,
+            max: f64::INFINITY,
+        }
+    }
+}
+
+/// Configuration for SPSA tuning.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SpsaConfig {
+    /// Number of matches to play.
+    pub iterations: usize,
+    /// Initial learning rate, in steps moved when a perturbation wins every
+    /// game of its match.
+    pub rate: f64,
+    /// Decay exponent of the learning rate.
+    pub alpha: f64,
+    /// Decay exponent of the perturbation size.
+    pub gamma: f64,
+}
+
+impl Default for SpsaConfig {
+    fn default() -> Self {
+        SpsaConfig {
+            iterations: 1000,
+            rate: RATE,
+            alpha: ALPHA,
+            gamma: GAMMA,
+        }
+    }
+}
+
+/// Tune parameters with SPSA.
+///
+/// `play` is given the values of two perturbations and returns the score of
+/// the first against the second, from 0 for losing every game to 1 for
+/// winning every game. The parameters are updated in place.
+pub fn tune<F, R>(params: &mut [Parameter], config: &SpsaConfig, mut play: F, rng: &mut R)
+where
+    F: FnMut(&[f64], &[f64]) -> f64,
+    R: Rng + ?Sized,
+{
+    // Keep early steps small relative to later ones
+    let stability = config.iterations as f64 / 10.;
+    for k in 0..config.iterations {
+        let rate = config.rate / (k as f64 + 1. + stability).powf(config.alpha);
+        let spread = 1. / (k as f64 + 1.).powf(config.gamma);
+
+        // Perturb every parameter by a random sign at once
+        let signs: Vec<f64> = params
+            .iter()
+            .map(|_| if rng.gen() { 1. } else { -1. })
+            .collect();
+        let shifted = |sign: f64| -> Vec<f64> {
+            params
+                .iter()
+                .zip(&signs)
+                .map(|(param, delta)| {
+                    (param.value + sign * spread * param.step * delta).clamp(param.min, param.max)
+                })
+                .collect()
+        };
+        let (plus, minus) = (shifted(1.), shifted(-1.));
+        let score = play(&plus, &minus);
+
+        // Move towards the better perturbation
+        for (param, delta) in params.iter_mut().zip(&signs) {
+            let change = rate * param.step * 2. * (score - 0.5) * delta;
+            param.value = (param.value + change).clamp(param.min, param.max);
+        }
+        debug!("iteration {k}: scored {score:.3}, {params:?}");
+    }
+}
+
+/// Configuration for genetic tuning.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GeneticConfig {
+    /// Number of candidates in each generation.
+    pub population: usize,
+    /// Number of generations to evolve.
+    pub generations: usize,
+    /// Number of candidates competing to be picked as each parent.
+    pub tournament: usize,
+    /// Probability of taking each value from the second parent.
+    pub crossover: f64,
+    /// Probability of shifting each value by up to its step.
+    pub mutation: f64,
+    /// Match played between each pair of candidates.
+    pub matches: MatchConfig,
+}
+
+impl Default for GeneticConfig {
+    fn default() -> Self {
+        GeneticConfig {
+            population: POPULATION,
+            generations: 50,
+            tournament: TOURNAMENT,
+            crossover: CROSSOVER,
+            mutation: MUTATION,
+            matches: MatchConfig {
+                games: GAMES,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// Tune parameters with a genetic algorithm.
+///
+/// The population starts around the current values, and `agent` creates a
+/// player from the values of a candidate. Each generation plays a
+/// [`round_robin`](tournament::round_robin) of
+/// [`matches`](GeneticConfig::matches) from `game`, then breeds the next
+/// generation from parents picked by tournament on their total scores,
+/// keeping the best candidate as is. The parameters are set to the best
+/// candidate of the last generation.
+pub fn evolve<'a, G, F, R>(
+    game: &G,
+    params: &mut [Parameter],
+    config: &GeneticConfig,
+    mut agent: F,
+    rng: &mut R,
+) where
+    G: Game,
+    F: FnMut(&[f64]) -> Agent<'a, G>,
+    R: Rng + ?Sized,
+{
+    let size = config.population.max(2);
+    let mutate = |values: &mut Vec<f64>, rng: &mut R, rate: f64| {
+        for (value, param) in values.iter_mut().zip(params.iter()) {
+            if rng.gen_bool(rate) {
+                *value += rng.gen_range(-1. ..=1.) * param.step;
+            }
+            *value = value.clamp(param.min, param.max);
+        }
+    };
+    let start: Vec<f64> = params.iter().map(|param| param.value).collect();
+    let mut population: Vec<Vec<f64>> = vec![start.clone()];
+    while population.len() < size {
+        let mut values = start.clone();
+        mutate(&mut values, rng, 1.);
+        population.push(values);
+    }
+
+    let mut fitness = round_robin(game, &population, &mut agent, &config.matches);
+    for generation in 0..config.generations {
+        let best = best(&fitness);
+        debug!("generation {generation}: best {:?}", population[best]);
+
+        // Breed from tournament winners, keeping the best as is
+        let mut next = vec![population[best].clone()];
+        while next.len() < size {
+            let first = select(&fitness, config.tournament, rng);
+            let second = select(&fitness, config.tournament, rng);
+            let mut child: Vec<f64> = population[first]
+                .iter()
+                .zip(&population[second])
+                .map(|(&a, &b)| if rng.gen_bool(config.crossover) { b } else { a })
+                .collect();
+            mutate(&mut child, rng, config.mutation);
+            next.push(child);
+        }
+        population = next;
+        fitness = round_robin(game, &population, &mut agent, &config.matches);
+    }
+
+    let best = &population[best(&fitness)];
+    for (param, &value) in params.iter_mut().zip(best) {
+        param.value = value;
+    }
+}
+
+/// Play every candidate against every other, totalling their scores.
+fn round_robin<'a, G, F>(
+    game: &G,
+    population: &[Vec<f64>],
+    agent: &mut F,
+    config: &MatchConfig,
+) -> Vec<f64>
+where
+    G: Game,
+    F: FnMut(&[f64]) -> Agent<'a, G>,
+{
+    let names: Vec<_> = (0..population.len()).map(|idx| idx.to_string()).collect();
+    let mut agents: Vec<_> = names
+        .iter()
+        .zip(population)
+        .map(|(name, values)| (name.as_str(), agent(values)))
+        .collect();
+    let table = tournament::round_robin(game, &mut agents, config);
+    (0..population.len()).map(|idx| table.score(idx)).collect()
+}
+
+/// Get the fittest candidate, preferring the first on ties.
+fn best(fitness: &[f64]) -> usize {
+    (0..fitness.len()).fold(0, |best, i| match fitness[i] > fitness[best] {
+        true => i,
+        false => best,
+    })
+}
+
+/// Pick the fittest of a few random candidates.
+fn select<R: Rng + ?Sized>(fitness: &[f64], tournament: usize, rng: &mut R) -> usize {
+    let indices: Vec<_> = (0..fitness.len()).collect();
+    let entrants = indices.choose_multiple(rng, tournament.max(1));
+    entrants
+        .copied()
+        .reduce(|best, i| if fitness[i] > fitness[best] { i } else { best })
+        .unwrap()
+}
+
+/// Play a match between two agents, returning the score of the first.
+///
+/// The match is played by [`arena::run`], so the first agent moves first in
+/// even games and an agent playing an illegal turn forfeits. Wins count 1 and
+/// ties count half, so the result suits [`tune`] and [`evolve`].
+pub fn compare<G, A, B>(game: &G, first: A, second: B, games: usize) -> f64
+where
+    G: Game,
+    A: FnMut(&G) -> G::Turn,
+    B: FnMut(&G) -> G::Turn,
+{
+    arena::run(game, first, second, games).score()
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::tests::Nim;
+
+    #[test]
+    fn tune_converges() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut params = [
+            Parameter::new(0., 1.),
+            Parameter {
+                max: 1.,
+                ..Parameter::new(0., 1.)
+            },
+        ];
+        // Expected score of an agent whose strength peaks at (3, 2)
+        let loss = |x: &[f64]| (x[0] - 3.).powi(2) + (x[1] - 2.).powi(2);
+        let play = |a: &[f64], b: &[f64]| 1. / (1. + (loss(a) - loss(b)).exp());
+        let config = SpsaConfig {
+            iterations: 500,
+            ..Default::default()
+        };
+        tune(&mut params, &config, play, &mut rng);
+        assert!((params[0].value - 3.).abs() < 0.3);
+        // Bounded short of its peak
+        assert_eq!(params[1].value, 1.);
+    }
+
+    #[test]
+    fn evolve_converges() {
+        let mut rng = StdRng::seed_from_u64(7);
+        // Candidates play perfectly with their first value above one half,
+        // and their second is ignored
+        let mut params = [
+            Parameter {
+                min: 0.,
+                max: 1.,
+                ..Parameter::new(0., 1.)
+            },
+            Parameter::new(0., 1.),
+        ];
+        let agent = |values: &[f64]| -> Agent<'_, Nim> {
+            let perfect = values[0] > 0.5;
+            Box::new(move |game: &Nim| match perfect {
+                true => (game.stones % 4).max(1),
+                false => 1,
+            })
+        };
+        let config = GeneticConfig {
+            population: 6,
+            generations: 5,
+            matches: MatchConfig {
+                games: 2,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        evolve(&Nim::new(9), &mut params, &config, agent, &mut rng);
+        assert!(params[0].value > 0.5);
+    }
+
+    #[test]
+    fn compare_alternates_seats() {
+        // The first agent always takes one stone, the second plays perfectly
+        let perfect = |game: &Nim| (game.stones % 4).max(1);
+        assert_eq!(compare(&Nim::new(8), |_: &Nim| 1, perfect, 4), 0.);
+        assert_eq!(compare(&Nim::new(8), perfect, perfect, 4), 0.5);
+        // Taking too many stones forfeits
+        assert_eq!(compare(&Nim::new(8), |_: &Nim| 5, perfect, 4), 0.);
+    }
+}
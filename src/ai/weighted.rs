@@ -0,0 +1,72 @@
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use crate::ai::greedy;
+use crate::Evaluate;
+
+#[cfg(feature = "entropy")]
+const TEMPERATURE: f64 = 0.25;
+
+/// Randomly select a turn, weighted towards turns with better evaluations.
+#[cfg(feature = "entropy")]
+pub fn run<G: Evaluate>(game: &G) -> G::Turn {
+    run_with(game, TEMPERATURE, &mut rand::thread_rng())
+}
+
+/// Randomly select a turn by a softmax over heuristic evaluations.
+///
+/// Each turn is weighted by the evaluation of the position it leads to for
+/// the player making it. Higher temperatures play closer to uniformly at
+/// random, while a temperature of zero always plays the best turn.
+pub fn run_with<G: Evaluate, R: Rng + ?Sized>(game: &G, temperature: f64, rng: &mut R) -> G::Turn {
+    if temperature <= 0. {
+        return greedy::run_with(game, 0., rng);
+    }
+
+    // Evaluate the position after each turn
+    let player = game.player();
+    let turns: Vec<_> = game
+        .turns()
+        .into_iter()
+        .map(|turn| {
+            let mut state = game.clone();
+            state.play(turn.clone());
+            (state.evaluate(&player), turn)
+        })
+        .collect();
+
+    // Offset by the best value to keep weights finite
+    let best = turns
+        .iter()
+        .map(|(value, _)| *value)
+        .fold(f64::NEG_INFINITY, f64::max);
+    turns
+        .choose_weighted(rng, |(value, _)| ((value - best) / temperature).exp())
+        .unwrap()
+        .1
+        .clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::tests::Nim;
+
+    #[test]
+    fn temperature_sharpens_choice() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let count = |temperature, rng: &mut StdRng| {
+            (0..200)
+                .filter(|_| run_with(&Nim::new(6), temperature, rng) == 2)
+                .count()
+        };
+        // Taking two stones leaves a multiple of four
+        assert_eq!(count(0., &mut rng), 200);
+        let cold = count(0.1, &mut rng);
+        let hot = count(10., &mut rng);
+        assert!(cold > 190 && hot < 100);
+    }
+}
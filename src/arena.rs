@@ -0,0 +1,250 @@
+//! Matches between agents.
+//!
+//! [`run`] plays a number of games between two agents, alternating who moves
+//! first, and returns a [`MatchReport`] of the results from the first
+//! agent's point of view. Comparing two versions of an engine this way shows
+//! whether a change made it stronger, and at what cost in time.
+//!
+//! With [`run_with`], each pair of games can also start from the same random
+//! opening with the agents swapped, so neither is favoured by the openings it
+//! happened to draw. Many agents can be compared at once in a
+//! [`tournament`].
+//!
+//! An agent which plays an illegal turn forfeits the game.
+
+use std::fmt::{self, Display};
+use std::time::{Duration, Instant};
+
+use log::warn;
+use rand::seq::SliceRandom;
+
+use crate::{util, Game};
+
+pub mod tournament;
+
+/// Configuration of a match.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MatchConfig {
+    /// Games to play.
+    pub games: usize,
+    /// Random turns played before the agents take over.
+    ///
+    /// Each opening is played twice, once with each agent moving first.
+    pub opening: usize,
+    /// Seed for the openings, or `None` to seed from entropy.
+    pub seed: Option<u64>,
+}
+
+impl Default for MatchConfig {
+    fn default() -> Self {
+        MatchConfig {
+            games: 100,
+            opening: 0,
+            seed: None,
+        }
+    }
+}
+
+/// Results of a match, from the first agent's point of view.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MatchReport {
+    /// Games played.
+    pub games: usize,
+    /// Games won by the first agent.
+    pub wins: usize,
+    /// Games drawn.
+    pub draws: usize,
+    /// Games won by the second agent.
+    pub losses: usize,
+    /// Games forfeited by each agent playing an illegal turn.
+    pub forfeits: [usize; 2],
+    /// Turns played by each agent.
+    pub turns: [u64; 2],
+    /// Time spent by each agent deciding its turns.
+    pub time: [Duration; 2],
+}
+
+impl MatchReport {
+    /// Get the score of the first agent, counting wins as 1 and draws as
+    /// half, or 0.5 if no games were played.
+    pub fn score(&self) -> f64 {
+        match self.games {
+            0 => 0.5,
+            games => (self.wins as f64 + self.draws as f64 / 2.) / games as f64,
+        }
+    }
+
+    /// Get the average number of turns per game.
+    pub fn average_length(&self) -> f64 {
+        let plies = self.turns[0] + self.turns[1];
+        plies as f64 / self.games.max(1) as f64
+    }
+
+    /// Get the report from the second agent's point of view.
+    pub fn reversed(&self) -> MatchReport {
+        MatchReport {
+            wins: self.losses,
+            losses: self.wins,
+            forfeits: [self.forfeits[1], self.forfeits[0]],
+            turns: [self.turns[1], self.turns[0]],
+            time: [self.time[1], self.time[0]],
+            ..*self
+        }
+    }
+
+    /// Get the average time an agent spent deciding each turn.
+    pub fn time_per_turn(&self, agent: usize) -> Duration {
+        let turns = u32::try_from(self.turns[agent].max(1)).unwrap_or(u32::MAX);
+        self.time[agent] / turns
+    }
+}
+
+impl Display for MatchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "+{} ={} -{} ({:.1}%), {:.1} turns/game, {:?} vs {:?} per turn",
+            self.wins,
+            self.draws,
+            self.losses,
+            self.score() * 100.,
+            self.average_length(),
+            self.time_per_turn(0),
+            self.time_per_turn(1),
+        )
+    }
+}
+
+/// Play `games` games between two agents from `game`.
+///
+/// The first agent moves first in even games, so seat advantages cancel out
+/// over an even number of games.
+pub fn run<G, A, B>(game: &G, first: A, second: B, games: usize) -> MatchReport
+where
+    G: Game,
+    A: FnMut(&G) -> G::Turn,
+    B: FnMut(&G) -> G::Turn,
+{
+    let config = MatchConfig {
+        games,
+        ..Default::default()
+    };
+    run_with(game, first, second, &config)
+}
+
+/// Play a match between two agents from `game`.
+///
+/// As with [`run`], the first agent moves first in even games. Each odd game
+/// replays the opening of the game before it, with the agents swapped.
+pub fn run_with<G, A, B>(game: &G, mut first: A, mut second: B, config: &MatchConfig) -> MatchReport
+where
+    G: Game,
+    A: FnMut(&G) -> G::Turn,
+    B: FnMut(&G) -> G::Turn,
+{
+    let mut rng = util::rng(config.seed);
+    let mut report = MatchReport {
+        games: config.games,
+        ..Default::default()
+    };
+    let opener = game.player();
+    let mut opening = game.clone();
+    for round in 0..config.games {
+        // Seat 0 moves first
+        let seat = round % 2;
+        if seat == 0 {
+            opening = game.clone();
+            for _ in 0..config.opening {
+                let Some(turn) = opening.turns().choose(&mut rng).cloned() else {
+                    break;
+                };
+                opening.play(turn);
+            }
+        }
+        let mut state = opening.clone();
+        let mut forfeit = None;
+        while !state.over() {
+            let agent = usize::from(usize::from(state.player() != opener) != seat);
+            let start = Instant::now();
+            let turn = match agent {
+                0 => first(&state),
+                _ => second(&state),
+            };
+            report.time[agent] += start.elapsed();
+            report.turns[agent] += 1;
+            if !state.play(turn.clone()) {
+                warn!("agent {agent} forfeits with illegal turn {turn}");
+                forfeit = Some(agent);
+                break;
+            }
+        }
+        // Whether the first agent won, or `None` for a draw
+        let won = match forfeit {
+            Some(agent) => {
+                report.forfeits[agent] += 1;
+                Some(agent == 1)
+            }
+            None => state
+                .winner()
+                .map(|winner| usize::from(winner != opener) == seat),
+        };
+        match won {
+            Some(true) => report.wins += 1,
+            Some(false) => report.losses += 1,
+            None => report.draws += 1,
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::Nim;
+
+    #[test]
+    fn run_aggregates_results() {
+        // The first agent always takes one stone, the second plays perfectly
+        let perfect = |game: &Nim| (game.stones % 4).max(1);
+        let report = run(&Nim::new(8), |_: &Nim| 1, perfect, 4);
+        assert_eq!((report.wins, report.draws, report.losses), (0, 0, 4));
+        assert_eq!(report.score(), 0.);
+        // Moving first from a lost position, the perfect agent needs a turn more
+        assert_eq!(report.turns, [8, 10]);
+        assert_eq!(report.average_length(), 4.5);
+        let reversed = report.reversed();
+        assert_eq!((reversed.wins, reversed.turns), (4, [10, 8]));
+        assert!(report
+            .to_string()
+            .starts_with("+0 =0 -4 (0.0%), 4.5 turns/game"));
+
+        let report = run(&Nim::new(8), perfect, perfect, 4);
+        assert_eq!(report.score(), 0.5);
+        assert_eq!(MatchReport::default().score(), 0.5);
+    }
+
+    #[test]
+    fn openings_are_mirrored() {
+        // Perfect play wins whichever side of an opening it is given
+        let perfect = |game: &Nim| (game.stones % 4).max(1);
+        let config = MatchConfig {
+            games: 10,
+            opening: 3,
+            seed: Some(7),
+        };
+        let report = run_with(&Nim::new(20), perfect, perfect, &config);
+        assert_eq!((report.wins, report.draws, report.losses), (5, 0, 5));
+        // The same seed gives the same openings
+        let again = run_with(&Nim::new(20), perfect, perfect, &config);
+        assert_eq!(again.turns, report.turns);
+    }
+
+    #[test]
+    fn illegal_turns_forfeit() {
+        // Taking three stones is illegal once fewer are left
+        let report = run(&Nim::new(2), |_: &Nim| 3, |_: &Nim| 1, 2);
+        assert_eq!((report.wins, report.draws, report.losses), (0, 0, 2));
+        assert_eq!(report.forfeits, [2, 0]);
+        assert_eq!(report.reversed().forfeits, [0, 2]);
+    }
+}
@@ -0,0 +1,364 @@
+//! Tournaments between many agents.
+//!
+//! [`round_robin`] plays a match between every pairing of the agents entered
+//! and collects the results into a [`Crosstable`], which shows how each agent
+//! did against every other along with its total score.
+//!
+//! For pools too large to play every pairing, [`swiss`] plays a fixed number
+//! of rounds instead, each pairing agents with similar scores who have not
+//! met yet. Its [`SwissTable`] breaks ties by Buchholz score, the total score
+//! of the opponents an agent met.
+
+use std::fmt::{self, Display};
+
+use super::{run_with, MatchConfig, MatchReport};
+use crate::Game;
+
+/// An agent entered into a tournament, which can hold state of its own,
+/// such as an engine configuration.
+pub type Agent<'a, G> = Box<dyn FnMut(&G) -> <G as Game>::Turn + 'a>;
+
+/// Results of a tournament.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Crosstable {
+    /// Names of the agents, in the order they were entered.
+    pub names: Vec<String>,
+    /// Results of each pairing, so that `results[i][j]` is the match between
+    /// agents `i` and `j` from the point of view of `i`.
+    ///
+    /// Agents do not play themselves, leaving empty reports along the
+    /// diagonal.
+    pub results: Vec<Vec<MatchReport>>,
+}
+
+impl Crosstable {
+    /// Get the points scored by an agent against another, counting wins as 1
+    /// and draws as half.
+    pub fn points(&self, agent: usize, opponent: usize) -> f64 {
+        let report = &self.results[agent][opponent];
+        report.wins as f64 + report.draws as f64 / 2.
+    }
+
+    /// Get the total points scored by an agent.
+    pub fn score(&self, agent: usize) -> f64 {
+        (0..self.names.len())
+            .map(|opponent| self.points(agent, opponent))
+            .sum()
+    }
+
+    /// Get the agents from highest total score to lowest, keeping the order
+    /// they were entered in on ties.
+    pub fn standings(&self) -> Vec<usize> {
+        let mut standings: Vec<_> = (0..self.names.len()).collect();
+        standings.sort_by(|&a, &b| self.score(b).total_cmp(&self.score(a)));
+        standings
+    }
+}
+
+/// Shows agents from first to last, with columns of points against each
+/// agent in the same order.
+impl Display for Crosstable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self.names.iter().map(String::len).max().unwrap_or(0);
+        write!(f, "{:>3}  {:width$}", "", "")?;
+        for opponent in 1..=self.names.len() {
+            write!(f, " {opponent:>5}")?;
+        }
+        writeln!(f, " {:>6}", "Score")?;
+        let standings = self.standings();
+        for (rank, &agent) in standings.iter().enumerate() {
+            write!(f, "{:>3}. {:width$}", rank + 1, self.names[agent])?;
+            for &opponent in &standings {
+                match agent == opponent {
+                    true => write!(f, " {:>5}", "-")?,
+                    false => write!(f, " {:>5}", self.points(agent, opponent))?,
+                }
+            }
+            writeln!(f, " {:>6}", self.score(agent))?;
+        }
+        Ok(())
+    }
+}
+
+/// Results of a Swiss tournament.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SwissTable {
+    /// Names of the agents, in the order they were entered.
+    pub names: Vec<String>,
+    /// Rounds played by each agent, in order, with their opponent and the
+    /// match from their point of view.
+    ///
+    /// A bye has no opponent, and counts as a match won outright.
+    pub rounds: Vec<Vec<(Option<usize>, MatchReport)>>,
+}
+
+impl SwissTable {
+    /// Get the total points scored by an agent, counting wins as 1 and draws
+    /// as half.
+    pub fn score(&self, agent: usize) -> f64 {
+        self.rounds[agent]
+            .iter()
+            .map(|(_, report)| report.wins as f64 + report.draws as f64 / 2.)
+            .sum()
+    }
+
+    /// Get the Buchholz score of an agent, the total score of every opponent
+    /// it met.
+    pub fn buchholz(&self, agent: usize) -> f64 {
+        self.rounds[agent]
+            .iter()
+            .filter_map(|&(opponent, _)| opponent)
+            .map(|opponent| self.score(opponent))
+            .sum()
+    }
+
+    /// Get the agents from highest total score to lowest, breaking ties by
+    /// Buchholz score, then by the order they were entered in.
+    pub fn standings(&self) -> Vec<usize> {
+        let mut standings: Vec<_> = (0..self.names.len()).collect();
+        standings.sort_by(|&a, &b| {
+            let score = self.score(b).total_cmp(&self.score(a));
+            score.then(self.buchholz(b).total_cmp(&self.buchholz(a)))
+        });
+        standings
+    }
+
+    /// Check if two agents have already met.
+    fn met(&self, agent: usize, opponent: usize) -> bool {
+        self.rounds[agent]
+            .iter()
+            .any(|&(other, _)| other == Some(opponent))
+    }
+
+    /// Check if an agent has already had a bye.
+    fn rested(&self, agent: usize) -> bool {
+        self.rounds[agent].iter().any(|(other, _)| other.is_none())
+    }
+
+    /// Pair agents for the next round, returning the pairings and the agent
+    /// given a bye, if any.
+    ///
+    /// Agents are paired from the top of the standings down, each with the
+    /// highest ranked agent left which it has not met. If there is no way to
+    /// avoid a rematch, each agent instead plays the next in the standings.
+    /// With an odd number of agents, the lowest ranked agent yet to have a bye
+    /// sits out.
+    fn pair(&self) -> (Vec<(usize, usize)>, Option<usize>) {
+        let mut unpaired = self.standings();
+        let bye = (unpaired.len() % 2 == 1).then(|| {
+            let rested = unpaired.iter().rposition(|&agent| !self.rested(agent));
+            unpaired.remove(rested.unwrap_or(unpaired.len() - 1))
+        });
+        let pairings = self.fresh(&unpaired).unwrap_or_else(|| {
+            let pairs = unpaired.chunks(2);
+            pairs.map(|pair| (pair[0], pair[1])).collect()
+        });
+        (pairings, bye)
+    }
+
+    /// Pair off agents without rematches, if possible.
+    ///
+    /// Searches from the top of the standings down, backing up whenever the
+    /// agents left cannot all be paired.
+    fn fresh(&self, unpaired: &[usize]) -> Option<Vec<(usize, usize)>> {
+        let Some((&first, rest)) = unpaired.split_first() else {
+            return Some(Vec::new());
+        };
+        for (idx, &opponent) in rest.iter().enumerate() {
+            if self.met(first, opponent) {
+                continue;
+            }
+            let mut others = rest.to_vec();
+            others.remove(idx);
+            if let Some(mut pairings) = self.fresh(&others) {
+                pairings.insert(0, (first, opponent));
+                return Some(pairings);
+            }
+        }
+        None
+    }
+}
+
+/// Shows agents from first to last, with the opponent and points of each
+/// round, followed by their total and Buchholz scores.
+impl Display for SwissTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self.names.iter().map(String::len).max().unwrap_or(0);
+        let standings = self.standings();
+        let mut ranks = vec![0; standings.len()];
+        for (rank, &agent) in standings.iter().enumerate() {
+            ranks[agent] = rank + 1;
+        }
+        let rounds = self.rounds.iter().map(Vec::len).max().unwrap_or(0);
+        write!(f, "{:>3}  {:width$}", "", "")?;
+        for round in 1..=rounds {
+            write!(f, " {:>7}", format!("R{round}"))?;
+        }
+        writeln!(f, " {:>6} {:>6}", "Score", "Buch")?;
+        for (rank, &agent) in standings.iter().enumerate() {
+            write!(f, "{:>3}. {:width$}", rank + 1, self.names[agent])?;
+            for (opponent, report) in &self.rounds[agent] {
+                let points = report.wins as f64 + report.draws as f64 / 2.;
+                let cell = match opponent {
+                    Some(opponent) => format!("{points}v{}", ranks[*opponent]),
+                    None => format!("{points}bye"),
+                };
+                write!(f, " {cell:>7}")?;
+            }
+            writeln!(f, " {:>6} {:>6}", self.score(agent), self.buchholz(agent))?;
+        }
+        Ok(())
+    }
+}
+
+/// Play a match between every pairing of `agents`, each of `config.games`
+/// games, from `game`.
+///
+/// Each pairing is played as by [`run_with`], with the agent entered first
+/// moving first in even games.
+pub fn round_robin<G: Game>(
+    game: &G,
+    agents: &mut [(&str, Agent<'_, G>)],
+    config: &MatchConfig,
+) -> Crosstable {
+    let mut played = Vec::new();
+    for second in 1..agents.len() {
+        // Each agent plays those entered before it
+        let (before, after) = agents.split_at_mut(second);
+        let agent = &mut after[0].1;
+        for (first, (_, opponent)) in before.iter_mut().enumerate() {
+            let report = run_with(game, &mut *opponent, &mut *agent, config);
+            played.push((first, second, report));
+        }
+    }
+    let n = agents.len();
+    let mut results = vec![vec![MatchReport::default(); n]; n];
+    for (first, second, report) in played {
+        results[second][first] = report.reversed();
+        results[first][second] = report;
+    }
+    Crosstable {
+        names: agents.iter().map(|(name, _)| name.to_string()).collect(),
+        results,
+    }
+}
+
+/// Play a Swiss tournament of `rounds` rounds between `agents`, each round a
+/// match of `config.games` games, from `game`.
+///
+/// Each pairing is played as by [`run_with`], with the higher ranked agent
+/// moving first in even games. An agent given a bye wins each game of its
+/// round.
+pub fn swiss<G: Game>(
+    game: &G,
+    agents: &mut [(&str, Agent<'_, G>)],
+    rounds: usize,
+    config: &MatchConfig,
+) -> SwissTable {
+    let mut table = SwissTable {
+        names: agents.iter().map(|(name, _)| name.to_string()).collect(),
+        rounds: vec![Vec::new(); agents.len()],
+    };
+    for _ in 0..rounds {
+        let (pairings, bye) = table.pair();
+        for (first, second) in pairings {
+            let (this, that) = pick(agents, first, second);
+            let report = run_with(game, &mut *this.1, &mut *that.1, config);
+            table.rounds[second].push((Some(first), report.reversed()));
+            table.rounds[first].push((Some(second), report));
+        }
+        if let Some(agent) = bye {
+            let report = MatchReport {
+                games: config.games,
+                wins: config.games,
+                ..Default::default()
+            };
+            table.rounds[agent].push((None, report));
+        }
+    }
+    table
+}
+
+/// Borrow two distinct elements of a slice at once.
+fn pick<T>(items: &mut [T], a: usize, b: usize) -> (&mut T, &mut T) {
+    assert_ne!(a, b);
+    match a < b {
+        true => {
+            let (left, right) = items.split_at_mut(b);
+            (&mut left[a], &mut right[0])
+        }
+        false => {
+            let (left, right) = items.split_at_mut(a);
+            (&mut right[0], &mut left[b])
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::Nim;
+
+    #[test]
+    fn round_robin_fills_crosstable() {
+        let perfect = |game: &Nim| (game.stones % 4).max(1);
+        let mut agents: Vec<(&str, Agent<Nim>)> = vec![
+            ("ones", Box::new(|_: &Nim| 1)),
+            ("twos", Box::new(|game: &Nim| game.stones.min(2))),
+            ("perfect", Box::new(perfect)),
+        ];
+        let config = MatchConfig {
+            games: 4,
+            ..Default::default()
+        };
+        let table = round_robin(&Nim::new(8), &mut agents, &config);
+        assert_eq!(table.standings()[0], 2);
+        assert_eq!(table.score(2), 8.);
+        // Every game gives out a point
+        let total: f64 = (0..3).map(|agent| table.score(agent)).sum();
+        assert_eq!(total, 12.);
+        assert_eq!(table.points(0, 1) + table.points(1, 0), 4.);
+
+        let expected = "                 1     2     3  Score
+  1. perfect     -     4     4      8
+  2. twos        0     -     4      4
+  3. ones        0     0     -      0
+";
+        assert_eq!(table.to_string(), expected);
+    }
+
+    #[test]
+    fn swiss_pairs_by_score() {
+        let mut agents: Vec<(&str, Agent<Nim>)> = vec![
+            ("ones", Box::new(|_: &Nim| 1)),
+            ("twos", Box::new(|game: &Nim| game.stones.min(2))),
+            ("threes", Box::new(|game: &Nim| game.stones.min(3))),
+            ("perfect", Box::new(|game: &Nim| (game.stones % 4).max(1))),
+            ("lazy", Box::new(|_: &Nim| 1)),
+        ];
+        let config = MatchConfig {
+            games: 2,
+            ..Default::default()
+        };
+        let table = swiss(&Nim::new(8), &mut agents, 3, &config);
+        let standings = table.standings();
+        let scores: Vec<_> = standings.iter().map(|&agent| table.score(agent)).collect();
+        assert!(scores.windows(2).all(|pair| pair[0] >= pair[1]));
+        // Two games are won each round by every pairing and the bye
+        assert_eq!(scores.iter().sum::<f64>(), 18.);
+        for agent in 0..5 {
+            assert_eq!(table.rounds[agent].len(), 3);
+            // Nobody sits out twice, or plays the same opponent twice
+            let mut met: Vec<_> = table.rounds[agent]
+                .iter()
+                .map(|(other, _)| *other)
+                .collect();
+            met.sort();
+            met.dedup();
+            assert_eq!(met.len(), 3);
+        }
+        assert!(table
+            .to_string()
+            .starts_with("                  R1      R2      R3  Score   Buch\n"));
+    }
+}
@@ -0,0 +1,160 @@
+use std::io::{self, BufRead, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use crate::ai::mcts::{self, MctsConfig};
+use crate::util::json;
+use crate::Game;
+
+/// Engine evaluation of a single position.
+#[derive(Clone, Debug)]
+pub struct Evaluation<G: Game> {
+    /// Most simulated turn.
+    pub best: G::Turn,
+    /// Estimated win rate for the player to move.
+    pub eval: f64,
+    /// Principal variation, starting with the best turn.
+    pub pv: Vec<G::Turn>,
+    /// Nodes in the search tree.
+    pub nodes: usize,
+    /// Simulations run.
+    pub sims: u32,
+}
+
+/// Evaluate each position in parallel using MCTS.
+///
+/// Returns an evaluation for every position in input order, or `None` for
+/// positions where the game is already over.
+pub fn evaluate<G>(
+    positions: &[G],
+    config: &MctsConfig,
+    threads: usize,
+) -> Vec<Option<Evaluation<G>>>
+where
+    G: Game + Sync,
+    G::Turn: Send,
+{
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new((0..positions.len()).map(|_| None).collect::<Vec<_>>());
+
+    thread::scope(|scope| {
+        for _ in 0..threads.clamp(1, positions.len().max(1)) {
+            scope.spawn(|| loop {
+                // Claim the next unevaluated position
+                let idx = next.fetch_add(1, Ordering::Relaxed);
+                let Some(game) = positions.get(idx) else {
+                    break;
+                };
+                let eval = (!game.over()).then(|| {
+                    let (lines, nodes) = mcts::search(game, config);
+                    let best = &lines[0];
+                    Evaluation {
+                        best: best.pv[0].clone(),
+                        eval: best.wins / best.sims.max(1) as f64,
+                        pv: best.pv.clone(),
+                        nodes,
+                        sims: lines.iter().map(|line| line.sims).sum(),
+                    }
+                });
+                results.lock().unwrap()[idx] = eval;
+            });
+        }
+    });
+
+    results.into_inner().unwrap()
+}
+
+/// Evaluate setup strings read line by line, writing a JSON array of results.
+///
+/// Blank lines and lines starting with `#` are skipped. Lines which fail to
+/// parse produce an object with an `error` field.
+pub fn run<G, F>(
+    input: impl BufRead,
+    mut output: impl Write,
+    parse: F,
+    config: &MctsConfig,
+    threads: usize,
+) -> io::Result<()>
+where
+    G: Game + Sync,
+    G::Turn: Send,
+    F: Fn(&str) -> Result<G, String>,
+{
+    // Parse every setup, keeping errors in place
+    let mut setups = Vec::new();
+    for line in input.lines() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        setups.push((line.to_string(), parse(line)));
+    }
+    let positions: Vec<_> = setups
+        .iter()
+        .filter_map(|(_, game)| game.as_ref().ok().cloned())
+        .collect();
+    let mut evals = evaluate(&positions, config, threads).into_iter();
+
+    writeln!(output, "[")?;
+    for (idx, (setup, game)) in setups.iter().enumerate() {
+        let setup = json::string(setup);
+        let object = match game {
+            Err(err) => format!(r#"{{"setup":{},"error":{}}}"#, setup, json::string(err)),
+            Ok(game) => match evals.next().flatten() {
+                None => {
+                    let winner = match game.winner() {
+                        Some(player) => json::string(&player.to_string()),
+                        None => "null".to_string(),
+                    };
+                    format!(r#"{{"setup":{},"over":true,"winner":{}}}"#, setup, winner)
+                }
+                Some(eval) => format!(
+                    r#"{{"setup":{},"best":{},"eval":{},"pv":{},"nodes":{},"sims":{}}}"#,
+                    setup,
+                    json::string(&eval.best.to_string()),
+                    json::number(eval.eval),
+                    json::strings(eval.pv.iter().map(ToString::to_string)),
+                    eval.nodes,
+                    eval.sims,
+                ),
+            },
+        };
+        let sep = if idx + 1 < setups.len() { "," } else { "" };
+        writeln!(output, "  {object}{sep}")?;
+    }
+    writeln!(output, "]")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::ai::mcts::Budget;
+    use crate::tests::Nim;
+
+    #[test]
+    fn run_writes_json() {
+        let config = MctsConfig {
+            budget: Budget::Time(Duration::from_millis(20)),
+            ..Default::default()
+        };
+        let input = b"# stones\n5\n\n0\nmany\n";
+        let parse = |s: &str| {
+            s.parse()
+                .map(Nim::new)
+                .map_err(|_| format!("bad setup: {s}"))
+        };
+        let mut output = Vec::new();
+        run(&input[..], &mut output, parse, &config, 2).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<_> = output.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert!(lines[1].starts_with(r#"  {"setup":"5","best":"1""#));
+        assert_eq!(lines[2], r#"  {"setup":"0","over":true,"winner":"1"},"#);
+        assert_eq!(lines[3], r#"  {"setup":"many","error":"bad setup: many"}"#);
+    }
+}
@@ -0,0 +1,136 @@
+//! Measuring the speed of games and agents.
+//!
+//! [`run`] times random playouts of a game and an MCTS search of it,
+//! returning a [`BenchReport`] of their throughput. Comparing reports before
+//! and after a change shows whether it made a game or the engine faster.
+
+use std::fmt::{self, Display};
+use std::time::{Duration, Instant};
+
+use rand::seq::SliceRandom;
+
+use crate::ai::mcts::{self, MctsConfig};
+use crate::Game;
+
+/// Throughput of a game and an agent.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BenchReport {
+    /// Random playouts finished.
+    pub playouts: u64,
+    /// Turns played during playouts.
+    pub plies: u64,
+    /// Time spent on playouts.
+    pub playing: Duration,
+    /// Simulations run by the search.
+    pub sims: u32,
+    /// Nodes expanded by the search.
+    pub nodes: usize,
+    /// Time spent searching.
+    pub searching: Duration,
+}
+
+impl BenchReport {
+    /// Get the random playouts finished per second.
+    pub fn playouts_per_sec(&self) -> f64 {
+        rate(self.playouts as f64, self.playing)
+    }
+
+    /// Get the turns played per second during playouts.
+    pub fn moves_per_sec(&self) -> f64 {
+        rate(self.plies as f64, self.playing)
+    }
+
+    /// Get the simulations run per second by the search.
+    pub fn sims_per_sec(&self) -> f64 {
+        rate(self.sims.into(), self.searching)
+    }
+
+    /// Get the nodes expanded per second by the search.
+    pub fn nodes_per_sec(&self) -> f64 {
+        rate(self.nodes as f64, self.searching)
+    }
+}
+
+impl Display for BenchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:.0} playouts/s, {:.0} moves/s, {:.0} sims/s, {:.0} nodes/s",
+            self.playouts_per_sec(),
+            self.moves_per_sec(),
+            self.sims_per_sec(),
+            self.nodes_per_sec(),
+        )
+    }
+}
+
+/// Benchmark a game and an MCTS configuration.
+///
+/// Random playouts from `game` are timed for about `duration`, using the
+/// configuration's seed, then a search of `game` is run within the
+/// configuration's budget.
+pub fn run<G: Game>(game: &G, config: &MctsConfig, duration: Duration) -> BenchReport {
+    let (playouts, plies, playing) = playouts(game, config, duration);
+    let report = mcts::analyze(game, config);
+    BenchReport {
+        playouts,
+        plies,
+        playing,
+        sims: report.sims,
+        nodes: report.nodes,
+        searching: report.elapsed,
+    }
+}
+
+/// Play random games to the end for about `duration`, counting them and
+/// their turns.
+fn playouts<G: Game>(game: &G, config: &MctsConfig, duration: Duration) -> (u64, u64, Duration) {
+    let mut rng = config.rng();
+    let start = Instant::now();
+    let (mut playouts, mut plies) = (0, 0);
+    // Always finish at least one game
+    while playouts == 0 || start.elapsed() < duration {
+        let mut state = game.clone();
+        while !state.over() {
+            let Some(turn) = state.turns().choose(&mut rng).cloned() else {
+                break;
+            };
+            state.play(turn);
+            plies += 1;
+        }
+        playouts += 1;
+    }
+    (playouts, plies, start.elapsed())
+}
+
+/// Get a count per second, or 0 if no time was spent.
+fn rate(count: f64, elapsed: Duration) -> f64 {
+    match elapsed.as_secs_f64() {
+        secs if secs > 0. => count / secs,
+        _ => 0.,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::mcts::Budget;
+    use crate::tests::Nim;
+
+    #[test]
+    fn run_reports_throughput() {
+        let config = MctsConfig {
+            budget: Budget::Iterations(200),
+            seed: Some(7),
+            ..Default::default()
+        };
+        let report = run(&Nim::new(10), &config, Duration::from_millis(10));
+        assert!(report.playouts > 0);
+        // Every game of Nim from ten stones takes four to ten turns
+        assert!(report.plies >= 4 * report.playouts && report.plies <= 10 * report.playouts);
+        assert_eq!(report.sims, 200);
+        assert!(report.nodes > 1);
+        assert!(report.playouts_per_sec() > 0.);
+        assert!(report.to_string().contains("playouts/s"));
+    }
+}
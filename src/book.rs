@@ -0,0 +1,372 @@
+//! Opening books of known good turns.
+//!
+//! A [`Book`] maps positions to weighted turns. Agents consult it
+//! with [`Book::run`] before searching, so well-known openings are played
+//! instantly instead of spending the whole time budget on them. Books can be
+//! built by hand or with [`Book::build`] from a collection of recorded games.
+//!
+//! Positions are keyed by a hash of their string form, which is stored with
+//! each entry so that colliding positions are never confused.
+
+use std::collections::hash_map::{self, HashMap};
+use std::io::{self, Read, Write};
+
+use log::debug;
+use rand::Rng;
+
+use crate::util::binary::{read_str, read_u32, write_str};
+use crate::util::hash;
+use crate::Game;
+
+/// Leading bytes of a saved book.
+const MAGIC: &[u8; 4] = b"GSBK";
+
+/// Configuration for building a book from recorded games.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BuildConfig {
+    /// Number of plies from the start to include.
+    pub depth: usize,
+    /// Fewest times a turn must have been played to be included.
+    pub min_count: u32,
+    /// Lowest score a turn must have for the player making it, counting ties
+    /// as half a win.
+    pub min_score: f64,
+}
+
+impl Default for BuildConfig {
+    fn default() -> Self {
+        BuildConfig {
+            depth: 12,
+            min_count: 2,
+            min_score: 0.4,
+        }
+    }
+}
+
+/// A table of weighted turns for known positions.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Book {
+    entries: HashMap<u64, Entry>,
+}
+
+/// The turns known from a position.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Entry {
+    /// String form of the position.
+    key: String,
+    /// Turn names with their weights.
+    turns: Vec<(String, u32)>,
+}
+
+impl Book {
+    /// Create a new, empty Book.
+    pub fn new() -> Book {
+        Book::default()
+    }
+
+    /// Add weight to a turn from a position.
+    pub fn insert<G: Game>(&mut self, game: &G, turn: &G::Turn, weight: u32) {
+        let name = turn.to_string();
+        let Some(turns) = self.turns_mut(game.to_string()) else {
+            return;
+        };
+        match turns.iter_mut().find(|(turn, _)| *turn == name) {
+            Some((_, total)) => *total = total.saturating_add(weight),
+            None => turns.push((name, weight)),
+        }
+    }
+
+    /// Add each turn of a line played from `game`, with a weight of 1.
+    pub fn add_line<G: Game>(&mut self, game: &G, turns: &[G::Turn]) {
+        let mut state = game.clone();
+        for turn in turns {
+            self.insert(&state, turn, 1);
+            if !state.play(turn.clone()) {
+                break;
+            }
+        }
+    }
+
+    /// Build a book from games recorded as turns played from `game`.
+    ///
+    /// Turns within the first `depth` plies are tallied with the result of
+    /// their game, and each turn passing the filters is weighted by how often
+    /// it was played. Unfinished games count as ties, and a record is cut
+    /// short at its first illegal turn.
+    pub fn build<G, I, T>(game: &G, records: I, config: &BuildConfig) -> Book
+    where
+        G: Game,
+        I: IntoIterator<Item = T>,
+        T: AsRef<[G::Turn]>,
+    {
+        // Tally the games and the score of each turn
+        let mut tally: HashMap<(String, String), (u32, f64)> = HashMap::new();
+        let mut order = Vec::new();
+        for record in records {
+            let mut state = game.clone();
+            let mut plies = Vec::new();
+            for turn in record.as_ref() {
+                let key = (state.to_string(), turn.to_string());
+                let player = state.player();
+                if !state.play(turn.clone()) {
+                    break;
+                }
+                plies.push((key, player));
+            }
+            let winner = state.winner().filter(|_| state.over());
+            for (key, player) in plies.into_iter().take(config.depth) {
+                let score = match &winner {
+                    Some(winner) if *winner == player => 1.,
+                    Some(_) => 0.,
+                    None => 0.5,
+                };
+                let entry = tally.entry(key.clone()).or_insert_with(|| {
+                    order.push(key);
+                    (0, 0.)
+                });
+                entry.0 += 1;
+                entry.1 += score;
+            }
+        }
+
+        let mut book = Book::new();
+        for key in order {
+            let (count, score) = tally[&key];
+            if count >= config.min_count && score / f64::from(count) >= config.min_score {
+                let (position, turn) = key;
+                if let Some(turns) = book.turns_mut(position) {
+                    turns.push((turn, count));
+                }
+            }
+        }
+        book
+    }
+
+    /// Look up the legal book turns from a position with their weights.
+    pub fn turns<G: Game>(&self, game: &G) -> Vec<(G::Turn, u32)> {
+        let key = game.to_string();
+        let Some(entry) = self
+            .entries
+            .get(&hash(&key))
+            .filter(|entry| entry.key == key)
+        else {
+            return Vec::new();
+        };
+        // Match entries by name against the legal turns
+        game.turns()
+            .into_iter()
+            .filter_map(|turn| {
+                let name = turn.to_string();
+                let (_, weight) = entry.turns.iter().find(|(entry, _)| *entry == name)?;
+                Some((turn, *weight)).filter(|_| *weight > 0)
+            })
+            .collect()
+    }
+
+    /// Pick a book turn from a position at random, by weight.
+    pub fn choose<G: Game, R: Rng + ?Sized>(&self, game: &G, rng: &mut R) -> Option<G::Turn> {
+        let turns = self.turns(game);
+        let total: u64 = turns.iter().map(|(_, weight)| u64::from(*weight)).sum();
+        if total == 0 {
+            return None;
+        }
+        let mut pick = rng.gen_range(0..total);
+        for (turn, weight) in turns {
+            match pick.checked_sub(u64::from(weight)) {
+                Some(rest) => pick = rest,
+                None => return Some(turn),
+            }
+        }
+        unreachable!()
+    }
+
+    /// Play a book turn if there is one, otherwise select a turn with
+    /// `search`.
+    #[cfg(feature = "entropy")]
+    pub fn run<G, F>(&self, game: &G, search: F) -> G::Turn
+    where
+        G: Game,
+        F: FnOnce(&G) -> G::Turn,
+    {
+        match self.choose(game, &mut rand::thread_rng()) {
+            Some(turn) => {
+                debug!("playing book turn {turn}");
+                turn
+            }
+            None => search(game),
+        }
+    }
+
+    /// Get the turns of a position by its string form, adding it if needed.
+    ///
+    /// Returns `None` if another position with the same hash is already in the
+    /// book.
+    fn turns_mut(&mut self, key: String) -> Option<&mut Vec<(String, u32)>> {
+        match self.entries.entry(hash(&key)) {
+            hash_map::Entry::Vacant(entry) => Some(
+                &mut entry
+                    .insert(Entry {
+                        key,
+                        turns: Vec::new(),
+                    })
+                    .turns,
+            ),
+            hash_map::Entry::Occupied(entry) if entry.get().key == key => {
+                Some(&mut entry.into_mut().turns)
+            }
+            hash_map::Entry::Occupied(_) => {
+                debug!("skipping position colliding with another in the book: {key}");
+                None
+            }
+        }
+    }
+
+    /// Get the number of positions in the book.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Check if the book has no positions.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Write the book in its binary format.
+    ///
+    /// All values are little-endian. After the magic bytes `GSBK` comes the
+    /// `u32` number of positions, then for each position its string form, the
+    /// `u32` number of turns, and each turn's name and `u32` weight. Strings
+    /// are written as a `u32` byte length followed by their UTF-8 bytes.
+    pub fn save(&self, mut output: impl Write) -> io::Result<()> {
+        let mut entries: Vec<_> = self.entries.values().collect();
+        entries.sort_by(|a, b| a.key.cmp(&b.key));
+        output.write_all(MAGIC)?;
+        output.write_all(&(entries.len() as u32).to_le_bytes())?;
+        for entry in entries {
+            write_str(&mut output, &entry.key)?;
+            output.write_all(&(entry.turns.len() as u32).to_le_bytes())?;
+            for (turn, weight) in &entry.turns {
+                write_str(&mut output, turn)?;
+                output.write_all(&weight.to_le_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Read a book written by [`save`](Book::save).
+    pub fn load(mut input: impl Read) -> io::Result<Book> {
+        let mut magic = [0; 4];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "not a book"));
+        }
+        let mut book = Book::new();
+        for _ in 0..read_u32(&mut input)? {
+            let key = read_str(&mut input)?;
+            let count = read_u32(&mut input)?;
+            let mut turns = Vec::new();
+            for _ in 0..count {
+                let name = read_str(&mut input)?;
+                turns.push((name, read_u32(&mut input)?));
+            }
+            if let Some(entry) = book.turns_mut(key) {
+                entry.extend(turns);
+            }
+        }
+        Ok(book)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::tests::Nim;
+
+    #[test]
+    fn book_plays_known_turns() {
+        let mut book = Book::new();
+        book.add_line(&Nim::new(10), &[2, 1, 3]);
+        book.insert(&Nim::new(10), &1, 3);
+        // Illegal turns are never played
+        book.insert(&Nim::new(1), &2, 1);
+        assert_eq!(book.len(), 4);
+        assert_eq!(book.turns(&Nim::new(10)), [(1, 3), (2, 1)]);
+        assert_eq!(
+            book.choose(&Nim::new(1), &mut StdRng::seed_from_u64(7)),
+            None
+        );
+
+        let mut rng = StdRng::seed_from_u64(7);
+        let picks: Vec<_> = (0..50)
+            .map(|_| book.choose(&Nim::new(10), &mut rng).unwrap())
+            .collect();
+        assert!(picks.contains(&1) && picks.contains(&2));
+        // Positions off the book fall back to the search
+        #[cfg(feature = "entropy")]
+        assert_eq!(book.run(&Nim::new(5), |_| 1), 1);
+
+        let mut saved = Vec::new();
+        book.save(&mut saved).unwrap();
+        assert_eq!(Book::load(&saved[..]).unwrap(), book);
+        assert!(Book::load(&b"BOOK"[..]).is_err());
+        assert!(Book::load(&saved[..saved.len() - 1]).is_err());
+        // Lengths past the end of the input are not allocated
+        let huge = [&MAGIC[..], &1u32.to_le_bytes(), &u32::MAX.to_le_bytes()].concat();
+        assert!(Book::load(&huge[..]).is_err());
+    }
+
+    #[test]
+    fn book_verifies_positions() {
+        // Hashes are the same on every platform
+        assert_eq!(hash("a"), 0xaf63_dc4c_8601_ec8c);
+        let mut book = Book::new();
+        book.insert(&Nim::new(10), &1, 1);
+        // Another position stored under the same hash is never mistaken for it
+        let key = hash(&Nim::new(10).to_string());
+        book.entries.get_mut(&key).unwrap().key = Nim::new(9).to_string();
+        assert!(book.turns(&Nim::new(10)).is_empty());
+        book.insert(&Nim::new(10), &2, 1);
+        assert_eq!(book.entries[&key].turns, [("1".to_string(), 1)]);
+    }
+
+    #[test]
+    fn build_filters_turns() {
+        // The first player wins the first two games and ties the unfinished
+        // third, and the second player wins the last
+        let records = [
+            vec![2, 3, 1, 1, 3],
+            vec![2, 1, 3, 2, 2],
+            vec![1, 3],
+            vec![3, 3, 3, 1],
+        ];
+        let config = BuildConfig {
+            depth: 2,
+            min_count: 2,
+            min_score: 0.5,
+        };
+        let book = Book::build(&Nim::new(10), &records, &config);
+        // Taking one or three stones is too rare
+        assert_eq!(book.turns(&Nim::new(10)), [(2, 2)]);
+        assert_eq!(book.len(), 1);
+
+        let config = BuildConfig {
+            min_count: 1,
+            ..config
+        };
+        let book = Book::build(&Nim::new(10), &records, &config);
+        // Taking three stones lost
+        assert_eq!(book.turns(&Nim::new(10)), [(1, 1), (2, 2)]);
+        assert!(book
+            .turns(&Nim {
+                stones: 8,
+                player: 1
+            })
+            .is_empty());
+        for stones in [7, 9] {
+            assert_eq!(book.turns(&Nim { stones, player: 1 }), [(3, 1)]);
+        }
+    }
+}
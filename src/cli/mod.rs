@@ -0,0 +1,535 @@
+//! Command line interface for registered games.
+//!
+//! Downstream binaries register their games and hand control to
+//! [`Registry::main`], which provides commands such as
+//! `analyze --interactive`.
+
+use std::error;
+use std::fmt::{self, Display};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter};
+use std::process::ExitCode;
+use std::str::FromStr;
+use std::thread;
+use std::time::Duration;
+
+use crate::ai::mcts::{Budget, MctsConfig};
+#[cfg(feature = "book")]
+use crate::book::Book;
+use crate::clock::TimeControl;
+use crate::debug::SearchMeta;
+#[cfg(feature = "plugin")]
+use crate::plugin::Plugin;
+#[cfg(feature = "tablebase")]
+use crate::tablebase::Tablebase;
+use crate::{batch, Game, ParseTurn, SetupGame, TurnError};
+
+pub mod repl;
+pub mod watch;
+
+pub use self::repl::{Repl, SetupFn};
+pub use self::watch::Watch;
+
+const USAGE: &str = "\
+usage: <command> [options]
+
+commands:
+  analyze           search the starting position
+  batch             evaluate setups from a file as JSON
+  games             list registered games
+  watch             watch the engine play itself
+
+options:
+  --game <name>     game to use (defaults to the only registered game)
+  --interactive     start an interactive analysis session
+  --time <ms>       search time limit
+  --iterations <n>  search iteration limit, for reproducible results
+  --explore <c>     exploration constant used by UCB
+  --threshold <n>   simulations at a leaf before it is expanded
+  --multipv <n>     number of lines printed by a search
+  --book <path>     opening book to probe while analyzing
+  --tb <path>       tablebase to probe while analyzing
+  --input <path>    setups to evaluate, one per line (defaults to stdin)
+  --output <path>   file to write results to (defaults to stdout)
+  --threads <n>     positions to evaluate in parallel
+  --plugin <path>   load a game from a dynamic library (repeatable)
+  --versus <meta>   second engine for watch, as `key=value` search metadata
+  --clock <m>+<s>   clocks for watch, in minutes plus increment seconds
+  --delay <ms>      pause between turns in watch";
+
+/// An error running a command.
+#[derive(Debug)]
+pub enum Error {
+    /// The command line could not be understood.
+    Usage(String),
+    /// An IO operation failed.
+    Io(io::Error),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Usage(msg) => write!(f, "{msg}\n\n{USAGE}"),
+            Error::Io(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+/// Options parsed from the command line.
+#[derive(Clone, Debug)]
+pub struct Options {
+    pub game: Option<String>,
+    pub interactive: bool,
+    pub config: MctsConfig,
+    pub multipv: usize,
+    pub book: Option<String>,
+    pub tablebase: Option<String>,
+    pub input: Option<String>,
+    pub output: Option<String>,
+    pub threads: usize,
+    pub plugins: Vec<String>,
+    pub versus: Option<MctsConfig>,
+    pub clock: Option<TimeControl>,
+    pub delay: Duration,
+}
+
+impl Options {
+    /// Parse options from command line arguments.
+    pub fn parse(args: &[String]) -> Result<Options, Error> {
+        let mut opts = Options {
+            game: None,
+            interactive: false,
+            config: MctsConfig::default(),
+            multipv: 3,
+            book: None,
+            tablebase: None,
+            input: None,
+            output: None,
+            threads: thread::available_parallelism().map_or(1, usize::from),
+            plugins: Vec::new(),
+            versus: None,
+            clock: None,
+            delay: Duration::from_millis(500),
+        };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| {
+                args.next()
+                    .ok_or_else(|| Error::Usage(format!("missing value for {name}")))
+            };
+            match arg.as_str() {
+                "--game" => opts.game = Some(value(arg)?.clone()),
+                "--interactive" | "-i" => opts.interactive = true,
+                "--time" => {
+                    let ms = Self::number(arg, value(arg)?)?;
+                    opts.config.budget = Budget::Time(Duration::from_millis(ms));
+                }
+                "--iterations" => {
+                    opts.config.budget = Budget::Iterations(Self::number(arg, value(arg)?)?);
+                }
+                "--explore" => {
+                    let value = value(arg)?;
+                    opts.config.explore = value
+                        .parse()
+                        .map_err(|_| Error::Usage(format!("invalid value for {arg}: {value}")))?;
+                }
+                "--threshold" => opts.config.threshold = Self::number(arg, value(arg)?)?,
+                "--multipv" => opts.multipv = Self::number(arg, value(arg)?)?,
+                "--book" => opts.book = Some(value(arg)?.clone()),
+                "--tb" => opts.tablebase = Some(value(arg)?.clone()),
+                "--input" => opts.input = Some(value(arg)?.clone()),
+                "--output" => opts.output = Some(value(arg)?.clone()),
+                "--threads" => opts.threads = Self::number(arg, value(arg)?)?,
+                "--plugin" => opts.plugins.push(value(arg)?.clone()),
+                "--versus" => {
+                    let meta: SearchMeta = value(arg)?
+                        .parse()
+                        .map_err(|err| Error::Usage(format!("invalid value for {arg}: {err}")))?;
+                    opts.versus = Some(meta.config);
+                }
+                "--clock" => {
+                    let value = value(arg)?;
+                    let (base, inc) = value.split_once('+').unwrap_or((value, "0"));
+                    opts.clock = Some(TimeControl::new(
+                        Duration::from_secs(60 * Self::number::<u64>(arg, base)?),
+                        Duration::from_secs(Self::number(arg, inc)?),
+                    ));
+                }
+                "--delay" => opts.delay = Duration::from_millis(Self::number(arg, value(arg)?)?),
+                _ => return Err(Error::Usage(format!("unknown option: {arg}"))),
+            }
+        }
+        Ok(opts)
+    }
+
+    /// Parse a numeric option value, which must fit in its type.
+    fn number<T: FromStr>(name: &str, value: &str) -> Result<T, Error> {
+        value
+            .parse()
+            .map_err(|_| Error::Usage(format!("invalid value for {name}: {value}")))
+    }
+}
+
+type Command = Box<dyn Fn(&Options) -> Result<(), Error>>;
+
+/// Commands available for a registered game.
+struct Entry {
+    name: String,
+    analyze: Command,
+    watch: Command,
+    batch: Option<Command>,
+}
+
+/// A collection of games available to the command line.
+#[derive(Default)]
+pub struct Registry {
+    games: Vec<Entry>,
+}
+
+impl Registry {
+    /// Create a new, empty Registry.
+    pub fn new() -> Registry {
+        Registry::default()
+    }
+
+    /// Register a game by name, given a constructor for its initial state.
+    pub fn register<G: Game + 'static>(mut self, name: &str, new: fn() -> G) -> Registry {
+        self.games.push(Entry {
+            name: name.to_string(),
+            analyze: Self::analyze(new, crate::by_name, None),
+            watch: Self::watch(new),
+            batch: None,
+        });
+        self
+    }
+
+    /// Register a game by name whose turns are read with its [`ParseTurn`].
+    pub fn register_with_parser<G>(mut self, name: &str, new: fn() -> G) -> Registry
+    where
+        G: ParseTurn + 'static,
+    {
+        self.games.push(Entry {
+            name: name.to_string(),
+            analyze: Self::analyze(new, G::parse_turn, None),
+            watch: Self::watch(new),
+            batch: None,
+        });
+        self
+    }
+
+    /// Register a game by name which can also be set up from a string.
+    pub fn register_with_setup<G>(
+        mut self,
+        name: &str,
+        new: fn() -> G,
+        setup: fn(&str) -> Result<G, String>,
+    ) -> Registry
+    where
+        G: Game + Sync + 'static,
+        G::Turn: Send,
+    {
+        let batch: Command = Box::new(move |opts| {
+            let input: Box<dyn io::BufRead> = match &opts.input {
+                Some(path) => Box::new(BufReader::new(File::open(path)?)),
+                None => Box::new(io::stdin().lock()),
+            };
+            let output: Box<dyn io::Write> = match &opts.output {
+                Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+                None => Box::new(io::stdout()),
+            };
+            Ok(batch::run(
+                input,
+                output,
+                setup,
+                &opts.config,
+                opts.threads,
+            )?)
+        });
+        self.games.push(Entry {
+            name: name.to_string(),
+            analyze: Self::analyze(new, crate::by_name, Some(setup)),
+            watch: Self::watch(new),
+            batch: Some(batch),
+        });
+        self
+    }
+
+    /// Register a game by name which can be set up with its [`SetupGame`].
+    pub fn register_setup<G>(self, name: &str, new: fn() -> G) -> Registry
+    where
+        G: SetupGame + Sync + 'static,
+        G::Turn: Send,
+    {
+        self.register_with_setup(name, new, |s| {
+            G::from_setup(s).map_err(|err| err.to_string())
+        })
+    }
+
+    /// Register a game loaded from a plugin under its own name.
+    #[cfg(feature = "plugin")]
+    pub fn register_plugin(mut self, plugin: Plugin) -> Registry {
+        self.games.push(Self::plugin(plugin));
+        self
+    }
+
+    /// Create the entry for a plugin game.
+    #[cfg(feature = "plugin")]
+    fn plugin(plugin: Plugin) -> Entry {
+        let name = plugin.name().to_string();
+        let watched = plugin.clone();
+        Entry {
+            name,
+            analyze: Self::analyze(move || plugin.game(), crate::by_name, None),
+            watch: Self::watch(move || watched.game()),
+            batch: None,
+        }
+    }
+
+    /// Create the analyze command for a game, reading turns with `parse`
+    /// and positions with `setup`, if any.
+    fn analyze<G, F>(
+        new: F,
+        parse: fn(&G, &str) -> Result<G::Turn, TurnError>,
+        setup: Option<SetupFn<G>>,
+    ) -> Command
+    where
+        G: Game + 'static,
+        F: Fn() -> G + 'static,
+    {
+        Box::new(move |opts| {
+            let repl = Repl::new(new(), opts.config.clone())
+                .multipv(opts.multipv)
+                .parser(parse);
+            let repl = match setup {
+                Some(setup) => repl.setup(setup),
+                None => repl,
+            };
+            let mut repl = Self::probes(repl, opts)?;
+            if opts.interactive {
+                let stdin = io::stdin();
+                repl.run(stdin.lock(), io::stdout())?;
+            } else {
+                let out = repl.exec("search", &[]).map_err(Error::Usage)?;
+                print!("{out}");
+            }
+            Ok(())
+        })
+    }
+
+    /// Load the book and tablebase named by the options into a session.
+    fn probes<G: Game>(repl: Repl<G>, opts: &Options) -> Result<Repl<G>, Error> {
+        #[cfg(feature = "book")]
+        let repl = match &opts.book {
+            Some(path) => repl.book(Book::load(BufReader::new(File::open(path)?))?),
+            None => repl,
+        };
+        #[cfg(not(feature = "book"))]
+        if opts.book.is_some() {
+            return Err(Error::Usage("book support is not enabled".to_string()));
+        }
+        #[cfg(feature = "tablebase")]
+        let repl = match &opts.tablebase {
+            Some(path) => repl.tablebase(Tablebase::load(BufReader::new(File::open(path)?))?),
+            None => repl,
+        };
+        #[cfg(not(feature = "tablebase"))]
+        if opts.tablebase.is_some() {
+            return Err(Error::Usage("tablebase support is not enabled".to_string()));
+        }
+        Ok(repl)
+    }
+
+    /// Create the watch command for a game.
+    fn watch<G, F>(new: F) -> Command
+    where
+        G: Game + 'static,
+        F: Fn() -> G + 'static,
+    {
+        Box::new(move |opts| {
+            let second = opts.versus.clone().unwrap_or_else(|| opts.config.clone());
+            let mut watch = Watch::new(new(), opts.config.clone(), second).delay(opts.delay);
+            if let Some(control) = &opts.clock {
+                watch = watch.clock(control.clone());
+            }
+            let stdin = io::BufReader::new(io::stdin());
+            Ok(watch.run(stdin, io::stdout())?)
+        })
+    }
+
+    /// Get the names of all registered games.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.games.iter().map(|entry| entry.name.as_str())
+    }
+
+    /// Run a command given its arguments, excluding the program name.
+    pub fn dispatch(&self, args: &[String]) -> Result<(), Error> {
+        let Some((cmd, args)) = args.split_first() else {
+            return Err(Error::Usage("missing command".to_string()));
+        };
+        let opts = Options::parse(args)?;
+
+        // Load plugins requested on the command line
+        let loaded = self.load(&opts)?;
+        let games: Vec<_> = self.games.iter().chain(&loaded).collect();
+
+        match cmd.as_str() {
+            "analyze" => (Self::game(&games, &opts)?.analyze)(&opts),
+            "watch" => (Self::game(&games, &opts)?.watch)(&opts),
+            "batch" => {
+                let entry = Self::game(&games, &opts)?;
+                let batch = entry.batch.as_ref().ok_or_else(|| {
+                    Error::Usage(format!("{} does not support setup strings", entry.name))
+                })?;
+                batch(&opts)
+            }
+            "games" => {
+                games.iter().for_each(|entry| println!("{}", entry.name));
+                Ok(())
+            }
+            "help" | "--help" | "-h" => {
+                println!("{USAGE}");
+                Ok(())
+            }
+            _ => Err(Error::Usage(format!("unknown command: {cmd}"))),
+        }
+    }
+
+    /// Run the command line using the process arguments.
+    pub fn main(&self) -> ExitCode {
+        let args: Vec<_> = std::env::args().skip(1).collect();
+        match self.dispatch(&args) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(err) => {
+                eprintln!("error: {err}");
+                ExitCode::FAILURE
+            }
+        }
+    }
+
+    /// Load the plugins named by the options.
+    #[cfg(feature = "plugin")]
+    fn load(&self, opts: &Options) -> Result<Vec<Entry>, Error> {
+        opts.plugins
+            .iter()
+            .map(|path| {
+                // SAFETY: The user asked for this library to be loaded.
+                let plugin = unsafe { Plugin::load(path) }
+                    .map_err(|err| Error::Usage(format!("{path}: {err}")))?;
+                Ok(Self::plugin(plugin))
+            })
+            .collect()
+    }
+
+    /// Load the plugins named by the options.
+    #[cfg(not(feature = "plugin"))]
+    fn load(&self, opts: &Options) -> Result<Vec<Entry>, Error> {
+        match opts.plugins.first() {
+            Some(_) => Err(Error::Usage("plugin support is not enabled".to_string())),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Find the game selected by the options.
+    fn game<'a>(games: &[&'a Entry], opts: &Options) -> Result<&'a Entry, Error> {
+        match (&opts.game, games) {
+            (Some(name), games) => games
+                .iter()
+                .find(|entry| entry.name == *name)
+                .copied()
+                .ok_or_else(|| Error::Usage(format!("unknown game: {name}"))),
+            (None, [entry]) => Ok(entry),
+            (None, []) => Err(Error::Usage("no games registered".to_string())),
+            (None, _) => Err(Error::Usage("--game is required".to_string())),
+        }
+    }
+}
+
+impl fmt::Debug for Registry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.names()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::Nim;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn parse_options() {
+        let opts = Options::parse(&args(&[
+            "--game",
+            "nim",
+            "-i",
+            "--time",
+            "50",
+            "--explore",
+            "0.5",
+        ]))
+        .unwrap();
+        assert_eq!(opts.game.as_deref(), Some("nim"));
+        assert_eq!(opts.config.explore, 0.5);
+        assert!(opts.interactive);
+        assert_eq!(opts.config.budget, Budget::Time(Duration::from_millis(50)));
+        assert!(Options::parse(&args(&["--time"])).is_err());
+        // Budgets too large for the engine are rejected, not truncated
+        assert!(Options::parse(&args(&["--iterations", "4294967296"])).is_err());
+        assert!(Options::parse(&args(&["--threshold", "4294967296"])).is_err());
+
+        let opts = Options::parse(&args(&["--versus", "iterations=50", "--clock", "5+3"])).unwrap();
+        assert_eq!(opts.versus.unwrap().budget, Budget::Iterations(50));
+        assert_eq!(opts.clock.unwrap().increment, Duration::from_secs(3));
+
+        let opts = Options::parse(&args(&["--book", "nim.book", "--tb", "nim.tb"])).unwrap();
+        assert_eq!(opts.book.as_deref(), Some("nim.book"));
+        assert_eq!(opts.tablebase.as_deref(), Some("nim.tb"));
+    }
+
+    #[test]
+    fn dispatch_selects_game() {
+        let registry = Registry::new().register("nim", || Nim::new(5));
+        assert!(registry
+            .dispatch(&args(&["analyze", "--time", "10"]))
+            .is_ok());
+        assert!(registry
+            .dispatch(&args(&["analyze", "--game", "chess"]))
+            .is_err());
+        assert!(registry.dispatch(&args(&["batch"])).is_err());
+    }
+
+    #[test]
+    fn setup_games_run_batches() {
+        let dir = std::env::temp_dir().join(format!("gamesweet-setup-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (input, output) = (dir.join("input"), dir.join("output"));
+        std::fs::write(&input, "5 0\n4 1\n").unwrap();
+
+        let registry = Registry::new().register_setup("nim", || Nim::new(5));
+        let cmd = [
+            "batch",
+            "--iterations",
+            "50",
+            "--input",
+            input.to_str().unwrap(),
+            "--output",
+            output.to_str().unwrap(),
+        ];
+        registry.dispatch(&args(&cmd)).unwrap();
+        let results = std::fs::read_to_string(&output).unwrap();
+        assert!(results.contains(r#"{"setup":"4 1","best":"#));
+        assert!(!results.contains("error"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,406 @@
+use std::fmt::Write as _;
+use std::io::{self, BufRead, Write};
+use std::time::Duration;
+
+use crate::ai::mcts::{self, Budget, Line, MctsConfig};
+#[cfg(feature = "book")]
+use crate::book::Book;
+#[cfg(feature = "tablebase")]
+use crate::tablebase::{Outcome, Tablebase};
+use crate::{Game, TurnError};
+
+const HELP: &str = "\
+commands:
+  show              print the position and legal turns
+  play <turn>...    play turns, by name or by index
+  undo [n]          take back the last n turns
+  reset             return to the starting position
+  setup <position>  start again from a set up position
+  search [ms|<n>i]  search the position for a time or iteration count
+  multipv <n>       set the number of lines printed by search
+  set <name> <x>    set a search parameter (explore, threshold)
+  book              list the book turns from the position
+  probe             look up the position in the tablebase
+  export            print the explored line with annotations
+  quit              leave the session";
+
+/// Reads a position from its setup.
+pub type SetupFn<G> = fn(&str) -> Result<G, String>;
+
+/// An interactive analysis session reading commands line by line.
+#[derive(Debug)]
+pub struct Repl<G: Game> {
+    start: G,
+    state: G,
+    history: Vec<(G, G::Turn)>,
+    notes: Vec<Option<String>>,
+    config: MctsConfig,
+    multipv: usize,
+    parse: fn(&G, &str) -> Result<G::Turn, TurnError>,
+    setup: Option<SetupFn<G>>,
+    #[cfg(feature = "book")]
+    book: Option<Book>,
+    #[cfg(feature = "tablebase")]
+    tablebase: Option<Tablebase>,
+}
+
+impl<G: Game> Repl<G> {
+    /// Create a new Repl starting from `game`.
+    pub fn new(game: G, config: MctsConfig) -> Repl<G> {
+        Repl {
+            start: game.clone(),
+            state: game,
+            history: Vec::new(),
+            notes: Vec::new(),
+            config,
+            multipv: 3,
+            parse: crate::by_name,
+            setup: None,
+            #[cfg(feature = "book")]
+            book: None,
+            #[cfg(feature = "tablebase")]
+            tablebase: None,
+        }
+    }
+
+    /// Set how turns are read, such as with a game's
+    /// [`ParseTurn::parse_turn`](crate::ParseTurn::parse_turn), rather than
+    /// by name.
+    pub fn parser(mut self, parse: fn(&G, &str) -> Result<G::Turn, TurnError>) -> Repl<G> {
+        self.parse = parse;
+        self
+    }
+
+    /// Set how positions are read by `setup`, such as with a game's
+    /// [`SetupGame::from_setup`](crate::SetupGame::from_setup).
+    pub fn setup(mut self, setup: SetupFn<G>) -> Repl<G> {
+        self.setup = Some(setup);
+        self
+    }
+
+    /// Set the book listed by `book`.
+    #[cfg(feature = "book")]
+    pub fn book(mut self, book: Book) -> Repl<G> {
+        self.book = Some(book);
+        self
+    }
+
+    /// Set the tablebase looked up by `probe`.
+    #[cfg(feature = "tablebase")]
+    pub fn tablebase(mut self, tablebase: Tablebase) -> Repl<G> {
+        self.tablebase = Some(tablebase);
+        self
+    }
+
+    /// Set the number of lines printed by a search.
+    pub fn multipv(mut self, multipv: usize) -> Repl<G> {
+        self.multipv = multipv.max(1);
+        self
+    }
+
+    /// Get the current position.
+    pub fn state(&self) -> &G {
+        &self.state
+    }
+
+    /// Run the session until the input ends or the user quits.
+    pub fn run(&mut self, input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+        writeln!(output, "{}", self.state)?;
+        write!(output, "> ")?;
+        output.flush()?;
+        for line in input.lines() {
+            let line = line?;
+            let mut words = line.split_whitespace();
+            let Some(cmd) = words.next() else {
+                write!(output, "> ")?;
+                output.flush()?;
+                continue;
+            };
+            let args: Vec<_> = words.collect();
+            match cmd {
+                "quit" | "exit" => return Ok(()),
+                _ => match self.exec(cmd, &args) {
+                    Ok(msg) => write!(output, "{msg}")?,
+                    Err(err) => writeln!(output, "error: {err}")?,
+                },
+            }
+            write!(output, "> ")?;
+            output.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Execute a single command, returning its output.
+    pub fn exec(&mut self, cmd: &str, args: &[&str]) -> Result<String, String> {
+        match cmd {
+            "help" => Ok(format!("{HELP}\n")),
+            "show" => Ok(self.show()),
+            "play" => {
+                for arg in args {
+                    let turn = self.find(arg)?;
+                    let before = self.state.clone();
+                    if !self.state.play(turn.clone()) {
+                        return Err(format!("could not play turn: {turn}"));
+                    }
+                    self.history.push((before, turn));
+                }
+                Ok(self.show())
+            }
+            "undo" => {
+                let n = Self::parse(args.first(), 1)?;
+                for _ in 0..n {
+                    match self.history.pop() {
+                        Some((before, _)) => self.state = before,
+                        None => return Err("nothing to undo".to_string()),
+                    }
+                }
+                self.notes.truncate(self.history.len() + 1);
+                Ok(self.show())
+            }
+            "reset" => {
+                self.state = self.start.clone();
+                self.history.clear();
+                self.notes.clear();
+                Ok(self.show())
+            }
+            "setup" => {
+                let setup = self
+                    .setup
+                    .ok_or("positions of this game cannot be set up")?;
+                let game = setup(&args.join(" "))?;
+                self.start = game.clone();
+                self.state = game;
+                self.history.clear();
+                self.notes.clear();
+                Ok(self.show())
+            }
+            "search" => {
+                let mut config = self.config.clone();
+                if let Some(arg) = args.first() {
+                    config.budget = match arg.strip_suffix('i') {
+                        Some(n) => {
+                            let n = Self::parse(Some(&n), 0)?;
+                            let n = u32::try_from(n).map_err(|_| format!("invalid number: {n}"))?;
+                            Budget::Iterations(n)
+                        }
+                        None => Budget::Time(Duration::from_millis(Self::parse(Some(arg), 0)?)),
+                    };
+                }
+                if self.state.over() {
+                    return Err("game is over".to_string());
+                }
+                let (lines, nodes) = mcts::search(&self.state, &config);
+                let ply = self.history.len();
+                if self.notes.len() <= ply {
+                    self.notes.resize(ply + 1, None);
+                }
+                self.notes[ply] = lines.first().map(Self::note);
+                Ok(self.lines(&lines, nodes))
+            }
+            "multipv" => {
+                let n = Self::parse(args.first(), 3)?;
+                self.multipv = usize::try_from(n)
+                    .map_err(|_| format!("invalid number: {n}"))?
+                    .max(1);
+                Ok(String::new())
+            }
+            "set" => {
+                let (Some(name), Some(value)) = (args.first(), args.get(1)) else {
+                    return Err("usage: set <name> <value>".to_string());
+                };
+                let invalid = || format!("invalid value for {name}: {value}");
+                match *name {
+                    "explore" => self.config.explore = value.parse().map_err(|_| invalid())?,
+                    "threshold" => self.config.threshold = value.parse().map_err(|_| invalid())?,
+                    _ => return Err(format!("unknown parameter: {name}")),
+                }
+                Ok(String::new())
+            }
+            #[cfg(feature = "book")]
+            "book" => {
+                let book = self.book.as_ref().ok_or("no book loaded")?;
+                let turns: Vec<_> = book
+                    .turns(&self.state)
+                    .iter()
+                    .map(|(turn, weight)| format!("{turn}:{weight}"))
+                    .collect();
+                match turns.is_empty() {
+                    true => Ok("out of book\n".to_string()),
+                    false => Ok(format!("book: {}\n", turns.join(" "))),
+                }
+            }
+            #[cfg(feature = "tablebase")]
+            "probe" => {
+                let tablebase = self.tablebase.as_ref().ok_or("no tablebase loaded")?;
+                let Some(outcome) = tablebase.probe(&self.state) else {
+                    return Ok("not in tablebase\n".to_string());
+                };
+                let mut out = match outcome {
+                    Outcome::Win(plies) => format!("win in {plies}"),
+                    Outcome::Loss(plies) => format!("loss in {plies}"),
+                    Outcome::Draw => "draw".to_string(),
+                };
+                if let Some(turn) = tablebase.best(&self.state) {
+                    write!(out, ", best {turn}").unwrap();
+                }
+                Ok(format!("{out}\n"))
+            }
+            "export" => Ok(self.export()),
+            _ => Err(format!("unknown command: {cmd} (try `help`)")),
+        }
+    }
+
+    /// Find a legal turn by its name or index.
+    fn find(&self, arg: &str) -> Result<G::Turn, String> {
+        let err = match (self.parse)(&self.state, arg) {
+            Ok(turn) => return Ok(turn),
+            Err(err) => err,
+        };
+        arg.parse::<usize>()
+            .ok()
+            .and_then(|idx| self.state.turns().get(idx).cloned())
+            .ok_or_else(|| err.to_string())
+    }
+
+    /// Parse an optional numeric argument.
+    fn parse(arg: Option<&&str>, default: u64) -> Result<u64, String> {
+        arg.map_or(Ok(default), |arg| {
+            arg.parse().map_err(|_| format!("invalid number: {arg}"))
+        })
+    }
+
+    /// Format the current position.
+    fn show(&self) -> String {
+        let mut out = format!("{}\n", self.state);
+        if self.state.over() {
+            match self.state.winner() {
+                Some(player) => writeln!(out, "Winner: {player}").unwrap(),
+                None => writeln!(out, "It's a tie!").unwrap(),
+            }
+        } else {
+            let turns: Vec<_> = self
+                .state
+                .turns()
+                .iter()
+                .enumerate()
+                .map(|(idx, turn)| format!("{idx}:{turn}"))
+                .collect();
+            writeln!(out, "{} to move: {}", self.state.player(), turns.join(" ")).unwrap();
+        }
+        out
+    }
+
+    /// Format the best lines from a search.
+    fn lines(&self, lines: &[Line<G>], nodes: usize) -> String {
+        let sims: u32 = lines.iter().map(|line| line.sims).sum();
+        let mut out = format!("{sims} simulations, {nodes} nodes\n");
+        for (idx, line) in lines.iter().take(self.multipv).enumerate() {
+            writeln!(out, "{}. {}", idx + 1, Self::note(line)).unwrap();
+        }
+        out
+    }
+
+    /// Summarize a line as an annotation.
+    fn note(line: &Line<G>) -> String {
+        let pv: Vec<_> = line.pv.iter().map(ToString::to_string).collect();
+        format!(
+            "{:5.1}% ({} sims) {}",
+            100. * line.wins / line.sims.max(1) as f64,
+            line.sims,
+            pv.join(" "),
+        )
+    }
+
+    /// Format the played line with search annotations.
+    fn export(&self) -> String {
+        let mut out = String::new();
+        let note = |out: &mut String, ply: usize| {
+            if let Some(Some(note)) = self.notes.get(ply) {
+                write!(out, "{{{}}} ", note.trim()).unwrap();
+            }
+        };
+        for (ply, (before, turn)) in self.history.iter().enumerate() {
+            write!(out, "{}. ", ply + 1).unwrap();
+            note(&mut out, ply);
+            write!(out, "{} ({}) ", turn, before.player()).unwrap();
+        }
+        note(&mut out, self.history.len());
+        format!("{}\n", out.trim_end())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::Nim;
+    use crate::{ParseTurn, SetupGame};
+
+    #[test]
+    fn play_undo_export() {
+        let mut repl = Repl::new(Nim::new(7), MctsConfig::default());
+        let input = b"play 3 1\nundo\nplay 2\nexport\nquit\nplay 1\n";
+        let mut output = Vec::new();
+        repl.run(&input[..], &mut output).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("1. 3 (0) 2. 2 (1)"));
+        assert_eq!(repl.state().stones, 2);
+    }
+
+    #[test]
+    fn illegal_turn_rejected() {
+        let mut repl = Repl::new(Nim::new(2), MctsConfig::default());
+        assert!(repl.exec("play", &["3"]).is_err());
+        assert!(repl.exec("undo", &[]).is_err());
+        // Budgets too large for the engine are rejected, not truncated
+        assert_eq!(
+            repl.exec("search", &["4294967296i"]),
+            Err("invalid number: 4294967296".to_string())
+        );
+    }
+
+    #[test]
+    fn setup_starts_from_position() {
+        let mut repl = Repl::new(Nim::new(5), MctsConfig::default());
+        assert!(repl.exec("setup", &["4", "1"]).is_err());
+        let mut repl = repl.setup(|s| Nim::from_setup(s).map_err(|err| err.to_string()));
+        repl.exec("play", &["1"]).unwrap();
+        repl.exec("setup", &["4", "1"]).unwrap();
+        assert_eq!((repl.state().stones, repl.state().player), (4, 1));
+        assert!(repl.exec("undo", &[]).is_err());
+        assert!(repl.exec("setup", &["x"]).is_err());
+    }
+
+    #[test]
+    #[cfg(all(feature = "book", feature = "tablebase"))]
+    fn probes_book_and_tablebase() {
+        let mut book = Book::new();
+        book.insert(&Nim::new(5), &1, 3);
+        let positions = (0..=5).flat_map(|stones| (0..2).map(move |player| Nim { stones, player }));
+        let tablebase = Tablebase::generate(positions);
+        let mut repl = Repl::new(Nim::new(5), MctsConfig::default());
+        assert_eq!(repl.exec("book", &[]), Err("no book loaded".to_string()));
+        let mut repl = repl.book(book).tablebase(tablebase);
+        assert_eq!(repl.exec("book", &[]), Ok("book: 1:3\n".to_string()));
+        assert_eq!(
+            repl.exec("probe", &[]),
+            Ok("win in 3, best 1\n".to_string())
+        );
+        repl.exec("play", &["1"]).unwrap();
+        assert_eq!(repl.exec("book", &[]), Ok("out of book\n".to_string()));
+    }
+
+    #[test]
+    fn play_reads_turns_with_parser() {
+        let mut repl = Repl::new(Nim::new(5), MctsConfig::default()).parser(Nim::parse_turn);
+        repl.exec("play", &["-2"]).unwrap();
+        assert_eq!(repl.state().stones, 3);
+        assert_eq!(
+            repl.exec("play", &["x"]),
+            Err("invalid turn: x is not a number".to_string())
+        );
+        // Indices still work once the parser gives up
+        repl.exec("play", &["0"]).unwrap();
+        assert_eq!(repl.state().stones, 2);
+    }
+}
@@ -0,0 +1,260 @@
+use std::fmt::Write as _;
+use std::io::{self, BufRead, Write};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::ai::mcts::{self, Budget, MctsConfig};
+use crate::clock::{Clock, Remaining, TimeControl};
+use crate::Game;
+
+const WIDTH: usize = 20;
+
+const HELP: &str = "[p]ause [s]tep [+] faster [-] slower [q]uit";
+
+/// A spectator session where two engines play each other.
+#[derive(Debug)]
+pub struct Watch<G: Game> {
+    state: G,
+    first: G::Player,
+    engines: [MctsConfig; 2],
+    clock: Option<Clock>,
+    delay: Duration,
+    paused: bool,
+    eval: Option<f64>,
+    pv: Vec<String>,
+    ply: usize,
+}
+
+impl<G: Game> Watch<G> {
+    /// Create a new Watch where `first` plays the player to move in `game`.
+    pub fn new(game: G, first: MctsConfig, second: MctsConfig) -> Watch<G> {
+        Watch {
+            first: game.player(),
+            state: game,
+            engines: [first, second],
+            clock: None,
+            delay: Duration::from_millis(500),
+            paused: false,
+            eval: None,
+            pv: Vec::new(),
+            ply: 0,
+        }
+    }
+
+    /// Play with a clock for each engine.
+    pub fn clock(mut self, control: TimeControl) -> Watch<G> {
+        self.clock = Some(Clock::new(control));
+        self
+    }
+
+    /// Set the pause between turns.
+    pub fn delay(mut self, delay: Duration) -> Watch<G> {
+        self.delay = delay;
+        self
+    }
+
+    /// Get the current position.
+    pub fn state(&self) -> &G {
+        &self.state
+    }
+
+    /// Check if play is paused.
+    pub fn paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Run the session until the game ends or the user quits.
+    ///
+    /// Controls are read line by line from `input` on a separate thread, so
+    /// play continues while waiting for them.
+    pub fn run<R>(&mut self, input: R, mut output: impl Write) -> io::Result<()>
+    where
+        R: BufRead + Send + 'static,
+    {
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            for line in input.lines().map_while(Result::ok) {
+                if tx.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        loop {
+            write!(output, "\x1b[2J\x1b[H{}", self.frame())?;
+            output.flush()?;
+            if self.state.over() {
+                return Ok(());
+            }
+
+            // Wait for the next turn, handling any controls
+            let cmd = if self.paused {
+                rx.recv().map_err(|_| RecvTimeoutError::Disconnected)
+            } else {
+                rx.recv_timeout(self.delay)
+            };
+            let step = match cmd {
+                Ok(cmd) => match self.control(cmd.trim()) {
+                    Some(step) => step,
+                    None => return Ok(()),
+                },
+                Err(RecvTimeoutError::Timeout) => true,
+                // Keep playing once the input ends, unless stuck paused
+                Err(RecvTimeoutError::Disconnected) if self.paused => return Ok(()),
+                Err(RecvTimeoutError::Disconnected) => {
+                    thread::sleep(self.delay);
+                    true
+                }
+            };
+            if step {
+                self.step();
+            }
+        }
+    }
+
+    /// Apply a control, returning whether to play a turn now.
+    ///
+    /// Returns `None` if the user quit.
+    pub fn control(&mut self, cmd: &str) -> Option<bool> {
+        match cmd {
+            "p" | "pause" => self.paused = !self.paused,
+            "s" | "step" => return Some(true),
+            "+" | "faster" => self.delay /= 2,
+            "-" | "slower" => self.delay = (self.delay * 2).max(Duration::from_millis(1)),
+            "q" | "quit" => return None,
+            _ => (),
+        }
+        Some(false)
+    }
+
+    /// Let the engine to move search and play a turn.
+    ///
+    /// Returns false if the game is already over.
+    pub fn step(&mut self) -> bool {
+        if self.state.over() {
+            return false;
+        }
+        let seat = self.seat();
+        let mut config = self.engines[seat].clone();
+        if let (Some(clock), Budget::Time(limit)) = (&self.clock, config.budget) {
+            // Spend a fraction of the remaining time
+            config.budget = Budget::Time(limit.min(clock.budget(seat)));
+        }
+
+        let start = Instant::now();
+        let (mut lines, _) = mcts::search(&self.state, &config);
+        if let Some(clock) = &mut self.clock {
+            clock.punch(seat, start.elapsed());
+        }
+
+        // Keep the evaluation from the first engine's view
+        let best = lines.swap_remove(0);
+        let eval = best.wins / best.sims.max(1) as f64;
+        self.eval = Some(if seat == 0 { eval } else { 1. - eval });
+        self.pv = best.pv.iter().map(ToString::to_string).collect();
+        self.ply += 1;
+        self.state.play(best.pv[0].clone())
+    }
+
+    /// Format the board, clocks, evaluation, and principal variation.
+    pub fn frame(&self) -> String {
+        let mut out = format!("{}\n", self.state);
+        if let Some(clock) = &self.clock {
+            let player = |seat| if seat == self.seat() { "*" } else { " " };
+            writeln!(
+                out,
+                "{}{} | {}{}",
+                player(0),
+                Remaining(clock.remaining(0)),
+                player(1),
+                Remaining(clock.remaining(1)),
+            )
+            .unwrap();
+        }
+        if let Some(eval) = self.eval {
+            let filled = (eval * WIDTH as f64).round() as usize;
+            writeln!(
+                out,
+                "[{}{}] {:5.1}%",
+                "#".repeat(filled),
+                ".".repeat(WIDTH - filled),
+                100. * eval
+            )
+            .unwrap();
+            writeln!(out, "pv: {}", self.pv.join(" ")).unwrap();
+        }
+        if self.state.over() {
+            match self.state.winner() {
+                Some(player) => writeln!(out, "Winner: {player}").unwrap(),
+                None => writeln!(out, "It's a tie!").unwrap(),
+            }
+        } else {
+            let status = if self.paused { "paused" } else { "playing" };
+            writeln!(
+                out,
+                "ply {}, {status}, {}ms per turn",
+                self.ply,
+                self.delay.as_millis()
+            )
+            .unwrap();
+            writeln!(out, "{HELP}").unwrap();
+        }
+        out
+    }
+
+    /// Get the seat of the engine to move.
+    fn seat(&self) -> usize {
+        if self.state.player() == self.first {
+            0
+        } else {
+            1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::Nim;
+
+    fn config() -> MctsConfig {
+        MctsConfig {
+            budget: Budget::Iterations(200),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn controls_and_frame() {
+        let mut watch = Watch::new(Nim::new(5), config(), config());
+        assert_eq!(watch.control("p"), Some(false));
+        assert!(watch.paused());
+        assert_eq!(watch.control("s"), Some(true));
+        assert_eq!(watch.control("q"), None);
+
+        assert!(watch.step());
+        assert_eq!(watch.state().stones, 4);
+        let frame = watch.frame();
+        assert!(frame.contains("pv: 1"));
+        assert!(frame.contains("ply 1, paused"));
+
+        // The first engine stays winning after its opponent moves
+        assert!(watch.eval.unwrap() > 0.5);
+        assert!(watch.step());
+        assert!(watch.eval.unwrap() > 0.5);
+    }
+
+    #[test]
+    fn run_plays_to_completion() {
+        let mut watch = Watch::new(Nim::new(6), config(), config())
+            .clock(TimeControl::new(Duration::from_secs(60), Duration::ZERO))
+            .delay(Duration::from_millis(1));
+        let mut output = Vec::new();
+        watch.run(&b"+\n"[..], &mut output).unwrap();
+
+        assert!(watch.state().over());
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("Winner: "));
+    }
+}
@@ -0,0 +1,209 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::{self, Display};
+use core::time::Duration;
+
+/// Time control settings shared by both players.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TimeControl {
+    /// Initial time on each clock.
+    pub base: Duration,
+    /// Time added after each turn.
+    pub increment: Duration,
+    /// Number of byo-yomi periods, used once the base time runs out.
+    pub periods: u32,
+    /// Length of each byo-yomi period.
+    pub period: Duration,
+    /// Remaining times at which to warn about low time.
+    pub warnings: Vec<Duration>,
+}
+
+impl TimeControl {
+    /// Create a new TimeControl.
+    pub fn new(base: Duration, increment: Duration) -> TimeControl {
+        TimeControl {
+            base,
+            increment,
+            periods: 0,
+            period: Duration::ZERO,
+            warnings: vec![base / 10],
+        }
+    }
+
+    /// Add byo-yomi periods after the base time.
+    ///
+    /// Once a player's base time runs out, each turn must be played within a
+    /// period. A turn which overruns uses up a period for each one it runs
+    /// past, and the clock runs out with the last period.
+    pub fn byoyomi(mut self, periods: u32, period: Duration) -> TimeControl {
+        self.periods = periods;
+        self.period = period;
+        self
+    }
+
+    /// Set the low time warning thresholds.
+    pub fn warnings(mut self, warnings: Vec<Duration>) -> TimeControl {
+        self.warnings = warnings;
+        self
+    }
+}
+
+/// A chess clock for two seats.
+#[derive(Clone, Debug)]
+pub struct Clock {
+    control: TimeControl,
+    remaining: [Duration; 2],
+    periods: [u32; 2],
+    flagged: [bool; 2],
+    warned: [usize; 2],
+}
+
+impl Clock {
+    /// Create a new Clock.
+    pub fn new(control: TimeControl) -> Clock {
+        Clock {
+            remaining: [control.base; 2],
+            periods: [control.periods; 2],
+            flagged: [false; 2],
+            warned: [0; 2],
+            control,
+        }
+    }
+
+    /// Get the time control.
+    pub fn control(&self) -> &TimeControl {
+        &self.control
+    }
+
+    /// Get the remaining base time for a seat.
+    pub fn remaining(&self, seat: usize) -> Duration {
+        self.remaining[seat]
+    }
+
+    /// Get the byo-yomi periods left for a seat.
+    pub fn periods(&self, seat: usize) -> u32 {
+        self.periods[seat]
+    }
+
+    /// Check if a seat's clock has run out.
+    pub fn flagged(&self, seat: usize) -> bool {
+        self.flagged[seat]
+    }
+
+    /// Get the time a seat can spend on its turn before its clock runs out.
+    pub fn available(&self, seat: usize) -> Duration {
+        self.remaining[seat] + self.control.period * self.periods[seat]
+    }
+
+    /// Get the time a seat should spend on its turn: a twentieth of its base
+    /// time, or a whole period once in byo-yomi.
+    pub fn budget(&self, seat: usize) -> Duration {
+        let period = match self.periods[seat] {
+            0 => Duration::ZERO,
+            _ => self.control.period,
+        };
+        (self.remaining[seat] / 20).max(period)
+    }
+
+    /// Deduct thinking time from a seat and apply the increment, overrunning
+    /// into byo-yomi once its base time runs out.
+    ///
+    /// Returns the low time threshold crossed by this turn, if any.
+    pub fn punch(&mut self, seat: usize, elapsed: Duration) -> Option<Duration> {
+        let overrun = elapsed.saturating_sub(self.remaining[seat]);
+        if !overrun.is_zero() {
+            self.flagged[seat] |= overrun > self.control.period * self.periods[seat];
+            // Turns within a period are free, and each period overrun is used up
+            let period = self.control.period.as_nanos().max(1);
+            let used = u32::try_from((overrun.as_nanos() - 1) / period).unwrap_or(u32::MAX);
+            self.periods[seat] = self.periods[seat].saturating_sub(used);
+        }
+        let remaining = self.remaining[seat].saturating_sub(elapsed);
+        self.remaining[seat] = remaining + self.control.increment;
+
+        // Find the smallest threshold not yet warned about which was crossed
+        let crossed = self
+            .control
+            .warnings
+            .iter()
+            .filter(|&&threshold| remaining <= threshold)
+            .count();
+        if crossed > self.warned[seat] {
+            self.warned[seat] = crossed;
+            let mut warnings = self.control.warnings.clone();
+            warnings.sort_unstable();
+            warnings
+                .into_iter()
+                .find(|&threshold| remaining <= threshold)
+        } else {
+            None
+        }
+    }
+}
+
+/// Displays remaining time formatted as `m:ss.s`.
+#[derive(Clone, Copy, Debug)]
+pub struct Remaining(pub Duration);
+
+impl Display for Remaining {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let tenths = self.0.as_millis() / 100;
+        write!(
+            f,
+            "{}:{:02}.{}",
+            tenths / 600,
+            tenths / 10 % 60,
+            tenths % 10
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn punch_warns_once_per_threshold() {
+        let control = TimeControl::new(Duration::from_secs(60), Duration::ZERO)
+            .warnings(vec![Duration::from_secs(30), Duration::from_secs(10)]);
+        let mut clock = Clock::new(control);
+        assert_eq!(clock.punch(0, Duration::from_secs(20)), None);
+        assert!(!clock.flagged(0));
+        assert_eq!(
+            clock.punch(0, Duration::from_secs(15)),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(clock.punch(0, Duration::from_secs(1)), None);
+        assert_eq!(
+            clock.punch(0, Duration::from_secs(20)),
+            Some(Duration::from_secs(10))
+        );
+        assert_eq!(clock.remaining(1), Duration::from_secs(60));
+    }
+
+    #[test]
+    fn byoyomi_uses_overrun_periods() {
+        let control = TimeControl::new(Duration::from_secs(10), Duration::ZERO)
+            .byoyomi(3, Duration::from_secs(5));
+        let mut clock = Clock::new(control);
+        assert_eq!(clock.available(0), Duration::from_secs(25));
+        assert_eq!(clock.budget(0), Duration::from_secs(5));
+        clock.punch(0, Duration::from_secs(14));
+        assert_eq!((clock.remaining(0), clock.periods(0)), (Duration::ZERO, 3));
+        clock.punch(0, Duration::from_secs(5));
+        assert_eq!(clock.periods(0), 3);
+        // Running eleven seconds over uses up two periods
+        clock.punch(0, Duration::from_secs(11));
+        assert_eq!(clock.available(0), Duration::from_secs(5));
+        assert!(!clock.flagged(0));
+        clock.punch(0, Duration::from_secs(6));
+        assert!(clock.flagged(0));
+        assert_eq!(clock.periods(1), 3);
+    }
+
+    #[test]
+    fn remaining_display() {
+        let time = Remaining(Duration::from_millis(125_340));
+        assert_eq!(time.to_string(), "2:05.3");
+    }
+}
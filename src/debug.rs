@@ -0,0 +1,376 @@
+//! Reproducing engine searches from recorded games.
+//!
+//! When a move is recorded with the [`SearchMeta`] of the search which chose
+//! it, [`reproduce`] re-runs that exact search and dumps its tree, so the
+//! question "why did it play that?" can be answered from the record alone.
+
+use std::error;
+use std::fmt::{self, Display};
+use std::str::FromStr;
+use std::time::Duration;
+
+use log::warn;
+
+use crate::ai::mcts::{self, Budget, Hooks, MctsConfig, RewardShaper, Selection};
+use crate::Game;
+
+/// Engine metadata recorded alongside a move.
+///
+/// Formats as space-separated `key=value` pairs, such as
+/// `agent=mcts seed=7 iterations=1000 explore=1.414 threshold=3`, followed by
+/// `shaper=<name>` for searches using a [`RewardShaper`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchMeta {
+    /// Name of the agent which searched.
+    pub agent: String,
+    /// Configuration of the search.
+    pub config: MctsConfig,
+    /// Name of the reward shaper used, if any.
+    pub shaper: Option<String>,
+}
+
+impl SearchMeta {
+    /// Create metadata for an MCTS search.
+    pub fn mcts(config: MctsConfig) -> SearchMeta {
+        SearchMeta {
+            agent: "mcts".to_string(),
+            config,
+            shaper: None,
+        }
+    }
+
+    /// Create metadata for an MCTS search using a reward shaper.
+    pub fn shaped<G: Game>(config: MctsConfig, shaper: &dyn RewardShaper<G>) -> SearchMeta {
+        SearchMeta {
+            shaper: Some(shaper.name()),
+            ..SearchMeta::mcts(config)
+        }
+    }
+}
+
+impl Display for SearchMeta {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "agent={}", self.agent)?;
+        if let Some(seed) = self.config.seed {
+            write!(f, " seed={seed}")?;
+        }
+        match self.config.budget {
+            Budget::Time(time) => write!(f, " time={}", time.as_millis())?,
+            Budget::Iterations(n) => write!(f, " iterations={n}")?,
+        }
+        write!(
+            f,
+            " explore={} threshold={}",
+            self.config.explore, self.config.threshold
+        )?;
+        if let Some(max) = self.config.max_nodes {
+            write!(f, " max_nodes={max}")?;
+        }
+        if self.config.rollouts != 1 {
+            write!(f, " rollouts={}", self.config.rollouts)?;
+        }
+        if self.config.batch != 1 {
+            write!(f, " batch={}", self.config.batch)?;
+        }
+        if let Some(rave) = self.config.rave {
+            write!(f, " rave={rave}")?;
+        }
+        match self.config.selection {
+            Selection::Ucb1 => (),
+            Selection::Ucb1Tuned => write!(f, " selection=ucb1-tuned")?,
+            Selection::Puct => write!(f, " selection=puct")?,
+            Selection::Thompson => write!(f, " selection=thompson")?,
+        }
+        if let Some(fpu) = self.config.fpu {
+            write!(f, " fpu={fpu}")?;
+        }
+        if self.config.solver {
+            write!(f, " solver=true")?;
+        }
+        if let Some(cutoff) = self.config.cutoff {
+            write!(f, " cutoff={cutoff}")?;
+        }
+        if let Some(noise) = self.config.noise {
+            write!(f, " noise={noise}")?;
+        }
+        if let Some(mast) = self.config.mast {
+            write!(f, " mast={mast}")?;
+        }
+        if let Some(bias) = self.config.bias {
+            write!(f, " bias={bias}")?;
+        }
+        if let Some(minimax) = self.config.minimax {
+            write!(f, " minimax={minimax}")?;
+        }
+        if self.config.lazy {
+            write!(f, " lazy=true")?;
+        }
+        if let Some(widening) = self.config.widening {
+            write!(f, " widening={widening}")?;
+        }
+        if let Some(shaper) = &self.shaper {
+            write!(f, " shaper={shaper}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for SearchMeta {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut meta = SearchMeta::mcts(MctsConfig::default());
+        for pair in s.split_whitespace() {
+            let invalid = || Error::Meta(pair.to_string());
+            let (key, value) = pair.split_once('=').ok_or_else(invalid)?;
+            let config = &mut meta.config;
+            match key {
+                "agent" => meta.agent = value.to_string(),
+                "seed" => config.seed = Some(value.parse().map_err(|_| invalid())?),
+                "time" => {
+                    let ms = value.parse().map_err(|_| invalid())?;
+                    config.budget = Budget::Time(Duration::from_millis(ms));
+                }
+                "iterations" => {
+                    let n = value.parse().map_err(|_| invalid())?;
+                    config.budget = Budget::Iterations(n);
+                }
+                "explore" => config.explore = value.parse().map_err(|_| invalid())?,
+                "threshold" => config.threshold = value.parse().map_err(|_| invalid())?,
+                "max_nodes" => config.max_nodes = Some(value.parse().map_err(|_| invalid())?),
+                "rollouts" => config.rollouts = value.parse().map_err(|_| invalid())?,
+                "batch" => config.batch = value.parse().map_err(|_| invalid())?,
+                "rave" => config.rave = Some(value.parse().map_err(|_| invalid())?),
+                "selection" => {
+                    config.selection = match value {
+                        "ucb1" => Selection::Ucb1,
+                        "ucb1-tuned" => Selection::Ucb1Tuned,
+                        "puct" => Selection::Puct,
+                        "thompson" => Selection::Thompson,
+                        _ => return Err(invalid()),
+                    }
+                }
+                "fpu" => config.fpu = Some(value.parse().map_err(|_| invalid())?),
+                "solver" => config.solver = value.parse().map_err(|_| invalid())?,
+                "cutoff" => config.cutoff = Some(value.parse().map_err(|_| invalid())?),
+                "noise" => config.noise = Some(value.parse().map_err(|_| invalid())?),
+                "mast" => config.mast = Some(value.parse().map_err(|_| invalid())?),
+                "bias" => config.bias = Some(value.parse().map_err(|_| invalid())?),
+                "minimax" => config.minimax = Some(value.parse().map_err(|_| invalid())?),
+                "lazy" => config.lazy = value.parse().map_err(|_| invalid())?,
+                "widening" => config.widening = Some(value.parse().map_err(|_| invalid())?),
+                "shaper" => meta.shaper = Some(value.to_string()),
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(meta)
+    }
+}
+
+/// An error reproducing a search.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The metadata could not be parsed.
+    Meta(String),
+    /// The agent cannot be reproduced.
+    Agent(String),
+    /// The reward shaper does not match the metadata.
+    Shaper(Option<String>),
+    /// The search needs a hook which was not supplied.
+    Hook(&'static str),
+    /// The requested ply is past the end of the game.
+    Ply(usize),
+    /// A recorded turn could not be played.
+    Illegal(usize),
+    /// The game was already over at the requested ply.
+    Over,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Meta(pair) => write!(f, "invalid search metadata: {pair}"),
+            Error::Agent(agent) => write!(f, "cannot reproduce agent: {agent}"),
+            Error::Shaper(Some(shaper)) => write!(f, "search requires reward shaper: {shaper}"),
+            Error::Shaper(None) => write!(f, "search used no reward shaper"),
+            Error::Hook(hook) => write!(f, "search requires hook: {hook}"),
+            Error::Ply(ply) => write!(f, "ply {ply} is past the end of the game"),
+            Error::Illegal(ply) => write!(f, "recorded turn at ply {ply} is illegal"),
+            Error::Over => write!(f, "game is over"),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+/// The result of a reproduced search.
+#[derive(Clone, Debug)]
+pub struct Trace<G: Game> {
+    /// Position which was searched.
+    pub position: G,
+    /// Principal variation of the chosen turn.
+    pub pv: Vec<G::Turn>,
+    /// Simulations and win rate of each root turn, most simulated first.
+    pub turns: Vec<(G::Turn, u32, f64)>,
+    /// Visited tree, formatted with one node per line.
+    pub tree: String,
+}
+
+impl<G: Game> Display for Trace<G> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.position)?;
+        let pv: Vec<_> = self.pv.iter().map(ToString::to_string).collect();
+        writeln!(f, "pv: {}", pv.join(" "))?;
+        write!(f, "{}", self.tree)
+    }
+}
+
+/// Re-run the search made before the turn at `ply`.
+///
+/// The position is reached by replaying `turns` from `start`, and the tree is
+/// dumped up to `depth` plies. Searches are only reproducible when the
+/// metadata has a seed and an iteration budget, and when given the same
+/// `hooks` as the original search. Shaped searches must be given the shaper
+/// named by the metadata, and options which need a hook, such as a
+/// [`bias`](MctsConfig::bias) needing an evaluation, fail without it.
+pub fn reproduce<G: Game>(
+    start: &G,
+    turns: &[G::Turn],
+    ply: usize,
+    meta: &SearchMeta,
+    hooks: &Hooks<G>,
+    depth: usize,
+) -> Result<Trace<G>, Error> {
+    if meta.agent != "mcts" {
+        return Err(Error::Agent(meta.agent.clone()));
+    }
+    if meta.shaper != hooks.shaper_name() {
+        return Err(Error::Shaper(meta.shaper.clone()));
+    }
+    if let Some(hook) = hooks.missing(&meta.config) {
+        return Err(Error::Hook(hook));
+    }
+    if ply > turns.len() {
+        return Err(Error::Ply(ply));
+    }
+    if meta.config.seed.is_none() || matches!(meta.config.budget, Budget::Time(_)) {
+        warn!("search without a seed and iteration budget may not reproduce");
+    }
+
+    // Replay the game up to the position
+    let mut position = start.clone();
+    for (idx, turn) in turns[..ply].iter().enumerate() {
+        if !position.play(turn.clone()) {
+            return Err(Error::Illegal(idx));
+        }
+    }
+    if position.over() {
+        return Err(Error::Over);
+    }
+
+    // Re-run the search
+    let (lines, tree) = mcts::trace(&position, &meta.config, hooks, depth);
+    Ok(Trace {
+        position,
+        pv: lines[0].pv.clone(),
+        turns: lines
+            .iter()
+            .map(|line| {
+                let rate = line.wins / line.sims.max(1) as f64;
+                (line.pv[0].clone(), line.sims, rate)
+            })
+            .collect(),
+        tree,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::ai::mcts::{Discount, Noise, Widening};
+    use crate::tests::Nim;
+
+    #[test]
+    fn meta_round_trip() {
+        let meta = SearchMeta::mcts(MctsConfig {
+            budget: Budget::Iterations(1000),
+            seed: Some(7),
+            ..Default::default()
+        });
+        let text = meta.to_string();
+        assert_eq!(
+            text,
+            "agent=mcts seed=7 iterations=1000 explore=1.414 threshold=3"
+        );
+        assert_eq!(text.parse::<SearchMeta>(), Ok(meta));
+        assert!("seed=x".parse::<SearchMeta>().is_err());
+
+        let meta: SearchMeta = "agent=mcts noise=0.3,0.25".parse().unwrap();
+        assert_eq!(meta.config.noise, Some(Noise::default()));
+        assert!(meta.to_string().ends_with(" noise=0.3,0.25"));
+
+        let meta = SearchMeta::mcts(MctsConfig {
+            widening: Some(Widening::default()),
+            ..Default::default()
+        });
+        assert!(meta.to_string().ends_with(" widening=1,0.5"));
+        assert_eq!(meta.to_string().parse::<SearchMeta>(), Ok(meta));
+    }
+
+    #[test]
+    fn reproduce_is_deterministic() {
+        let meta: SearchMeta = "agent=mcts seed=7 iterations=500".parse().unwrap();
+        let turns = [2, 1];
+        let hooks = Hooks::new();
+        let a = reproduce(&Nim::new(12), &turns, 1, &meta, &hooks, 2).unwrap();
+        let b = reproduce(&Nim::new(12), &turns, 1, &meta, &hooks, 2).unwrap();
+        assert_eq!(a.position.stones, 10);
+        assert_eq!(a.tree, b.tree);
+        assert_eq!(a.pv, b.pv);
+        assert_eq!(
+            reproduce(&Nim::new(12), &turns, 3, &meta, &hooks, 2).err(),
+            Some(Error::Ply(3))
+        );
+    }
+
+    #[test]
+    fn reproduce_requires_hooks() {
+        let meta: SearchMeta = "agent=mcts seed=7 iterations=200 bias=0.5".parse().unwrap();
+        let game = Nim::new(6);
+        assert_eq!(
+            reproduce(&game, &[], 0, &meta, &Hooks::new(), 1).err(),
+            Some(Error::Hook("evaluate"))
+        );
+        assert!(reproduce(&game, &[], 0, &meta, &Hooks::new().evaluate(), 1).is_ok());
+        let meta: SearchMeta = "agent=mcts selection=puct".parse().unwrap();
+        assert_eq!(
+            reproduce(&game, &[], 0, &meta, &Hooks::new(), 1).err(),
+            Some(Error::Hook("priors"))
+        );
+    }
+
+    #[test]
+    fn reproduce_requires_shaper() {
+        let config = MctsConfig {
+            budget: Budget::Iterations(200),
+            seed: Some(7),
+            ..Default::default()
+        };
+        let shaper: Arc<dyn RewardShaper<Nim>> = Arc::new(Discount(0.9));
+        let meta = SearchMeta::shaped(config, &*shaper);
+        assert!(meta.to_string().ends_with(" shaper=discount:0.9"));
+        assert_eq!(meta.to_string().parse::<SearchMeta>(), Ok(meta.clone()));
+
+        let game = Nim::new(6);
+        assert_eq!(
+            reproduce(&game, &[], 0, &meta, &Hooks::new(), 1).err(),
+            Some(Error::Shaper(Some("discount:0.9".to_string())))
+        );
+        let hooks = Hooks::new().shaper(shaper);
+        let a = reproduce(&game, &[], 0, &meta, &hooks, 1).unwrap();
+        let b = reproduce(&game, &[], 0, &meta, &hooks, 1).unwrap();
+        assert_eq!(a.tree, b.tree);
+    }
+}
@@ -1,12 +1,94 @@
 //! Gamesweet
 //!
 //! `gamesweet` is a library defining a common interface for board games.
+//!
+//! # Features
+//!
+//! Only the [`Game`] trait, the game loop, and the MCTS engine are enabled by
+//! default. Everything else is opt-in:
+//!
+//! - `std`: the standard library, needed by all but `nmcs` (default).
+//! - `entropy`: seeding random number generators from the operating system
+//!   (default).
+//! - `mcts`: Monte Carlo tree search engine (default).
+//! - `ismcts`: information set MCTS for hidden-information games.
+//! - `duct`: decoupled UCT for simultaneous-move games.
+//! - `nmcs`: nested Monte Carlo search for single-player puzzles.
+//! - `qlearn`: tabular Q-learning through self-play.
+//! - `td`: training value functions with TD(λ) through self-play.
+//! - `rayon`: parallel leaf simulations in MCTS.
+//! - `analysis`: statistical and symmetry diagnostics.
+//! - `arena`: matches between agents with aggregate statistics.
+//! - `batch`: parallel batch evaluation of positions.
+//! - `bench`: throughput benchmarks of games and agents.
+//! - `book`: opening books consulted before searching.
+//! - `cli`: command line interface for registered games.
+//! - `plugin`: loading games from dynamic libraries.
+//! - `record`: game records which can be saved as JSON, PGN, or SGF.
+//! - `tablebase`: endgame tablebases by retrograde analysis.
+//! - `testing`: conformance checks for game implementations.
+//! - `train`: AlphaZero-style training through self-play.
+//! - `tune`: SPSA tuning of engine parameters.
+//! - `full`: all of the above.
+//!
+//! Without `std` the crate is `no_std` and only needs `alloc`. The traits,
+//! clocks, and the random, greedy, and nested Monte Carlo agents remain, so
+//! games can be played with [`Game::play_out`] on a device that brings its
+//! own timer and random number generator.
+//!
+//! Without `entropy`, agents are seeded or given a generator by the caller.
+//! This is needed on `wasm32-unknown-unknown`, which has no entropy source,
+//! where the engine builds with `--no-default-features --features mcts`.
 
-use std::fmt::{Debug, Display};
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 
-use log::error;
+extern crate alloc;
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::{self, Debug, Display};
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::time::Instant;
+#[cfg(feature = "record")]
+use std::time::SystemTime;
+
+use log::{error, warn};
+use rand::Rng;
+
+use crate::clock::{Clock, Remaining, TimeControl};
+#[cfg(feature = "record")]
+use crate::record::GameRecord;
+#[cfg(feature = "std")]
+use crate::session::{Event, Observer, Pause};
 
 pub mod ai;
+#[cfg(feature = "arena")]
+pub mod arena;
+#[cfg(feature = "batch")]
+pub mod batch;
+#[cfg(feature = "bench")]
+pub mod bench;
+#[cfg(feature = "book")]
+pub mod book;
+#[cfg(feature = "cli")]
+pub mod cli;
+pub mod clock;
+#[cfg(feature = "mcts")]
+pub mod debug;
+#[cfg(feature = "plugin")]
+pub mod plugin;
+#[cfg(feature = "record")]
+pub mod record;
+#[cfg(feature = "std")]
+pub mod session;
+#[cfg(feature = "tablebase")]
+pub mod tablebase;
+#[cfg(feature = "testing")]
+pub mod testing;
+#[cfg(feature = "train")]
+pub mod train;
+pub mod util;
 
 pub trait Game: Clone + Debug + Display {
     type Player: Clone + Debug + Display + PartialEq;
@@ -15,6 +97,13 @@ pub trait Game: Clone + Debug + Display {
     /// Get the current player.
     fn player(&self) -> Self::Player;
 
+    /// Get the number of players.
+    ///
+    /// Defaults to two; single-player puzzles should return 1.
+    fn players(&self) -> usize {
+        2
+    }
+
     /// Get all legal turns.
     fn turns(&self) -> Vec<Self::Turn>;
 
@@ -28,54 +117,1156 @@ pub trait Game: Clone + Debug + Display {
     fn winner(&self) -> Option<Self::Player>;
 
     /// Main loop for a game.
-    fn main(mut self, config: Config<Self>) {
+    #[cfg(feature = "std")]
+    fn main(self, config: Config<Self>) {
+        main_loop(self, config, |_, _| ());
+    }
+
+    /// Main loop for a game, returning a record of it.
+    ///
+    /// Players are named as displayed, and each turn is recorded with the
+    /// time taken to choose it.
+    #[cfg(feature = "record")]
+    fn main_recorded(self, config: Config<Self>) -> GameRecord<Self> {
+        let mut record = GameRecord::new(self.clone());
+        record.players = vec![config.player1.0.to_string(), config.player2.0.to_string()];
+        record.started = Some(SystemTime::now());
+        let ending = main_loop(self, config, |turn, elapsed| {
+            record.push(turn, Some(elapsed))
+        });
+        match ending {
+            Some(Ending::Resigned(winner)) => record.resign(winner),
+            Some(Ending::Agreed) => record.agree(),
+            Some(Ending::Forfeit(winner)) => record.forfeit(winner),
+            None => record.finish(),
+        }
+        record
+    }
+
+    /// Play a game to the end without printing, returning the winner.
+    ///
+    /// Turns are timed with `now`, which reads any monotonic clock, such as a
+    /// hardware timer on a device without the standard library. If a player
+    /// resigns or runs out of time, their opponent wins, and a draw agreed to
+    /// has no winner.
+    fn play_out<F>(mut self, config: &mut Config<Self>, mut now: F) -> Option<Self::Player>
+    where
+        F: FnMut() -> Duration,
+    {
+        let mut offered = false;
         while !self.over() {
-            println!("{}", self);
+            let seat = config.seat(&self);
+            let player = self.player();
+            config.started(&self);
+            let start = now();
+            let (turn, offers) = match config.choose(&mut self, offered) {
+                Ok(choice) => choice,
+                Err(ending) => return ending.winner(),
+            };
+            let elapsed = now().saturating_sub(start);
+            config.played(&self, player.clone(), &turn, elapsed);
+            config.punch(&self, seat, elapsed);
+            if offers {
+                config.offered(&self, player);
+            }
+            offered = offers;
+            if let Some(ending) = config.timed_out(&self, seat) {
+                return ending.winner();
+            }
+        }
+        config.finished(&self, self.winner());
+        self.winner()
+    }
+}
+
+/// A game where some of the state is hidden from the current player.
+pub trait Hidden: Game {
+    /// Sample a full state consistent with the current player's information.
+    fn determinize<R: Rng + ?Sized>(&self, rng: &mut R) -> Self;
+}
+
+/// A game whose turns can be taken back.
+///
+/// Lets MCTS with [`reversible`](crate::ai::mcts::Hooks::reversible) hooks
+/// walk a single state through the tree instead of cloning it at every node.
+pub trait Reversible: Game {
+    /// Take back a turn, which must be the last one played.
+    fn unplay(&mut self, turn: &Self::Turn);
+}
+
+/// A game with a heuristic evaluation of its positions.
+pub trait Evaluate: Game {
+    /// Estimate the value of the position for a player, from 0 to 1.
+    fn evaluate(&self, player: &Self::Player) -> f64;
+}
+
+/// A game with scored outcomes.
+///
+/// In two-player, zero-sum games the score is a margin over the opponent; in
+/// single-player puzzles it is simply the score achieved.
+pub trait Score: Game {
+    /// Get the final score of a finished game for a player.
+    fn score(&self, player: &Self::Player) -> f64;
+}
+
+/// A game played on a grid, where turns map to cells.
+///
+/// Lets tools such as [`mcts::heatmap`](crate::ai::mcts::heatmap) draw
+/// statistics about turns onto the board.
+pub trait TurnCoords: Game {
+    /// Get the size of the grid, as rows and columns.
+    fn dimensions(&self) -> (usize, usize);
+
+    /// Get the cell of a turn, as a row and column, if it has one.
+    fn coords(&self, turn: &Self::Turn) -> Option<(usize, usize)>;
+}
+
+/// A game whose turns can be read from text, such as `e4` or `3,2`.
+///
+/// Turns are read in the context of the position they are played in, so
+/// implementations can resolve short forms and explain why a turn cannot be
+/// played. The default implementation matches the legal turns by name.
+/// Used by the [human agent](crate::ai::human), the command line, and the
+/// record parsers.
+pub trait ParseTurn: Game {
+    /// Read a turn to be played from this position.
+    fn parse_turn(&self, s: &str) -> Result<Self::Turn, TurnError> {
+        by_name(self, s)
+    }
+}
+
+/// An error reading a turn.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TurnError {
+    /// The text does not describe a turn, with the reason.
+    Invalid(String),
+    /// The turn cannot be played from this position, with the reason.
+    Illegal(String),
+    /// The text matches several legal turns, by name.
+    Ambiguous(Vec<String>),
+}
 
-            while !self.play(config.turn(&self)) {
-                error!("could not play turn");
+impl Display for TurnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TurnError::Invalid(msg) => write!(f, "invalid turn: {msg}"),
+            TurnError::Illegal(msg) => write!(f, "illegal turn: {msg}"),
+            TurnError::Ambiguous(turns) => {
+                write!(f, "ambiguous turn, could be any of: {}", turns.join(", "))
             }
         }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for TurnError {}
+
+/// Find a legal turn by its name, ignoring case if there is no exact match.
+pub(crate) fn by_name<G: Game>(game: &G, s: &str) -> Result<G::Turn, TurnError> {
+    let turns = game.turns();
+    if let Some(turn) = turns.iter().find(|turn| turn.to_string() == s) {
+        return Ok(turn.clone());
+    }
+    let mut matches: Vec<_> = turns
+        .into_iter()
+        .filter(|turn| turn.to_string().eq_ignore_ascii_case(s))
+        .collect();
+    match matches.len() {
+        0 => Err(TurnError::Illegal(s.to_string())),
+        1 => Ok(matches.swap_remove(0)),
+        _ => Err(TurnError::Ambiguous(
+            matches.iter().map(ToString::to_string).collect(),
+        )),
+    }
+}
+
+/// A game with a short written notation for its turns.
+///
+/// Notation may depend on the position, as in chess, so turns are always
+/// written from the position they are played in, and read back with
+/// [`ParseTurn`]. Used by
+/// [`GameRecord::to_pgn`](crate::record::GameRecord::to_pgn) to write records
+/// for people to read.
+pub trait Notation: ParseTurn {
+    /// Write a turn to be played from this position.
+    fn write_turn(&self, turn: &Self::Turn) -> String;
+}
+
+/// A game whose positions can be written as text, like chess's FEN.
+///
+/// Lets tools load arbitrary positions for analysis, puzzles, and tests
+/// rather than always starting from the initial position.
+pub trait SetupGame: Game {
+    /// Read a position from its setup.
+    fn from_setup(s: &str) -> Result<Self, SetupError>;
 
-        println!("{}", self);
-        match self.winner() {
-            Some(player) => println!("Winner: {}", player),
-            None => println!("It's a tie!"),
+    /// Write the position as a setup, which
+    /// [`from_setup`](SetupGame::from_setup) reads back.
+    fn to_setup(&self) -> String;
+}
+
+/// An error reading a setup.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SetupError {
+    /// The text is not a setup, with the reason.
+    Malformed(String),
+    /// The setup is well-formed but describes an impossible position, with
+    /// the reason.
+    Invalid(String),
+}
+
+impl Display for SetupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SetupError::Malformed(msg) => write!(f, "malformed setup: {msg}"),
+            SetupError::Invalid(msg) => write!(f, "invalid setup: {msg}"),
         }
     }
 }
 
+#[cfg(feature = "std")]
+impl std::error::Error for SetupError {}
+
+/// A game where every player moves at once.
+pub trait SimultaneousGame: Clone + Debug + Display {
+    type Player: Clone + Debug + Display + PartialEq;
+    type Turn: Clone + Debug + Display;
+
+    /// Get all players, in the order their turns are played.
+    fn players(&self) -> Vec<Self::Player>;
+
+    /// Get all legal turns for a player, or none if they must pass.
+    fn turns(&self, player: &Self::Player) -> Vec<Self::Turn>;
+
+    /// Play one turn for each player who does not pass at once, in the order
+    /// of [`players`](SimultaneousGame::players).
+    fn play(&mut self, turns: Vec<Self::Turn>) -> bool;
+
+    /// Check if the game is over.
+    fn over(&self) -> bool;
+
+    /// Get the winner of the game.
+    fn winner(&self) -> Option<Self::Player>;
+}
+
+/// Play a game to the end, printing each position and reporting each turn
+/// played with the time taken to choose it.
+///
+/// Returns how the game ended, if it ended before it was over.
+#[cfg(feature = "std")]
+fn main_loop<G, F>(mut game: G, mut config: Config<G>, mut played: F) -> Option<Ending<G::Player>>
+where
+    G: Game,
+    F: FnMut(G::Turn, Duration),
+{
+    let mut offered = false;
+    while !game.over() {
+        println!("{}", game);
+        config.show_clock();
+
+        // Time the current player's turn
+        let seat = config.seat(&game);
+        let player = game.player();
+        config.started(&game);
+        let start = Instant::now();
+        let (turn, offers) = match config.choose(&mut game, offered) {
+            Ok(choice) => choice,
+            Err(ending) => {
+                match &ending {
+                    Ending::Resigned(winner) => {
+                        println!("{} resigns", game.player());
+                        println!("Winner: {}", winner);
+                    }
+                    _ => println!("Drawn by agreement"),
+                }
+                return Some(ending);
+            }
+        };
+        let elapsed = start.elapsed();
+        config.played(&game, player.clone(), &turn, elapsed);
+        config.punch(&game, seat, elapsed);
+        if offers {
+            println!("{} offers a draw", player);
+            config.offered(&game, player.clone());
+        }
+        offered = offers;
+        played(turn, elapsed);
+        if let Some(ending) = config.timed_out(&game, seat) {
+            println!("{}", game);
+            println!("{} ran out of time", player);
+            match &ending {
+                Ending::Forfeit(Some(winner)) => println!("Winner: {}", winner),
+                _ => println!("It's a tie!"),
+            }
+            return Some(ending);
+        }
+    }
+
+    println!("{}", game);
+    match game.winner() {
+        Some(player) => println!("Winner: {}", player),
+        None => println!("It's a tie!"),
+    }
+    config.finished(&game, game.winner());
+    None
+}
+
 pub type TurnFn<G> = fn(&G) -> <G as Game>::Turn;
 
+/// Chooses a turn for a player, or ends the game, in the context of the
+/// game being played.
+pub type DecideFn<G> = fn(&G, &Context) -> Decision<<G as Game>::Turn>;
+
+/// What a player knows when deciding their turn, besides the position.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Context {
+    /// Whether the opponent offered a draw with their last turn.
+    pub offered: bool,
+    /// Time the player can spend before their clock runs out, if enabled.
+    pub remaining: Option<Duration>,
+    /// Time the player should spend on this turn, if their clock is enabled.
+    pub budget: Option<Duration>,
+}
+
+/// What a player decided to do on their turn.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Decision<T> {
+    /// Play a turn.
+    Play(T),
+    /// Play a turn, offering a draw which the opponent can accept instead of
+    /// replying.
+    OfferDraw(T),
+    /// Accept the draw offered by the opponent with their last turn.
+    ///
+    /// Accepting when no draw was offered concedes the game, as with
+    /// [`Resign`](Decision::Resign), so an agent cannot stall by repeating it.
+    AcceptDraw,
+    /// Concede the game to the opponent.
+    Resign,
+}
+
+/// How a game ended before it was over.
+enum Ending<P> {
+    /// A player resigned, leaving the winner.
+    Resigned(P),
+    /// The players agreed to a draw.
+    Agreed,
+    /// A player ran out of time, leaving the winner if their opponent could
+    /// still win.
+    Forfeit(Option<P>),
+}
+
+impl<P> Ending<P> {
+    /// Get the winner, if any.
+    fn winner(self) -> Option<P> {
+        match self {
+            Ending::Resigned(winner) => Some(winner),
+            Ending::Agreed => None,
+            Ending::Forfeit(winner) => winner,
+        }
+    }
+}
+
+/// How a seat chooses its turns.
+enum Agent<G: Game> {
+    Turn(TurnFn<G>),
+    Decide(DecideFn<G>),
+}
+
+impl<G: Game> Clone for Agent<G> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<G: Game> Copy for Agent<G> {}
+
+impl<G: Game> Agent<G> {
+    /// Decide what to do from a position.
+    fn decide(&self, game: &G, context: &Context) -> Decision<G::Turn> {
+        match self {
+            Agent::Turn(turn) => Decision::Play(turn(game)),
+            Agent::Decide(decide) => decide(game, context),
+        }
+    }
+}
+
+/// Lets an agent decide within a time limit, if it can.
+#[cfg(feature = "std")]
+type WithinFn<G> = fn(Agent<G>, &G, &Context, Duration) -> Option<Decision<<G as Game>::Turn>>;
+
+/// A hard limit on the time agents can spend deciding a turn.
+#[cfg(feature = "std")]
+struct MoveLimit<G: Game> {
+    limit: Duration,
+    fallback: TurnFn<G>,
+    decide: WithinFn<G>,
+}
+
+/// Let an agent decide on a worker thread, giving up after `limit` or if it
+/// panics.
+#[cfg(feature = "std")]
+fn decide_within<G>(
+    agent: Agent<G>,
+    game: &G,
+    context: &Context,
+    limit: Duration,
+) -> Option<Decision<G::Turn>>
+where
+    G: Game + Send + 'static,
+    G::Turn: Send,
+{
+    let (tx, rx) = std::sync::mpsc::channel();
+    let (game, context) = (game.clone(), *context);
+    std::thread::spawn(move || {
+        // The receiver is gone if the agent was cut off
+        let _ = tx.send(agent.decide(&game, &context));
+    });
+    rx.recv_timeout(limit).ok()
+}
+
+/// Called with a position, such as when a turn starts or the game ends.
+pub type HookFn<G> = fn(&G);
+
+/// Called with a position and a turn played or tried from it.
+pub type TurnHookFn<G> = fn(&G, &<G as Game>::Turn);
+
+/// Checks whether a player could still win from a position.
+pub type WinFn<G> = fn(&G, &<G as Game>::Player) -> bool;
+
 pub struct Config<G: Game> {
-    player1: (G::Player, TurnFn<G>),
-    player2: (G::Player, TurnFn<G>),
+    player1: (G::Player, Agent<G>),
+    player2: (G::Player, Agent<G>),
+    clock: Option<Clock>,
+    on_turn_start: Option<HookFn<G>>,
+    on_turn_played: Option<TurnHookFn<G>>,
+    on_illegal_turn: Option<TurnHookFn<G>>,
+    on_game_over: Option<HookFn<G>>,
+    can_win: Option<WinFn<G>>,
+    #[cfg(feature = "std")]
+    observers: Vec<Box<dyn Observer<G>>>,
+    #[cfg(feature = "std")]
+    pause: Option<Pause<G>>,
+    #[cfg(feature = "std")]
+    move_limit: Option<MoveLimit<G>>,
 }
 
 impl<G: Game> Config<G> {
     /// Create a new Config.
     pub fn new(player1: (G::Player, TurnFn<G>), player2: (G::Player, TurnFn<G>)) -> Config<G> {
         assert!(player1.0 != player2.0);
-        Config { player1, player2 }
+        Config {
+            player1: (player1.0, Agent::Turn(player1.1)),
+            player2: (player2.0, Agent::Turn(player2.1)),
+            clock: None,
+            on_turn_start: None,
+            on_turn_played: None,
+            on_illegal_turn: None,
+            on_game_over: None,
+            can_win: None,
+            #[cfg(feature = "std")]
+            observers: Vec::new(),
+            #[cfg(feature = "std")]
+            pause: None,
+            #[cfg(feature = "std")]
+            move_limit: None,
+        }
     }
 
-    /// Get a turn for a player.
-    pub fn turn(&self, game: &G) -> G::Turn {
-        if self.player1.0 == game.player() {
-            self.player1.1(game)
+    /// Enable time controls.
+    ///
+    /// A player whose clock runs out forfeits the game once their turn is
+    /// played.
+    pub fn clock(mut self, control: TimeControl) -> Config<G> {
+        self.clock = Some(Clock::new(control));
+        self
+    }
+
+    /// Check whether a player could still win, so that running out of time
+    /// against a player who cannot is a draw, as with insufficient material
+    /// in chess.
+    pub fn can_win(mut self, can_win: WinFn<G>) -> Config<G> {
+        self.can_win = Some(can_win);
+        self
+    }
+
+    /// Call a hook with the position whenever a turn starts.
+    pub fn on_turn_start(mut self, hook: HookFn<G>) -> Config<G> {
+        self.on_turn_start = Some(hook);
+        self
+    }
+
+    /// Call a hook with the resulting position and the turn whenever one is
+    /// played.
+    pub fn on_turn_played(mut self, hook: TurnHookFn<G>) -> Config<G> {
+        self.on_turn_played = Some(hook);
+        self
+    }
+
+    /// Call a hook with the position and the turn whenever a player chooses
+    /// an illegal turn.
+    pub fn on_illegal_turn(mut self, hook: TurnHookFn<G>) -> Config<G> {
+        self.on_illegal_turn = Some(hook);
+        self
+    }
+
+    /// Call a hook with the final position once the game is over, or a
+    /// player resigns.
+    pub fn on_game_over(mut self, hook: HookFn<G>) -> Config<G> {
+        self.on_game_over = Some(hook);
+        self
+    }
+
+    /// Add an observer, which sees every event of the game along with the
+    /// position after it.
+    ///
+    /// Unlike hooks, any number of observers can be added, and each can hold
+    /// state of its own, such as a channel to a spectator.
+    #[cfg(feature = "std")]
+    pub fn observe(mut self, observer: impl Observer<G> + 'static) -> Config<G> {
+        self.observers.push(Box::new(observer));
+        self
+    }
+
+    /// Hold the game before each turn while paused by a
+    /// [`Stepper`](session::Stepper), such as to inspect an agent which goes
+    /// wrong deep into a game.
+    #[cfg(feature = "std")]
+    pub fn pause(mut self, pause: Pause<G>) -> Config<G> {
+        self.pause = Some(pause);
+        self
+    }
+
+    /// Cut off agents which take longer than `limit` to decide a turn,
+    /// playing the turn chosen by `fallback` instead, such as
+    /// [`ai::rand::run`]. Agents which panic are replaced by the fallback too.
+    ///
+    /// This holds without a clock, so a buggy agent cannot hang a match.
+    /// Agents decide on a worker thread, which is left to finish in the
+    /// background once cut off.
+    #[cfg(feature = "std")]
+    pub fn move_limit(mut self, limit: Duration, fallback: TurnFn<G>) -> Config<G>
+    where
+        G: Send + 'static,
+        G::Turn: Send,
+    {
+        self.move_limit = Some(MoveLimit {
+            limit,
+            fallback,
+            decide: decide_within,
+        });
+        self
+    }
+
+    /// Let a player decide their turns with `decide`, which can also resign
+    /// or agree to draws.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `player` is not seated.
+    pub fn decider(mut self, player: G::Player, decide: DecideFn<G>) -> Config<G> {
+        if self.player1.0 == player {
+            self.player1.1 = Agent::Decide(decide);
+        } else if self.player2.0 == player {
+            self.player2.1 = Agent::Decide(decide);
+        } else {
+            panic!("{player} is not seated");
+        }
+        self
+    }
+
+    /// Get a decision for the current player.
+    ///
+    /// Agents which exceed the [move limit](Config::move_limit), if any, are
+    /// replaced by its fallback.
+    pub fn decide(&self, game: &G, context: &Context) -> Decision<G::Turn> {
+        let agent = if self.player1.0 == game.player() {
+            self.player1.1
         } else if self.player2.0 == game.player() {
-            self.player2.1(game)
+            self.player2.1
         } else {
             panic!()
+        };
+        #[cfg(feature = "std")]
+        if let Some(limit) = &self.move_limit {
+            return (limit.decide)(agent, game, context, limit.limit).unwrap_or_else(|| {
+                warn!("{} exceeded the move limit", game.player());
+                Decision::Play((limit.fallback)(game))
+            });
+        }
+        agent.decide(game, context)
+    }
+
+    /// Get the context of the current player's decision, whose opponent may
+    /// have `offered` a draw.
+    fn context(&self, game: &G, offered: bool) -> Context {
+        let seat = self.seat(game);
+        Context {
+            offered,
+            remaining: self.clock.as_ref().map(|clock| clock.available(seat)),
+            budget: self.clock.as_ref().map(|clock| clock.budget(seat)),
+        }
+    }
+
+    /// Play the current player's decision, returning the turn and whether it
+    /// offers a draw, or how the game ended.
+    ///
+    /// Players are asked again after an illegal turn, but accepting a draw
+    /// which was not `offered` resigns.
+    fn choose(
+        &mut self,
+        game: &mut G,
+        offered: bool,
+    ) -> Result<(G::Turn, bool), Ending<G::Player>> {
+        loop {
+            let context = self.context(game, offered);
+            let (turn, offers) = match self.decide(game, &context) {
+                Decision::Play(turn) => (turn, false),
+                Decision::OfferDraw(turn) => (turn, true),
+                Decision::AcceptDraw if offered => {
+                    self.agreed(game);
+                    return Err(Ending::Agreed);
+                }
+                Decision::AcceptDraw => {
+                    error!("no draw was offered, so accepting one resigns");
+                    return Err(Ending::Resigned(self.resigned(game)));
+                }
+                Decision::Resign => return Err(Ending::Resigned(self.resigned(game))),
+            };
+            if game.play(turn.clone()) {
+                return Ok((turn, offers));
+            }
+            self.illegal(game, &turn);
+        }
+    }
+
+    /// Get the seat of the current player.
+    fn seat(&self, game: &G) -> usize {
+        if self.player1.0 == game.player() {
+            0
+        } else {
+            1
         }
     }
+
+    /// Print both clocks, if enabled.
+    #[cfg(feature = "std")]
+    fn show_clock(&self) {
+        if let Some(clock) = &self.clock {
+            println!(
+                "{}: {} | {}: {}",
+                self.player1.0,
+                Remaining(clock.remaining(0)),
+                self.player2.0,
+                Remaining(clock.remaining(1)),
+            );
+        }
+    }
+
+    /// Report that a turn started.
+    fn started(&mut self, game: &G) {
+        if let Some(hook) = self.on_turn_start {
+            hook(game);
+        }
+        #[cfg(feature = "std")]
+        self.broadcast(game, || Event::TurnStarted(game.player()));
+        #[cfg(feature = "std")]
+        if let Some(pause) = &mut self.pause {
+            pause.wait(game);
+        }
+    }
+
+    /// Report that a player's turn was played, leading to `game`.
+    fn played(&mut self, game: &G, player: G::Player, turn: &G::Turn, elapsed: Duration) {
+        if let Some(hook) = self.on_turn_played {
+            hook(game, turn);
+        }
+        #[cfg(feature = "std")]
+        self.broadcast(game, || Event::TurnPlayed {
+            player,
+            turn: turn.clone(),
+            elapsed,
+        });
+        #[cfg(not(feature = "std"))]
+        let _ = (player, elapsed);
+    }
+
+    /// Report that an illegal turn was chosen.
+    fn illegal(&mut self, game: &G, turn: &G::Turn) {
+        error!("could not play turn");
+        if let Some(hook) = self.on_illegal_turn {
+            hook(game, turn);
+        }
+        #[cfg(feature = "std")]
+        self.broadcast(game, || Event::IllegalTurn {
+            player: game.player(),
+            turn: turn.clone(),
+        });
+    }
+
+    /// Report that a player's turn was taken back, leading to `game`.
+    #[cfg(feature = "std")]
+    fn taken_back(&mut self, game: &G, player: G::Player, turn: &G::Turn) {
+        self.broadcast(game, || Event::TakenBack {
+            player,
+            turn: turn.clone(),
+        });
+    }
+
+    /// Report that the current player resigned, returning their opponent.
+    fn resigned(&mut self, game: &G) -> G::Player {
+        let winner = match self.seat(game) {
+            0 => self.player2.0.clone(),
+            _ => self.player1.0.clone(),
+        };
+        #[cfg(feature = "std")]
+        self.broadcast(game, || Event::Resigned(game.player()));
+        self.finished(game, Some(winner.clone()));
+        winner
+    }
+
+    /// Check if a seat ran out of time with the turn leading to `game`,
+    /// reporting the forfeit.
+    fn timed_out(&mut self, game: &G, seat: usize) -> Option<Ending<G::Player>> {
+        if !self.clock.as_ref()?.flagged(seat) {
+            return None;
+        }
+        let (player, opponent) = match seat {
+            0 => (&self.player1.0, &self.player2.0),
+            _ => (&self.player2.0, &self.player1.0),
+        };
+        let (player, opponent) = (player.clone(), opponent.clone());
+        warn!("{} ran out of time", player);
+        let winner = Some(opponent).filter(|opponent| match self.can_win {
+            Some(can_win) => can_win(game, opponent),
+            None => true,
+        });
+        #[cfg(feature = "std")]
+        self.broadcast(game, || Event::TimeForfeit(player));
+        self.finished(game, winner.clone());
+        Some(Ending::Forfeit(winner))
+    }
+
+    /// Report that a player offered a draw with the turn leading to `game`.
+    fn offered(&mut self, game: &G, player: G::Player) {
+        #[cfg(feature = "std")]
+        self.broadcast(game, || Event::DrawOffered(player));
+        #[cfg(not(feature = "std"))]
+        let _ = (game, player);
+    }
+
+    /// Report that the players agreed to a draw.
+    fn agreed(&mut self, game: &G) {
+        #[cfg(feature = "std")]
+        self.broadcast(game, || Event::DrawAgreed);
+        self.finished(game, None);
+    }
+
+    /// Report that the game is over.
+    fn finished(&mut self, game: &G, winner: Option<G::Player>) {
+        if let Some(hook) = self.on_game_over {
+            hook(game);
+        }
+        #[cfg(feature = "std")]
+        self.broadcast(game, || Event::GameOver(winner));
+        #[cfg(not(feature = "std"))]
+        let _ = winner;
+    }
+
+    /// Send an event to every observer, along with the position.
+    #[cfg(feature = "std")]
+    fn broadcast(&mut self, game: &G, event: impl FnOnce() -> Event<G>) {
+        if self.observers.is_empty() {
+            return;
+        }
+        let event = event();
+        for observer in &mut self.observers {
+            observer.observe(game, &event);
+        }
+    }
+
+    /// Stop the clock for a seat that thought for `elapsed` on the turn
+    /// leading to `game`, returning its remaining time if it crossed a
+    /// low-time warning.
+    fn punch(&mut self, game: &G, seat: usize, elapsed: Duration) -> Option<Duration> {
+        let player = match seat {
+            0 => self.player1.0.clone(),
+            _ => self.player2.0.clone(),
+        };
+        let clock = self.clock.as_mut()?;
+        let threshold = clock.punch(seat, elapsed)?;
+        let remaining = clock.remaining(seat);
+        warn!(
+            "{} is low on time: {} remaining (below {})",
+            player,
+            Remaining(remaining),
+            Remaining(threshold),
+        );
+        #[cfg(feature = "std")]
+        self.broadcast(game, || Event::LowTime { player, remaining });
+        #[cfg(not(feature = "std"))]
+        let _ = game;
+        Some(remaining)
+    }
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
+    use std::fmt::{self, Display};
+    use std::time::Duration;
+
+    use super::{
+        Config, Context, Decision, Evaluate, Game, Notation, ParseTurn, Reversible, Score,
+        SetupError, SetupGame, TurnCoords, TurnError,
+    };
+    use crate::clock::TimeControl;
+
+    /// Subtraction game where players take 1-3 stones; taking the last wins.
+    #[derive(Clone, Debug, Hash, PartialEq, Eq)]
+    pub(crate) struct Nim {
+        pub(crate) stones: u32,
+        pub(crate) player: u8,
+    }
+
+    impl Nim {
+        pub(crate) fn new(stones: u32) -> Nim {
+            Nim { stones, player: 0 }
+        }
+    }
+
+    impl Display for Nim {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "{} stones, player {} to move", self.stones, self.player)
+        }
+    }
+
+    impl Game for Nim {
+        type Player = u8;
+        type Turn = u32;
+
+        fn player(&self) -> Self::Player {
+            self.player
+        }
+
+        fn turns(&self) -> Vec<Self::Turn> {
+            (1..=self.stones.min(3)).collect()
+        }
+
+        fn play(&mut self, turn: Self::Turn) -> bool {
+            if !(1..=self.stones.min(3)).contains(&turn) {
+                return false;
+            }
+            self.stones -= turn;
+            self.player ^= 1;
+            true
+        }
+
+        fn over(&self) -> bool {
+            self.stones == 0
+        }
+
+        fn winner(&self) -> Option<Self::Player> {
+            self.over().then_some(self.player ^ 1)
+        }
+    }
+
+    impl Reversible for Nim {
+        fn unplay(&mut self, turn: &Self::Turn) {
+            self.stones += turn;
+            self.player ^= 1;
+        }
+    }
+
+    // Stones taken laid out in a row
+    impl TurnCoords for Nim {
+        fn dimensions(&self) -> (usize, usize) {
+            (1, 3)
+        }
+
+        fn coords(&self, turn: &u32) -> Option<(usize, usize)> {
+            Some((0, *turn as usize - 1))
+        }
+    }
+
+    // Turns are read as the stones taken, optionally written as "-2"
+    impl ParseTurn for Nim {
+        fn parse_turn(&self, s: &str) -> Result<u32, TurnError> {
+            let taken: u32 = s
+                .strip_prefix('-')
+                .unwrap_or(s)
+                .parse()
+                .map_err(|_| TurnError::Invalid(format!("{s} is not a number")))?;
+            match self.turns().contains(&taken) {
+                true => Ok(taken),
+                false => Err(TurnError::Illegal(format!("cannot take {taken} stones"))),
+            }
+        }
+    }
+
+    // Positions are written as the stones left and the player to move
+    impl SetupGame for Nim {
+        fn from_setup(s: &str) -> Result<Nim, SetupError> {
+            let malformed = || SetupError::Malformed(format!("expected `<stones> <player>`: {s}"));
+            let (stones, player) = s.split_once(' ').ok_or_else(malformed)?;
+            let stones = stones.parse().map_err(|_| malformed())?;
+            match player.parse() {
+                Ok(player @ (0 | 1)) => Ok(Nim { stones, player }),
+                Ok(player) => Err(SetupError::Invalid(format!("no player {player}"))),
+                Err(_) => Err(malformed()),
+            }
+        }
+
+        fn to_setup(&self) -> String {
+            format!("{} {}", self.stones, self.player)
+        }
+    }
+
+    impl Notation for Nim {
+        fn write_turn(&self, turn: &u32) -> String {
+            format!("-{turn}")
+        }
+    }
+
+    // Leaving a multiple of four stones is good for the player who moved
+    impl Evaluate for Nim {
+        fn evaluate(&self, player: &u8) -> f64 {
+            let good = self.stones.is_multiple_of(4);
+            if good == (*player != self.player) {
+                1.
+            } else {
+                0.
+            }
+        }
+    }
+
+    /// Puzzle scoring a point for each of five picks one more than the last.
+    #[derive(Clone, Debug, Default)]
+    pub(crate) struct Steps {
+        pub(crate) picks: Vec<u32>,
+    }
+
+    impl Display for Steps {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "{:?}", self.picks)
+        }
+    }
+
+    impl Game for Steps {
+        type Player = u8;
+        type Turn = u32;
+
+        fn player(&self) -> Self::Player {
+            0
+        }
+
+        fn players(&self) -> usize {
+            1
+        }
+
+        fn turns(&self) -> Vec<Self::Turn> {
+            match self.over() {
+                true => Vec::new(),
+                false => (0..5).collect(),
+            }
+        }
+
+        fn play(&mut self, turn: Self::Turn) -> bool {
+            if self.over() || turn >= 5 {
+                return false;
+            }
+            self.picks.push(turn);
+            true
+        }
+
+        fn over(&self) -> bool {
+            self.picks.len() >= 5
+        }
+
+        fn winner(&self) -> Option<Self::Player> {
+            None
+        }
+    }
+
+    impl Score for Steps {
+        fn score(&self, _: &Self::Player) -> f64 {
+            self.picks.windows(2).filter(|w| w[1] == w[0] + 1).count() as f64
+        }
+    }
+
     #[test]
     fn it_works() {
         let result = 2 + 2;
         assert_eq!(result, 4);
     }
+
+    #[test]
+    #[cfg(feature = "entropy")]
+    fn main_plays_to_completion() {
+        let run = crate::ai::rand::run;
+        Nim::new(10).main(Config::new((0, run), (1, run)));
+    }
+
+    #[test]
+    fn play_out_uses_external_clock() {
+        let control = TimeControl::new(Duration::from_secs(10), Duration::ZERO);
+        let mut config = Config::new((0, |_: &Nim| 1), (1, |_: &Nim| 2)).clock(control);
+        // Every reading advances the clock by a second
+        let mut ticks = 0;
+        let now = || {
+            ticks += 1;
+            Duration::from_secs(ticks)
+        };
+        assert_eq!(Nim::new(7).play_out(&mut config, now), Some(0));
+        let clock = config.clock.unwrap();
+        assert_eq!(clock.remaining(0), Duration::from_secs(7));
+        assert_eq!(clock.remaining(1), Duration::from_secs(8));
+    }
+
+    #[test]
+    fn deciders_see_their_clock() {
+        use core::sync::atomic::{AtomicU64, Ordering};
+
+        // Remaining time seen by the first player, in seconds
+        static SEEN: AtomicU64 = AtomicU64::new(0);
+
+        let timed = |_: &Nim, context: &Context| {
+            let remaining = context.remaining.unwrap();
+            assert_eq!(context.budget, Some(remaining / 20));
+            SEEN.store(remaining.as_secs(), Ordering::Relaxed);
+            Decision::Play(1)
+        };
+        let control = TimeControl::new(Duration::from_secs(10), Duration::ZERO);
+        let mut config = Config::new((0, |_: &Nim| 1), (1, |_: &Nim| 1))
+            .clock(control)
+            .decider(0, timed);
+        let mut ticks = 0;
+        let now = || {
+            ticks += 1;
+            Duration::from_secs(ticks) / 2
+        };
+        assert_eq!(Nim::new(3).play_out(&mut config, now), Some(0));
+        assert_eq!(SEEN.load(Ordering::Relaxed), 9);
+    }
+
+    #[test]
+    fn hooks_follow_the_game() {
+        use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+        static STARTED: AtomicUsize = AtomicUsize::new(0);
+        static PLAYED: AtomicUsize = AtomicUsize::new(0);
+        static ILLEGAL: AtomicUsize = AtomicUsize::new(0);
+        static OVER: AtomicBool = AtomicBool::new(false);
+        // Tries to take too many stones once
+        static CONFUSED: AtomicBool = AtomicBool::new(true);
+
+        let take = |_: &Nim| match CONFUSED.swap(false, Ordering::Relaxed) {
+            true => 4,
+            false => 1,
+        };
+        let mut config = Config::new((0, take), (1, take))
+            .on_turn_start(|_| {
+                STARTED.fetch_add(1, Ordering::Relaxed);
+            })
+            .on_turn_played(|game, turn| {
+                assert_eq!(*turn, 1);
+                assert!(game.stones < 3);
+                PLAYED.fetch_add(1, Ordering::Relaxed);
+            })
+            .on_illegal_turn(|game, turn| {
+                assert_eq!((game.stones, *turn), (3, 4));
+                ILLEGAL.fetch_add(1, Ordering::Relaxed);
+            })
+            .on_game_over(|game| OVER.store(game.over(), Ordering::Relaxed));
+        assert_eq!(
+            Nim::new(3).play_out(&mut config, || Duration::ZERO),
+            Some(0)
+        );
+        assert_eq!(STARTED.load(Ordering::Relaxed), 3);
+        assert_eq!(PLAYED.load(Ordering::Relaxed), 3);
+        assert_eq!(ILLEGAL.load(Ordering::Relaxed), 1);
+        assert!(OVER.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn resigning_concedes_the_game() {
+        let resign = |game: &Nim, _: &Context| match game.stones {
+            1 => Decision::Resign,
+            _ => Decision::Play(1),
+        };
+        let mut config = Config::new((0, |_: &Nim| 1), (1, |_: &Nim| 1)).decider(0, resign);
+        let context = Context::default();
+        assert_eq!(config.decide(&Nim::new(2), &context), Decision::Play(1));
+        assert_eq!(
+            Nim::new(3).play_out(&mut config, || Duration::ZERO),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn draws_can_be_agreed() {
+        let offer = |_: &Nim, _: &Context| Decision::OfferDraw(1);
+        let accept = |_: &Nim, context: &Context| match context.offered {
+            true => Decision::AcceptDraw,
+            false => Decision::Play(1),
+        };
+        let mut config = Config::new((0, |_: &Nim| 1), (1, |_: &Nim| 1))
+            .decider(0, offer)
+            .decider(1, accept);
+        assert_eq!(Nim::new(5).play_out(&mut config, || Duration::ZERO), None);
+
+        // Accepting a draw which was never offered resigns
+        let mut config = Config::new((0, |_: &Nim| 1), (1, |_: &Nim| 1))
+            .decider(1, |_: &Nim, _: &Context| Decision::AcceptDraw);
+        assert_eq!(
+            Nim::new(5).play_out(&mut config, || Duration::ZERO),
+            Some(0)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn slow_agents_are_cut_off() {
+        let slow = |game: &Nim| {
+            std::thread::sleep(Duration::from_secs(1));
+            game.stones.min(3)
+        };
+        let broken = |_: &Nim| -> u32 { panic!("lost the plot") };
+        let mut config =
+            Config::new((0, slow), (1, broken)).move_limit(Duration::from_millis(10), |_| 1);
+        let context = Context::default();
+        assert_eq!(config.decide(&Nim::new(3), &context), Decision::Play(1));
+        // Cut off every turn, neither player can take the last three stones
+        assert_eq!(
+            Nim::new(5).play_out(&mut config, || Duration::ZERO),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn running_out_of_time_forfeits() {
+        let control = TimeControl::new(Duration::from_secs(1), Duration::ZERO);
+        // Every reading advances the clock by two seconds
+        let ticking = || {
+            let mut ticks = 0;
+            move || {
+                ticks += 2;
+                Duration::from_secs(ticks)
+            }
+        };
+        let mut config = Config::new((0, |_: &Nim| 1), (1, |_: &Nim| 1)).clock(control.clone());
+        assert_eq!(Nim::new(10).play_out(&mut config, ticking()), Some(1));
+        assert!(config.clock.unwrap().flagged(0));
+
+        // Opponents who could never win only draw
+        let mut config = Config::new((0, |_: &Nim| 1), (1, |_: &Nim| 1))
+            .clock(control)
+            .can_win(|game, player| game.stones > 0 && *player == 0);
+        assert_eq!(Nim::new(10).play_out(&mut config, ticking()), None);
+    }
+
+    #[test]
+    fn players_defaults_to_two() {
+        assert_eq!(Nim::new(10).players(), 2);
+        assert_eq!(Steps::default().players(), 1);
+    }
 }
@@ -0,0 +1,431 @@
+//! Dynamically loaded games.
+//!
+//! A plugin is a `cdylib` which exports a game with [`export_game!`]. The host
+//! loads it with [`Plugin::load`] and plays it through [`PluginGame`], which
+//! implements [`Game`]. Only C types cross the library boundary, so plugins
+//! and hosts may be built by different compiler versions as long as they
+//! agree on [`ABI_VERSION`].
+//!
+//! Turns are identified by their index into [`Game::turns`], while players
+//! and turns are described across the boundary by their `Display` output.
+//! Playing a turn passes both, so a turn taken from another position is
+//! rejected unless it names the same move.
+
+use std::error;
+use std::ffi::{c_char, c_void, CStr};
+use std::fmt::{self, Debug, Display};
+use std::path::Path;
+use std::sync::Arc;
+
+use libloading::Library;
+
+use crate::Game;
+
+/// Version of the plugin ABI; bumped on any change to [`VTable`].
+pub const ABI_VERSION: u32 = 3;
+
+/// Name of the symbol a plugin exports to provide its [`VTable`].
+pub const ENTRY: &[u8] = b"gamesweet_plugin";
+
+/// Callback used to pass strings across the boundary.
+pub type Write = unsafe extern "C" fn(ctx: *mut c_void, ptr: *const u8, len: usize);
+
+/// Table of functions implementing a game across the library boundary.
+///
+/// States are opaque pointers owned by the plugin. Hosts may send and share
+/// them between threads, so tables are only built by [`VTable::new`] for
+/// games which are [`Send`] and [`Sync`]. The legal turns are listed in a
+/// single call, which writes the name of each in order.
+#[repr(C)]
+pub struct VTable {
+    abi: u32,
+    name: *const c_char,
+    new: unsafe extern "C" fn() -> *mut c_void,
+    clone: unsafe extern "C" fn(*const c_void) -> *mut c_void,
+    drop: unsafe extern "C" fn(*mut c_void),
+    display: unsafe extern "C" fn(*const c_void, Write, *mut c_void),
+    player: unsafe extern "C" fn(*const c_void, Write, *mut c_void),
+    turns: unsafe extern "C" fn(*const c_void, Write, *mut c_void),
+    play: unsafe extern "C" fn(*mut c_void, usize, *const u8, usize) -> bool,
+    over: unsafe extern "C" fn(*const c_void) -> bool,
+    winner: unsafe extern "C" fn(*const c_void, Write, *mut c_void) -> bool,
+}
+
+// SAFETY: The table holds only function pointers and a static string.
+unsafe impl Sync for VTable {}
+unsafe impl Send for VTable {}
+
+impl VTable {
+    /// Create the table for a game whose initial state is its default.
+    pub const fn new<G: Game + Default + Send + Sync>(name: &'static CStr) -> VTable {
+        VTable {
+            abi: ABI_VERSION,
+            name: name.as_ptr(),
+            new: new::<G>,
+            clone: clone::<G>,
+            drop: drop::<G>,
+            display: display::<G>,
+            player: player::<G>,
+            turns: turns::<G>,
+            play: play::<G>,
+            over: over::<G>,
+            winner: winner::<G>,
+        }
+    }
+}
+
+/// Export a game from a plugin library.
+///
+/// The game must implement [`Default`] to provide its initial state, and be
+/// [`Send`] and [`Sync`] so that hosts can search it on many threads.
+#[macro_export]
+macro_rules! export_game {
+    ($game:ty, $name:literal) => {
+        #[no_mangle]
+        pub extern "C" fn gamesweet_plugin() -> *const $crate::plugin::VTable {
+            static VTABLE: $crate::plugin::VTable = $crate::plugin::VTable::new::<$game>(
+                match ::std::ffi::CStr::from_bytes_with_nul(concat!($name, "\0").as_bytes()) {
+                    Ok(name) => name,
+                    Err(_) => panic!("plugin names cannot contain NUL"),
+                },
+            );
+            &VTABLE
+        }
+    };
+}
+
+unsafe extern "C" fn new<G: Game + Default>() -> *mut c_void {
+    Box::into_raw(Box::new(G::default())) as *mut c_void
+}
+
+unsafe extern "C" fn clone<G: Game>(game: *const c_void) -> *mut c_void {
+    Box::into_raw(Box::new((*(game as *const G)).clone())) as *mut c_void
+}
+
+unsafe extern "C" fn drop<G: Game>(game: *mut c_void) {
+    std::mem::drop(Box::from_raw(game as *mut G));
+}
+
+unsafe fn send(text: &str, write: Write, ctx: *mut c_void) {
+    write(ctx, text.as_ptr(), text.len());
+}
+
+unsafe extern "C" fn display<G: Game>(game: *const c_void, write: Write, ctx: *mut c_void) {
+    send(&(*(game as *const G)).to_string(), write, ctx);
+}
+
+unsafe extern "C" fn player<G: Game>(game: *const c_void, write: Write, ctx: *mut c_void) {
+    send(&(*(game as *const G)).player().to_string(), write, ctx);
+}
+
+unsafe extern "C" fn turns<G: Game>(game: *const c_void, write: Write, ctx: *mut c_void) {
+    for turn in (*(game as *const G)).turns() {
+        send(&turn.to_string(), write, ctx);
+    }
+}
+
+unsafe extern "C" fn play<G: Game>(
+    game: *mut c_void,
+    idx: usize,
+    ptr: *const u8,
+    len: usize,
+) -> bool {
+    let game = &mut *(game as *mut G);
+    let name = std::slice::from_raw_parts(ptr, len);
+    match game.turns().get(idx) {
+        Some(turn) if turn.to_string().as_bytes() == name => game.play(turn.clone()),
+        _ => false,
+    }
+}
+
+unsafe extern "C" fn over<G: Game>(game: *const c_void) -> bool {
+    (*(game as *const G)).over()
+}
+
+unsafe extern "C" fn winner<G: Game>(game: *const c_void, write: Write, ctx: *mut c_void) -> bool {
+    match (*(game as *const G)).winner() {
+        Some(player) => {
+            send(&player.to_string(), write, ctx);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Host-side callback appending to a `String`.
+unsafe extern "C" fn append(ctx: *mut c_void, ptr: *const u8, len: usize) {
+    let buf = &mut *(ctx as *mut String);
+    buf.push_str(&String::from_utf8_lossy(std::slice::from_raw_parts(
+        ptr, len,
+    )));
+}
+
+/// Host-side callback pushing each string to a `Vec<String>`.
+unsafe extern "C" fn push(ctx: *mut c_void, ptr: *const u8, len: usize) {
+    let buf = &mut *(ctx as *mut Vec<String>);
+    buf.push(String::from_utf8_lossy(std::slice::from_raw_parts(ptr, len)).into_owned());
+}
+
+/// An error loading a plugin.
+#[derive(Debug)]
+pub enum Error {
+    /// The library or its entry point could not be loaded.
+    Load(libloading::Error),
+    /// The plugin was built against a different ABI version.
+    Version(u32),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Load(err) => write!(f, "could not load plugin: {err}"),
+            Error::Version(abi) => write!(
+                f,
+                "plugin ABI version {abi} does not match host version {ABI_VERSION}"
+            ),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+impl From<libloading::Error> for Error {
+    fn from(err: libloading::Error) -> Self {
+        Error::Load(err)
+    }
+}
+
+/// A loaded game plugin.
+#[derive(Clone)]
+pub struct Plugin {
+    vtable: &'static VTable,
+    // Keeps the library loaded while any state is alive
+    _lib: Option<Arc<Library>>,
+}
+
+impl Plugin {
+    /// Load a plugin from a dynamic library.
+    ///
+    /// # Safety
+    ///
+    /// Loading a library runs its initialization code, and the library must
+    /// export a valid [`VTable`] through [`export_game!`]. A library built any
+    /// other way must lay out the table the same way, and its states must be
+    /// safe to send and share between threads.
+    pub unsafe fn load(path: impl AsRef<Path>) -> Result<Plugin, Error> {
+        let lib = Library::new(path.as_ref())?;
+        let entry = lib.get::<extern "C" fn() -> *const VTable>(ENTRY)?;
+        let vtable = &*entry();
+        if vtable.abi != ABI_VERSION {
+            return Err(Error::Version(vtable.abi));
+        }
+        Ok(Plugin {
+            vtable,
+            _lib: Some(Arc::new(lib)),
+        })
+    }
+
+    /// Create a plugin from a table linked into the host.
+    pub fn from_static(vtable: &'static VTable) -> Result<Plugin, Error> {
+        if vtable.abi != ABI_VERSION {
+            return Err(Error::Version(vtable.abi));
+        }
+        Ok(Plugin { vtable, _lib: None })
+    }
+
+    /// Get the name of the game.
+    pub fn name(&self) -> &str {
+        // SAFETY: Tables are built from a static `CStr` by `VTable::new`.
+        unsafe { CStr::from_ptr(self.vtable.name) }
+            .to_str()
+            .unwrap_or("<invalid>")
+    }
+
+    /// Create a game in its initial state.
+    pub fn game(&self) -> PluginGame {
+        PluginGame {
+            // SAFETY: The plugin owns the returned state until it is dropped.
+            state: unsafe { (self.vtable.new)() },
+            plugin: self.clone(),
+        }
+    }
+}
+
+impl Debug for Plugin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Plugin")
+            .field("name", &self.name())
+            .finish()
+    }
+}
+
+/// A player in a plugin game, identified by its name.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PluginPlayer(pub String);
+
+impl Display for PluginPlayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// A turn in a plugin game, identified by its index among the legal turns
+/// and its name.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PluginTurn {
+    pub idx: usize,
+    pub name: String,
+}
+
+impl Display for PluginTurn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+/// The state of a game provided by a plugin.
+pub struct PluginGame {
+    state: *mut c_void,
+    plugin: Plugin,
+}
+
+impl PluginGame {
+    /// Collect a string written by the plugin.
+    fn read(&self, f: unsafe extern "C" fn(*const c_void, Write, *mut c_void)) -> String {
+        let mut buf = String::new();
+        // SAFETY: The state is valid and `append` only writes to `buf`.
+        unsafe { f(self.state, append, &mut buf as *mut String as *mut c_void) };
+        buf
+    }
+}
+
+// SAFETY: Plugin states are owned values of a `Game` which is `Send` and
+// `Sync`, as required by `VTable::new`, and the table is static.
+unsafe impl Send for PluginGame {}
+unsafe impl Sync for PluginGame {}
+
+impl Clone for PluginGame {
+    fn clone(&self) -> Self {
+        PluginGame {
+            // SAFETY: The state is valid for the lifetime of `self`.
+            state: unsafe { (self.plugin.vtable.clone)(self.state) },
+            plugin: self.plugin.clone(),
+        }
+    }
+}
+
+impl Drop for PluginGame {
+    fn drop(&mut self) {
+        // SAFETY: The state was created by this plugin and is not used again.
+        unsafe { (self.plugin.vtable.drop)(self.state) }
+    }
+}
+
+impl Debug for PluginGame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PluginGame")
+            .field("plugin", &self.plugin.name())
+            .field("state", &self.read(self.plugin.vtable.display))
+            .finish()
+    }
+}
+
+impl Display for PluginGame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.read(self.plugin.vtable.display))
+    }
+}
+
+impl Game for PluginGame {
+    type Player = PluginPlayer;
+    type Turn = PluginTurn;
+
+    fn player(&self) -> Self::Player {
+        PluginPlayer(self.read(self.plugin.vtable.player))
+    }
+
+    fn turns(&self) -> Vec<Self::Turn> {
+        let mut names: Vec<String> = Vec::new();
+        // SAFETY: `push` only writes to `names`.
+        unsafe {
+            (self.plugin.vtable.turns)(
+                self.state,
+                push,
+                &mut names as *mut Vec<String> as *mut c_void,
+            )
+        };
+        (0..)
+            .zip(names)
+            .map(|(idx, name)| PluginTurn { idx, name })
+            .collect()
+    }
+
+    fn play(&mut self, turn: Self::Turn) -> bool {
+        let name = turn.name.as_bytes();
+        // SAFETY: The state is valid and uniquely borrowed, and the name
+        // outlives the call.
+        unsafe { (self.plugin.vtable.play)(self.state, turn.idx, name.as_ptr(), name.len()) }
+    }
+
+    fn over(&self) -> bool {
+        // SAFETY: The state is valid for the lifetime of `self`.
+        unsafe { (self.plugin.vtable.over)(self.state) }
+    }
+
+    fn winner(&self) -> Option<Self::Player> {
+        let mut name = String::new();
+        // SAFETY: `append` only writes to `name`.
+        let some = unsafe {
+            (self.plugin.vtable.winner)(self.state, append, &mut name as *mut String as *mut c_void)
+        };
+        some.then_some(PluginPlayer(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::Nim;
+
+    impl Default for Nim {
+        fn default() -> Self {
+            Nim::new(5)
+        }
+    }
+
+    static NIM: VTable = VTable::new::<Nim>(c"nim");
+
+    #[test]
+    fn play_through_vtable() {
+        let plugin = Plugin::from_static(&NIM).unwrap();
+        assert_eq!(plugin.name(), "nim");
+
+        let mut game = plugin.game();
+        let turns = game.turns();
+        assert_eq!(turns.len(), 3);
+        assert_eq!(turns[2].name, "3");
+
+        let copy = game.clone();
+        assert!(game.play(turns[2].clone()));
+        // Turns must name the move at their index
+        let wrong = PluginTurn {
+            idx: 0,
+            name: "3".to_string(),
+        };
+        assert!(!copy.clone().play(wrong));
+        assert!(game.play(game.turns()[1].clone()));
+        assert!(game.over());
+        assert_eq!(game.winner(), Some(PluginPlayer("1".to_string())));
+        assert_eq!(copy.player(), PluginPlayer("0".to_string()));
+        assert!(!copy.over());
+    }
+
+    crate::export_game!(Nim, "exported nim");
+
+    #[test]
+    fn exported_tables_are_named() {
+        // SAFETY: The table is static.
+        let plugin = Plugin::from_static(unsafe { &*gamesweet_plugin() }).unwrap();
+        assert_eq!(plugin.name(), "exported nim");
+    }
+}
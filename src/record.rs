@@ -0,0 +1,627 @@
+//! Records of played games.
+//!
+//! A [`GameRecord`] holds everything needed to replay a game: the initial
+//! position, each turn with its thinking time and free-form tags, the
+//! players, and the result. [`Game::main_recorded`] fills one in while
+//! playing, and records can be saved and loaded as JSON, or for games with a
+//! [`Notation`](crate::Notation), in a [PGN-like notation](pgn). Records of
+//! grid games, along with their variations, can be exported as [SGF](sgf).
+//! A [`Replay`](replay::Replay) steps through a record and branches new lines
+//! from it, and with the `mcts` feature, [`annotate`](annotate::annotate)
+//! tags each turn with the engine's analysis for a
+//! [`BlunderReport`](blunders::BlunderReport) or a
+//! [`WinGraph`](graph::WinGraph).
+//!
+//! Records are written as a single JSON object:
+//!
+//! ```json
+//! {"initial":"10 stones","players":["0","1"],"started":1700000000000,
+//!  "finished":1700000004000,"over":true,"winner":"1","termination":null,
+//!  "plies":[{"turn":"2","elapsed":1500,"tags":{"eval":"0.6"}}]}
+//! ```
+//!
+//! Timestamps are milliseconds since the Unix epoch and thinking times are
+//! milliseconds, either of which may be `null`. The termination is `null`
+//! unless the game ended early, by `"resignation"`, `"agreement"`, or
+//! `"time forfeit"`. The initial position is
+//! written as displayed, for reference only, so it must be supplied again
+//! when reading a record.
+
+use std::error;
+use std::fmt::{self, Display};
+use std::io::{self, Write};
+use std::iter;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::util::json::{self, Value};
+use crate::{Game, ParseTurn, TurnError};
+
+#[cfg(feature = "mcts")]
+pub mod annotate;
+#[cfg(feature = "mcts")]
+pub mod blunders;
+pub mod graph;
+pub mod pgn;
+pub mod replay;
+pub mod sgf;
+
+/// Seconds in a day.
+const DAY: u64 = 86_400;
+/// Termination of a game ended by resignation.
+const RESIGNATION: &str = "resignation";
+/// Termination of a game drawn by agreement.
+const AGREEMENT: &str = "agreement";
+/// Termination of a game ended by a player running out of time.
+const TIME_FORFEIT: &str = "time forfeit";
+
+/// The result of a finished game.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Outcome<P> {
+    /// Won by a player.
+    Win(P),
+    /// Won by a player after their opponent resigned.
+    Resignation(P),
+    /// Finished without a winner.
+    Draw,
+    /// Drawn by agreement of the players.
+    Agreement,
+    /// Won by a player after their opponent ran out of time.
+    Timeout(P),
+    /// Drawn after a player ran out of time, as their opponent could not win.
+    TimeoutDraw,
+}
+
+impl<P> Outcome<P> {
+    /// Get the winner, if any.
+    pub fn winner(&self) -> Option<&P> {
+        match self {
+            Outcome::Win(player) | Outcome::Resignation(player) | Outcome::Timeout(player) => {
+                Some(player)
+            }
+            Outcome::Draw | Outcome::Agreement | Outcome::TimeoutDraw => None,
+        }
+    }
+
+    /// Get how the game ended, if it ended before it was over.
+    fn termination(&self) -> Option<&'static str> {
+        match self {
+            Outcome::Win(_) | Outcome::Draw => None,
+            Outcome::Resignation(_) => Some(RESIGNATION),
+            Outcome::Agreement => Some(AGREEMENT),
+            Outcome::Timeout(_) | Outcome::TimeoutDraw => Some(TIME_FORFEIT),
+        }
+    }
+}
+
+/// A turn played in a recorded game.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Ply<T> {
+    /// Turn played.
+    pub turn: T,
+    /// Time spent choosing the turn.
+    pub elapsed: Option<Duration>,
+    /// Annotations, such as evaluations, in the order added.
+    pub tags: Vec<(String, String)>,
+    /// Alternative lines to this turn, each played from the position before
+    /// it.
+    pub variations: Vec<Vec<Ply<T>>>,
+}
+
+impl<T> Ply<T> {
+    /// Create a new Ply without timing or tags.
+    pub fn new(turn: T) -> Ply<T> {
+        Ply {
+            turn,
+            elapsed: None,
+            tags: Vec::new(),
+            variations: Vec::new(),
+        }
+    }
+
+    /// Look up a tag.
+    pub fn tag(&self, key: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Set a tag, replacing any with the same key.
+    pub fn set_tag(&mut self, key: &str, value: String) {
+        match self.tags.iter_mut().find(|(name, _)| name == key) {
+            Some((_, old)) => *old = value,
+            None => self.tags.push((key.to_string(), value)),
+        }
+    }
+}
+
+/// A game recorded from its initial position.
+#[derive(Clone, Debug)]
+pub struct GameRecord<G: Game> {
+    /// Position the game started from.
+    pub initial: G,
+    /// Names of the players, in seat order.
+    pub players: Vec<String>,
+    /// Turns played, in order.
+    pub plies: Vec<Ply<G::Turn>>,
+    /// Result, if the game is finished.
+    pub result: Option<Outcome<G::Player>>,
+    /// When the game started.
+    pub started: Option<SystemTime>,
+    /// When the game finished.
+    pub finished: Option<SystemTime>,
+}
+
+impl<G: Game> GameRecord<G> {
+    /// Create a new, empty GameRecord.
+    pub fn new(initial: G) -> GameRecord<G> {
+        GameRecord {
+            initial,
+            players: Vec::new(),
+            plies: Vec::new(),
+            result: None,
+            started: None,
+            finished: None,
+        }
+    }
+
+    /// Add a turn played in `elapsed`.
+    pub fn push(&mut self, turn: G::Turn, elapsed: Option<Duration>) {
+        self.plies.push(Ply {
+            elapsed,
+            ..Ply::new(turn)
+        });
+    }
+
+    /// Get the number of turns played.
+    pub fn len(&self) -> usize {
+        self.plies.len()
+    }
+
+    /// Check if no turns have been played.
+    pub fn is_empty(&self) -> bool {
+        self.plies.is_empty()
+    }
+
+    /// Get the turns played, in order.
+    pub fn turns(&self) -> impl Iterator<Item = &G::Turn> {
+        self.plies.iter().map(|ply| &ply.turn)
+    }
+
+    /// Get the position after `ply` turns.
+    ///
+    /// Returns `None` if `ply` is past the end of the record or a turn along
+    /// the way is illegal.
+    pub fn position(&self, ply: usize) -> Option<G> {
+        if ply > self.len() {
+            return None;
+        }
+        let mut state = self.initial.clone();
+        for turn in self.turns().take(ply) {
+            if !state.play(turn.clone()) {
+                return None;
+            }
+        }
+        Some(state)
+    }
+
+    /// Set the result from the final position and stamp the finishing time.
+    pub fn finish(&mut self) {
+        self.result = self.position(self.len()).and_then(|state| outcome(&state));
+        self.finished = Some(SystemTime::now());
+    }
+
+    /// Record that the game ended by resignation, won by `winner`, and stamp
+    /// the finishing time.
+    pub fn resign(&mut self, winner: G::Player) {
+        self.result = Some(Outcome::Resignation(winner));
+        self.finished = Some(SystemTime::now());
+    }
+
+    /// Record that the players agreed to a draw, and stamp the finishing
+    /// time.
+    pub fn agree(&mut self) {
+        self.result = Some(Outcome::Agreement);
+        self.finished = Some(SystemTime::now());
+    }
+
+    /// Record that a player ran out of time, leaving `winner` if their
+    /// opponent could still win, and stamp the finishing time.
+    pub fn forfeit(&mut self, winner: Option<G::Player>) {
+        self.result = Some(match winner {
+            Some(winner) => Outcome::Timeout(winner),
+            None => Outcome::TimeoutDraw,
+        });
+        self.finished = Some(SystemTime::now());
+    }
+
+    /// Format the record as JSON.
+    ///
+    /// Only the main line is written; variations are left out.
+    pub fn to_json(&self) -> String {
+        let time = |time: Option<SystemTime>| match time {
+            Some(time) => millis(time.duration_since(UNIX_EPOCH).unwrap_or_default()),
+            None => "null".to_string(),
+        };
+        let winner = match self.result.as_ref().and_then(Outcome::winner) {
+            Some(player) => json::string(&player.to_string()),
+            None => "null".to_string(),
+        };
+        let termination = match self.result.as_ref().and_then(Outcome::termination) {
+            Some(termination) => json::string(termination),
+            None => "null".to_string(),
+        };
+        let plies: Vec<_> = self
+            .plies
+            .iter()
+            .map(|ply| {
+                let tags: Vec<_> = ply
+                    .tags
+                    .iter()
+                    .map(|(key, value)| format!("{}:{}", json::string(key), json::string(value)))
+                    .collect();
+                format!(
+                    r#"{{"turn":{},"elapsed":{},"tags":{{{}}}}}"#,
+                    json::string(&ply.turn.to_string()),
+                    ply.elapsed.map_or("null".to_string(), millis),
+                    tags.join(","),
+                )
+            })
+            .collect();
+        format!(
+            r#"{{"initial":{},"players":{},"started":{},"finished":{},"over":{},"winner":{},"termination":{},"plies":[{}]}}"#,
+            json::string(&self.initial.to_string()),
+            json::strings(&self.players),
+            time(self.started),
+            time(self.finished),
+            self.result.is_some(),
+            winner,
+            termination,
+            plies.join(","),
+        )
+    }
+
+    /// Write the record as a line of JSON.
+    pub fn write_json(&self, mut output: impl Write) -> io::Result<()> {
+        writeln!(output, "{}", self.to_json())
+    }
+
+    /// Read a record written by [`to_json`](GameRecord::to_json), played from
+    /// `initial`.
+    ///
+    /// Turns are read with [`ParseTurn`] and replayed, and the result is
+    /// taken from the final position rather than trusted from the input,
+    /// unless the game ended before it was over.
+    pub fn from_json(initial: G, s: &str) -> Result<GameRecord<G>, Error>
+    where
+        G: ParseTurn,
+    {
+        let value = json::parse(s).map_err(Error::Json)?;
+        let field = |name| value.get(name).ok_or(Error::Field(name));
+        let invalid = |name| move |_| Error::Field(name);
+
+        let mut record = GameRecord::new(initial);
+        record.players = field("players")?
+            .as_array()
+            .ok_or(Error::Field("players"))?
+            .iter()
+            .map(|name| name.as_str().map(str::to_string))
+            .collect::<Option<_>>()
+            .ok_or(Error::Field("players"))?;
+        record.started = time(field("started")?).map_err(invalid("started"))?;
+        record.finished = time(field("finished")?).map_err(invalid("finished"))?;
+
+        let mut state = record.initial.clone();
+        let plies = field("plies")?.as_array().ok_or(Error::Field("plies"))?;
+        for (idx, ply) in plies.iter().enumerate() {
+            let name = ply.get("turn").and_then(Value::as_str);
+            let name = name.ok_or(Error::Field("turn"))?;
+            let turn = state
+                .parse_turn(name)
+                .map_err(|err| Error::Turn(idx, err))?;
+            if !state.play(turn.clone()) {
+                return Err(Error::Illegal(idx));
+            }
+            let elapsed = match ply.get("elapsed") {
+                None | Some(Value::Null) => None,
+                Some(ms) => Some(duration(ms).ok_or(Error::Field("elapsed"))?),
+            };
+            let tags = match ply.get("tags") {
+                None => Vec::new(),
+                Some(Value::Object(tags)) => tags
+                    .iter()
+                    .map(|(key, value)| Some((key.clone(), value.as_str()?.to_string())))
+                    .collect::<Option<_>>()
+                    .ok_or(Error::Field("tags"))?,
+                Some(_) => return Err(Error::Field("tags")),
+            };
+            record.plies.push(Ply {
+                elapsed,
+                tags,
+                ..Ply::new(turn)
+            });
+        }
+        record.result = outcome(&state);
+        let termination = value.get("termination").and_then(Value::as_str);
+        if let Some(termination) = termination.filter(|_| record.result.is_none()) {
+            let winner = field("winner")?.as_str();
+            record
+                .terminate(termination, winner)
+                .ok_or(Error::Field("termination"))?;
+        }
+        Ok(record)
+    }
+
+    /// Set the result of a game which ended before it was over, from how it
+    /// ended and the name of the winner, if any.
+    ///
+    /// Returns `None` if the termination is unknown, or the winner does not
+    /// play in the game.
+    fn terminate(&mut self, termination: &str, winner: Option<&str>) -> Option<()> {
+        let winner = match winner {
+            Some(name) => Some(self.player_named(name)?),
+            None => None,
+        };
+        self.result = Some(match (termination, winner) {
+            (RESIGNATION, Some(winner)) => Outcome::Resignation(winner),
+            (AGREEMENT, None) => Outcome::Agreement,
+            (TIME_FORFEIT, Some(winner)) => Outcome::Timeout(winner),
+            (TIME_FORFEIT, None) => Outcome::TimeoutDraw,
+            _ => return None,
+        });
+        Some(())
+    }
+
+    /// Find a player by name who moves in the main line, or who would move
+    /// next after it.
+    ///
+    /// Players who never got to move, such as the opponent of a player who
+    /// resigned before their first turn, are found by trying each turn from
+    /// the final position.
+    fn player_named(&self, name: &str) -> Option<G::Player> {
+        let mut state = self.initial.clone();
+        for turn in self.turns() {
+            if state.player().to_string() == name {
+                return Some(state.player());
+            }
+            if !state.play(turn.clone()) {
+                return None;
+            }
+        }
+        let mut players = iter::once(state.player()).chain(state.turns().into_iter().map(|turn| {
+            let mut next = state.clone();
+            next.play(turn);
+            next.player()
+        }));
+        players.find(|player| player.to_string() == name)
+    }
+}
+
+/// An error reading a record.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The input is not valid JSON.
+    Json(String),
+    /// The input is not valid PGN.
+    Pgn(String),
+    /// A field is missing or has the wrong type.
+    Field(&'static str),
+    /// The turn at a ply could not be read.
+    Turn(usize, TurnError),
+    /// The turn at a ply could not be played.
+    Illegal(usize),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Json(err) => write!(f, "invalid JSON: {err}"),
+            Error::Pgn(err) => write!(f, "invalid PGN: {err}"),
+            Error::Field(name) => write!(f, "invalid field: {name}"),
+            Error::Turn(ply, err) => write!(f, "at ply {ply}: {err}"),
+            Error::Illegal(ply) => write!(f, "recorded turn at ply {ply} is illegal"),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+/// Get the result of a position, if it is finished.
+fn outcome<G: Game>(state: &G) -> Option<Outcome<G::Player>> {
+    state.over().then(|| match state.winner() {
+        Some(player) => Outcome::Win(player),
+        None => Outcome::Draw,
+    })
+}
+
+/// Format a duration as whole milliseconds.
+fn millis(duration: Duration) -> String {
+    duration.as_millis().to_string()
+}
+
+/// Read a duration written in milliseconds.
+fn duration(value: &Value) -> Option<Duration> {
+    let ms = value.as_f64().filter(|ms| *ms >= 0.)?;
+    Some(Duration::from_millis(ms as u64))
+}
+
+/// Read an optional timestamp written in milliseconds since the Unix epoch.
+fn time(value: &Value) -> Result<Option<SystemTime>, ()> {
+    match value {
+        Value::Null => Ok(None),
+        ms => duration(ms).map(|since| Some(UNIX_EPOCH + since)).ok_or(()),
+    }
+}
+
+/// Get the calendar date of a time as a year, month, and day, in UTC.
+fn civil(time: SystemTime) -> (i64, u32, u32) {
+    let days = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+        / DAY;
+    // Count from March 1st of year 0, in 400-year eras
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    (yoe + era * 400 + i64::from(month <= 2), month, day)
+}
+
+/// Get midnight of a calendar date, in UTC.
+///
+/// Returns `None` for dates before the Unix epoch.
+fn midnight(year: i64, month: u32, day: u32) -> Option<SystemTime> {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = i64::from((month + 9) % 12);
+    let doy = (153 * mp + 2) / 5 + i64::from(day) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = u64::try_from(era * 146_097 + doe - 719_468).ok()?;
+    Some(UNIX_EPOCH + Duration::from_secs(days * DAY))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::Nim;
+
+    #[test]
+    fn json_round_trip() {
+        let mut record = GameRecord::new(Nim::new(5));
+        record.players = vec!["mcts".to_string(), "human \"h\"".to_string()];
+        record.started = Some(UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+        record.push(2, Some(Duration::from_millis(1500)));
+        record.push(3, None);
+        record.plies[1]
+            .tags
+            .push(("eval".to_string(), "0.9".to_string()));
+        record.finish();
+        assert_eq!(record.result, Some(Outcome::Win(1)));
+        assert_eq!(
+            record.position(1),
+            Some(Nim {
+                stones: 3,
+                player: 1
+            })
+        );
+        assert_eq!(record.position(3), None);
+
+        let json = record.to_json();
+        assert!(json.starts_with(r#"{"initial":"5 stones, player 0 to move","players":"#));
+        let read = GameRecord::from_json(Nim::new(5), &json).unwrap();
+        assert_eq!(read.players, record.players);
+        assert_eq!(read.plies, record.plies);
+        assert_eq!(read.result, record.result);
+        assert_eq!(read.started, record.started);
+        assert_eq!(read.plies[1].tag("eval"), Some("0.9"));
+
+        // Turns are replayed from the supplied position
+        assert!(matches!(
+            GameRecord::from_json(Nim::new(4), &json),
+            Err(Error::Turn(1, TurnError::Illegal(_)))
+        ));
+        let json = json.replace(r#""turn":"3""#, r#""turn":"x""#);
+        assert!(matches!(
+            GameRecord::from_json(Nim::new(5), &json),
+            Err(Error::Turn(1, TurnError::Invalid(_)))
+        ));
+        assert!(GameRecord::<Nim>::from_json(Nim::new(5), "{").is_err());
+    }
+
+    #[test]
+    fn main_records_games() {
+        fn one(_: &Nim) -> u32 {
+            1
+        }
+        let record = Nim::new(3).main_recorded(crate::Config::new((0, one), (1, one)));
+        assert_eq!(record.players, ["0", "1"]);
+        assert_eq!(record.turns().copied().collect::<Vec<_>>(), [1, 1, 1]);
+        assert!(record.plies.iter().all(|ply| ply.elapsed.is_some()));
+        assert_eq!(record.result, Some(Outcome::Win(0)));
+        assert!(record.started <= record.finished);
+    }
+
+    #[test]
+    fn early_endings_are_recorded() {
+        use crate::Decision;
+
+        fn one(_: &Nim) -> u32 {
+            1
+        }
+        // Gives up once it is sure to lose
+        fn quitter(game: &Nim, _: &crate::Context) -> Decision<u32> {
+            match game.stones % 4 {
+                0 => Decision::Resign,
+                _ => Decision::Play(1),
+            }
+        }
+        let config = crate::Config::new((0, one), (1, one)).decider(1, quitter);
+        let record = Nim::new(5).main_recorded(config);
+        assert_eq!(record.len(), 1);
+        assert_eq!(record.result, Some(Outcome::Resignation(0)));
+
+        let json = record.to_json();
+        assert!(json.contains(r#""over":true,"winner":"0","termination":"resignation""#));
+        let read = GameRecord::from_json(Nim::new(5), &json).unwrap();
+        assert_eq!(read.result, record.result);
+        let pgn = record.to_pgn();
+        assert!(pgn.contains("[Termination \"resignation\"]"));
+        let read = GameRecord::from_pgn(Nim::new(5), &pgn).unwrap();
+        assert_eq!(read.result, record.result);
+
+        // Agreed draws are kept apart from drawn positions
+        let mut record = record;
+        record.agree();
+        let json = record.to_json();
+        assert!(json.contains(r#""winner":null,"termination":"agreement""#));
+        let read = GameRecord::from_json(Nim::new(5), &json).unwrap();
+        assert_eq!(read.result, Some(Outcome::Agreement));
+        let pgn = record.to_pgn();
+        assert!(pgn.contains("[Result \"1/2-1/2\"]\n[Termination \"agreement\"]"));
+        let read = GameRecord::from_pgn(Nim::new(5), &pgn).unwrap();
+        assert_eq!(read.result, Some(Outcome::Agreement));
+
+        // So are time forfeits, whether or not anyone wins
+        for winner in [Some(1), None] {
+            record.forfeit(winner);
+            let json = record.to_json();
+            assert!(json.contains(r#""termination":"time forfeit""#));
+            let read = GameRecord::from_json(Nim::new(5), &json).unwrap();
+            assert_eq!(read.result, record.result);
+            let read = GameRecord::from_pgn(Nim::new(5), &record.to_pgn()).unwrap();
+            assert_eq!(read.result, record.result);
+        }
+        assert_eq!(record.result, Some(Outcome::TimeoutDraw));
+
+        // Resigning before the first turn still names a winner
+        let mut record = GameRecord::new(Nim::new(5));
+        record.resign(1);
+        let read = GameRecord::from_json(Nim::new(5), &record.to_json()).unwrap();
+        assert_eq!(read.result, Some(Outcome::Resignation(1)));
+        let read = GameRecord::from_pgn(Nim::new(5), &record.to_pgn()).unwrap();
+        assert_eq!(read.result, Some(Outcome::Resignation(1)));
+    }
+
+    #[test]
+    fn dates_convert() {
+        for (days, ymd) in [
+            (0, (1970, 1, 1)),
+            (19_675, (2023, 11, 14)),
+            (11_016, (2000, 2, 29)),
+        ] {
+            let time = UNIX_EPOCH + Duration::from_secs(days * DAY + 1234);
+            assert_eq!(civil(time), ymd);
+            assert_eq!(
+                midnight(ymd.0, ymd.1, ymd.2),
+                Some(time - Duration::from_secs(1234))
+            );
+        }
+        assert_eq!(midnight(1969, 12, 31), None);
+    }
+}
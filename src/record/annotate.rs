@@ -0,0 +1,163 @@
+//! Annotating recorded games with engine analysis.
+//!
+//! [`annotate`] searches every position of a [`GameRecord`] with MCTS and
+//! tags each turn with what the engine thought of it:
+//!
+//! - `eval`: win rate of the best turn for the player to move.
+//! - `played`: win rate of the turn played.
+//! - `best`: the engine's best turn.
+//! - `visits`: simulations of each turn, such as `1:120 2:60 3:20`.
+//!
+//! When the best turn was not played, its principal variation is added as a
+//! variation, so it shows up in [SGF](super::sgf) exports. The enriched
+//! record can then be written back out as usual.
+
+use super::{GameRecord, Ply};
+use crate::ai::mcts::{self, MctsConfig};
+use crate::Game;
+
+/// Engine analysis of a recorded turn.
+#[derive(Clone, Debug)]
+pub struct Analysis<G: Game> {
+    /// Player who made the turn.
+    pub player: G::Player,
+    /// Most simulated turn.
+    pub best: G::Turn,
+    /// Win rate of the best turn for the player.
+    pub eval: f64,
+    /// Win rate of the turn played for the player.
+    pub played: f64,
+    /// Principal variation, starting with the best turn.
+    pub pv: Vec<G::Turn>,
+    /// Simulations of each turn, most simulated first.
+    pub visits: Vec<(G::Turn, u32)>,
+}
+
+impl<G: Game> Analysis<G> {
+    /// Get how much lower the win rate of the turn played was than the best.
+    pub fn loss(&self) -> f64 {
+        (self.eval - self.played).max(0.)
+    }
+}
+
+/// Search every position of the main line and annotate each turn.
+///
+/// Each position is searched within the configuration's budget. Returns the
+/// analysis of each turn, stopping at the first which cannot be played.
+/// Annotating a record again replaces the old tags.
+pub fn annotate<G: Game>(record: &mut GameRecord<G>, config: &MctsConfig) -> Vec<Analysis<G>> {
+    let mut analyses = Vec::new();
+    let mut state = record.initial.clone();
+    for ply in &mut record.plies {
+        if state.over() {
+            break;
+        }
+        let (lines, _) = mcts::search(&state, config);
+        let player = state.player();
+        let name = ply.turn.to_string();
+        let mut child = state.clone();
+        if !child.play(ply.turn.clone()) {
+            break;
+        }
+        let rate = |wins: f64, sims: u32| wins / f64::from(sims.max(1));
+        let played = match lines.iter().find(|line| line.pv[0].to_string() == name) {
+            Some(line) => rate(line.wins, line.sims),
+            // Turns skipped by the search are estimated on their own
+            None => estimate(&child, &player, config),
+        };
+        let best = &lines[0];
+        let analysis = Analysis {
+            player,
+            best: best.pv[0].clone(),
+            eval: rate(best.wins, best.sims),
+            played,
+            pv: best.pv.clone(),
+            visits: lines
+                .iter()
+                .map(|line| (line.pv[0].clone(), line.sims))
+                .collect(),
+        };
+        tag(ply, &analysis);
+        analyses.push(analysis);
+        state = child;
+    }
+    analyses
+}
+
+/// Tag a turn with its analysis, adding the best line if it was not played.
+fn tag<G: Game>(ply: &mut Ply<G::Turn>, analysis: &Analysis<G>) {
+    let visits: Vec<_> = analysis
+        .visits
+        .iter()
+        .map(|(turn, sims)| format!("{turn}:{sims}"))
+        .collect();
+    ply.set_tag("eval", format!("{:.3}", analysis.eval));
+    ply.set_tag("played", format!("{:.3}", analysis.played));
+    ply.set_tag("best", analysis.best.to_string());
+    ply.set_tag("visits", visits.join(" "));
+
+    let best = analysis.best.to_string();
+    let known = ply.turn.to_string() == best
+        || ply.variations.iter().any(|line| {
+            line.first()
+                .is_some_and(|first| first.turn.to_string() == best)
+        });
+    if !known {
+        let line = analysis.pv.iter().cloned().map(Ply::new).collect();
+        ply.variations.push(line);
+    }
+}
+
+/// Estimate the win rate of a position for a player.
+fn estimate<G: Game>(game: &G, player: &G::Player, config: &MctsConfig) -> f64 {
+    if game.over() {
+        return match game.winner() {
+            Some(winner) if winner == *player => 1.,
+            Some(_) => 0.,
+            None => 0.5,
+        };
+    }
+    mcts::evaluate(game, config)
+        .into_iter()
+        .find(|(other, _)| other == player)
+        .map_or(0., |(_, rate)| rate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::mcts::Budget;
+    use crate::tests::Nim;
+
+    #[test]
+    fn annotate_tags_turns() {
+        let config = MctsConfig {
+            budget: Budget::Iterations(2000),
+            seed: Some(3),
+            ..Default::default()
+        };
+        // Taking three stones from six loses, and one from three wins
+        let mut record = GameRecord::new(Nim::new(6));
+        for turn in [3, 3] {
+            record.push(turn, None);
+        }
+        let analyses = annotate(&mut record, &config);
+        assert_eq!(analyses.len(), 2);
+        assert_eq!(analyses[0].best, 2);
+        assert!(analyses[0].loss() > 0.5);
+        assert_eq!(analyses[1].best, 3);
+        assert_eq!(analyses[1].loss(), 0.);
+
+        let first = &record.plies[0];
+        assert_eq!(first.tag("best"), Some("2"));
+        assert!(first.tag("visits").unwrap().starts_with("2:"));
+        assert_eq!(first.variations.len(), 1);
+        assert_eq!(first.variations[0][0].turn, 2);
+        assert!(record.plies[1].variations.is_empty());
+
+        // Annotating again replaces the tags
+        annotate(&mut record, &config);
+        assert_eq!(record.plies[0].tags.len(), 4);
+        assert_eq!(record.plies[0].variations.len(), 1);
+    }
+}
@@ -0,0 +1,265 @@
+//! Reports of blunders in analyzed games.
+//!
+//! Given the [`Analysis`] of each turn from [`annotate`](super::annotate),
+//! a [`BlunderReport`] flags turns whose win rate fell short of the best turn
+//! by more than a threshold, and counts each player's inaccuracies, mistakes,
+//! and blunders.
+
+use std::fmt::{self, Display};
+
+use super::annotate::Analysis;
+use super::GameRecord;
+use crate::Game;
+
+/// Win rate losses at which turns are flagged.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Thresholds {
+    /// Smallest loss flagged as an inaccuracy.
+    pub inaccuracy: f64,
+    /// Smallest loss flagged as a mistake.
+    pub mistake: f64,
+    /// Smallest loss flagged as a blunder.
+    pub blunder: f64,
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Thresholds {
+            inaccuracy: 0.05,
+            mistake: 0.1,
+            blunder: 0.2,
+        }
+    }
+}
+
+impl Thresholds {
+    /// Get the severity of a loss, if it is flagged at all.
+    pub fn classify(&self, loss: f64) -> Option<Severity> {
+        if loss > self.blunder {
+            Some(Severity::Blunder)
+        } else if loss > self.mistake {
+            Some(Severity::Mistake)
+        } else if loss > self.inaccuracy {
+            Some(Severity::Inaccuracy)
+        } else {
+            None
+        }
+    }
+}
+
+/// How bad a flagged turn was.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// A slightly worse turn.
+    Inaccuracy,
+    /// A clearly worse turn.
+    Mistake,
+    /// A turn which threw away the game.
+    Blunder,
+}
+
+impl Severity {
+    /// Get the usual annotation symbol, from `?!` to `??`.
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Severity::Inaccuracy => "?!",
+            Severity::Mistake => "?",
+            Severity::Blunder => "??",
+        }
+    }
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Severity::Inaccuracy => "inaccuracy",
+            Severity::Mistake => "mistake",
+            Severity::Blunder => "blunder",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// A turn which fell short of the best.
+#[derive(Clone, Debug)]
+pub struct Flag<G: Game> {
+    /// Ply of the turn, from 0.
+    pub ply: usize,
+    /// Player who made the turn.
+    pub player: G::Player,
+    /// Turn played.
+    pub turn: G::Turn,
+    /// Best turn instead.
+    pub best: G::Turn,
+    /// Win rate lost by the turn.
+    pub loss: f64,
+    /// How bad the turn was.
+    pub severity: Severity,
+}
+
+/// Counts of a player's flagged turns.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Summary<P> {
+    /// Player counted.
+    pub player: P,
+    /// Turns analyzed.
+    pub turns: usize,
+    /// Turns flagged as inaccuracies.
+    pub inaccuracies: usize,
+    /// Turns flagged as mistakes.
+    pub mistakes: usize,
+    /// Turns flagged as blunders.
+    pub blunders: usize,
+}
+
+/// Flagged turns of a game, with a summary for each player.
+#[derive(Clone, Debug)]
+pub struct BlunderReport<G: Game> {
+    /// Flagged turns, in order.
+    pub flags: Vec<Flag<G>>,
+    /// Summary of each player, in order of their first turn.
+    pub players: Vec<Summary<G::Player>>,
+}
+
+impl<G: Game> BlunderReport<G> {
+    /// Flag the turns of a record analyzed by
+    /// [`annotate`](super::annotate::annotate).
+    pub fn new(
+        record: &GameRecord<G>,
+        analyses: &[Analysis<G>],
+        thresholds: &Thresholds,
+    ) -> BlunderReport<G> {
+        let mut flags = Vec::new();
+        let mut players: Vec<Summary<G::Player>> = Vec::new();
+        for (ply, (analysis, turn)) in analyses.iter().zip(record.turns()).enumerate() {
+            let idx = match players.iter().position(|s| s.player == analysis.player) {
+                Some(idx) => idx,
+                None => {
+                    players.push(Summary {
+                        player: analysis.player.clone(),
+                        turns: 0,
+                        inaccuracies: 0,
+                        mistakes: 0,
+                        blunders: 0,
+                    });
+                    players.len() - 1
+                }
+            };
+            let summary = &mut players[idx];
+            summary.turns += 1;
+            let loss = analysis.loss();
+            let Some(severity) = thresholds.classify(loss) else {
+                continue;
+            };
+            *match severity {
+                Severity::Inaccuracy => &mut summary.inaccuracies,
+                Severity::Mistake => &mut summary.mistakes,
+                Severity::Blunder => &mut summary.blunders,
+            } += 1;
+            flags.push(Flag {
+                ply,
+                player: analysis.player.clone(),
+                turn: turn.clone(),
+                best: analysis.best.clone(),
+                loss,
+                severity,
+            });
+        }
+        BlunderReport { flags, players }
+    }
+}
+
+impl<G: Game> Display for BlunderReport<G> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for summary in &self.players {
+            writeln!(
+                f,
+                "{}: {} inaccuracies, {} mistakes, {} blunders in {} turns",
+                summary.player,
+                summary.inaccuracies,
+                summary.mistakes,
+                summary.blunders,
+                summary.turns,
+            )?;
+        }
+        for flag in &self.flags {
+            writeln!(
+                f,
+                "{}. {}{} ({}) {}, best {}, -{:.1}%",
+                flag.ply + 1,
+                flag.turn,
+                flag.severity.symbol(),
+                flag.player,
+                flag.severity,
+                flag.best,
+                100. * flag.loss,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::Nim;
+
+    fn analysis(player: u8, best: u32, eval: f64, played: f64) -> Analysis<Nim> {
+        Analysis {
+            player,
+            best,
+            eval,
+            played,
+            pv: vec![best],
+            visits: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn report_flags_by_threshold() {
+        let mut record = GameRecord::new(Nim::new(9));
+        for turn in [2, 3, 1, 3] {
+            record.push(turn, None);
+        }
+        let analyses = [
+            analysis(0, 1, 0.9, 0.5),
+            analysis(1, 3, 0.6, 0.6),
+            analysis(0, 3, 0.7, 0.62),
+            analysis(1, 2, 0.3, 0.22),
+        ];
+        let report = BlunderReport::new(&record, &analyses, &Thresholds::default());
+        let flags: Vec<_> = report.flags.iter().map(|f| (f.ply, f.severity)).collect();
+        assert_eq!(
+            flags,
+            [
+                (0, Severity::Blunder),
+                (2, Severity::Inaccuracy),
+                (3, Severity::Inaccuracy)
+            ]
+        );
+        assert_eq!(
+            report.players[0],
+            Summary {
+                player: 0,
+                turns: 2,
+                inaccuracies: 1,
+                mistakes: 0,
+                blunders: 1,
+            }
+        );
+        assert_eq!(report.players[1].turns, 2);
+        assert!(report
+            .to_string()
+            .contains("1. 2?? (0) blunder, best 1, -40.0%"));
+
+        // Stricter thresholds flag more
+        let strict = Thresholds {
+            inaccuracy: 0.,
+            mistake: 0.05,
+            blunder: 0.5,
+        };
+        let report = BlunderReport::new(&record, &analyses, &strict);
+        assert_eq!(report.players[1].mistakes, 1);
+        assert_eq!(report.players[0].mistakes, 2);
+    }
+}
@@ -0,0 +1,151 @@
+//! Win probabilities over the course of a game.
+//!
+//! Once a record has been [annotated](super::annotate), its `eval` tags give
+//! the engine's estimate for the player to move at each ply. A [`WinGraph`]
+//! collects these into a series for each player, ready to plot as the
+//! classic evaluation graph or to save as CSV.
+
+use std::fmt::Write;
+
+use super::{outcome, GameRecord};
+use crate::Game;
+
+/// Estimated win probability of each player at each ply.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WinGraph<P> {
+    /// Points of each player as plies and probabilities, in order of their
+    /// first turn.
+    pub series: Vec<(P, Vec<(usize, f64)>)>,
+}
+
+impl<P: Clone + PartialEq> WinGraph<P> {
+    /// Get the points of a player.
+    pub fn points(&self, player: &P) -> &[(usize, f64)] {
+        self.series
+            .iter()
+            .find(|(other, _)| other == player)
+            .map_or(&[], |(_, points)| points)
+    }
+
+    /// Add a point for a player.
+    fn push(&mut self, player: &P, ply: usize, rate: f64) {
+        match self.series.iter_mut().find(|(other, _)| other == player) {
+            Some((_, points)) => points.push((ply, rate)),
+            None => self.series.push((player.clone(), vec![(ply, rate)])),
+        }
+    }
+}
+
+impl<P: ToString> WinGraph<P> {
+    /// Format the graph as CSV, with a row for each ply and a column for each
+    /// player.
+    ///
+    /// Plies without an estimate for a player leave the cell empty.
+    pub fn to_csv(&self) -> String {
+        let mut out = String::from("ply");
+        for (player, _) in &self.series {
+            write!(out, ",{}", player.to_string()).unwrap();
+        }
+        out.push('\n');
+        let plies = self
+            .series
+            .iter()
+            .flat_map(|(_, points)| points.last())
+            .map(|(ply, _)| ply + 1)
+            .max()
+            .unwrap_or(0);
+        for ply in 0..plies {
+            write!(out, "{ply}").unwrap();
+            for (_, points) in &self.series {
+                out.push(',');
+                if let Some((_, rate)) = points.iter().find(|(at, _)| *at == ply) {
+                    write!(out, "{rate:.3}").unwrap();
+                }
+            }
+            out.push('\n');
+        }
+        out
+    }
+}
+
+impl<G: Game> GameRecord<G> {
+    /// Collect the win probabilities from the `eval` tags of each turn.
+    ///
+    /// Each estimate is for the player to move. In two-player games the
+    /// other player is given the rest. A finished game adds a final point of
+    /// 1 for the winner and 0 for everyone else, or ½ each for a draw.
+    pub fn win_graph(&self) -> WinGraph<G::Player> {
+        // Find who moves at each ply, and everyone who moves at all
+        let mut state = self.initial.clone();
+        let mut movers = Vec::new();
+        let mut players = Vec::new();
+        let mut played = 0;
+        for turn in self.turns() {
+            let player = state.player();
+            if !players.contains(&player) {
+                players.push(player.clone());
+            }
+            movers.push(player);
+            if !state.play(turn.clone()) {
+                break;
+            }
+            played += 1;
+        }
+
+        let mut graph = WinGraph { series: Vec::new() };
+        let pairs = players.len() == 2 && self.initial.players() == 2;
+        for (ply, (player, turn)) in movers.iter().zip(&self.plies).enumerate() {
+            let Some(rate) = turn.tag("eval").and_then(|eval| eval.parse::<f64>().ok()) else {
+                continue;
+            };
+            graph.push(player, ply, rate);
+            if let Some(other) = players.iter().find(|other| pairs && *other != player) {
+                graph.push(other, ply, 1. - rate);
+            }
+        }
+        // Games which end early leave the final position unfinished
+        let ended = self
+            .result
+            .clone()
+            .filter(|result| result.termination().is_some());
+        let result = outcome(&state).or(ended);
+        if let Some(result) = result.filter(|_| played == self.len()) {
+            for player in &players {
+                let rate = match result.winner() {
+                    Some(winner) if winner == player => 1.,
+                    Some(_) => 0.,
+                    None => 0.5,
+                };
+                graph.push(player, self.len(), rate);
+            }
+        }
+        graph
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::Nim;
+
+    #[test]
+    fn graph_follows_evals() {
+        let mut record = GameRecord::new(Nim::new(5));
+        for (turn, eval) in [(1, "0.75"), (2, "0.25"), (1, "")] {
+            record.push(turn, None);
+            let ply = record.plies.last_mut().unwrap();
+            if !eval.is_empty() {
+                ply.set_tag("eval", eval.to_string());
+            }
+        }
+        record.push(1, None);
+        let graph = record.win_graph();
+        // The second player takes the last stone
+        assert_eq!(graph.points(&0), [(0, 0.75), (1, 0.75), (4, 0.)]);
+        assert_eq!(graph.points(&1), [(0, 0.25), (1, 0.25), (4, 1.)]);
+        assert_eq!(
+            graph.to_csv(),
+            "ply,0,1\n0,0.750,0.250\n1,0.750,0.250\n2,,\n3,,\n4,0.000,1.000\n"
+        );
+    }
+}
@@ -0,0 +1,250 @@
+//! Writing records in a PGN-like notation.
+//!
+//! Like chess's Portable Game Notation, a record is written as a few headers
+//! followed by its numbered turns and result:
+//!
+//! ```text
+//! [Player1 "mcts"]
+//! [Player2 "human"]
+//! [Date "2023.11.14"]
+//! [Result "1"]
+//!
+//! 1. -2 -3 1
+//! ```
+//!
+//! Turns are written with the game's [`Notation`] and numbered once per round
+//! of play. The result is the winner as displayed, `1/2-1/2` for a draw, or
+//! `*` for an unfinished game, and a game which ended before it was over,
+//! such as by resignation, also has a `Termination` header. Thinking times,
+//! tags, and variations are not written.
+
+use std::time::SystemTime;
+
+use super::{civil, midnight, outcome, Error, GameRecord, Outcome, Ply};
+use crate::Notation;
+
+/// Result of a drawn game.
+const DRAW: &str = "1/2-1/2";
+/// Result of an unfinished game.
+const UNFINISHED: &str = "*";
+/// Widest line of turns written.
+const WIDTH: usize = 80;
+
+impl<G: Notation> GameRecord<G> {
+    /// Format the record in a PGN-like notation.
+    ///
+    /// The turns are cut short at the first illegal one.
+    pub fn to_pgn(&self) -> String {
+        let mut out = String::new();
+        for (idx, name) in self.players.iter().enumerate() {
+            header(&mut out, &format!("Player{}", idx + 1), name);
+        }
+        if let Some(started) = self.started {
+            header(&mut out, "Date", &date(started));
+        }
+        let result = match &self.result {
+            Some(result) => match result.winner() {
+                Some(player) => player.to_string(),
+                None => DRAW.to_string(),
+            },
+            None => UNFINISHED.to_string(),
+        };
+        header(&mut out, "Result", &result);
+        if let Some(termination) = self.result.as_ref().and_then(Outcome::termination) {
+            header(&mut out, "Termination", termination);
+        }
+        out.push('\n');
+
+        // Number each round, in which every player moves once
+        let round = self.initial.players().max(1);
+        let mut tokens = Vec::new();
+        let mut state = self.initial.clone();
+        for (idx, turn) in self.turns().enumerate() {
+            let text = state.write_turn(turn);
+            if !state.play(turn.clone()) {
+                break;
+            }
+            if idx % round == 0 {
+                tokens.push(format!("{}.", idx / round + 1));
+            }
+            tokens.push(text);
+        }
+        tokens.push(result);
+
+        let mut line = String::new();
+        for token in tokens {
+            if !line.is_empty() && line.len() + 1 + token.len() > WIDTH {
+                out.push_str(&line);
+                out.push('\n');
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&token);
+        }
+        out.push_str(&line);
+        out.push('\n');
+        out
+    }
+
+    /// Read a record written by [`to_pgn`](GameRecord::to_pgn), played from
+    /// `initial`.
+    ///
+    /// Unknown headers are ignored. As with JSON, every turn is replayed and
+    /// the result is taken from the final position, unless the game ended
+    /// before it was over.
+    pub fn from_pgn(initial: G, s: &str) -> Result<GameRecord<G>, Error> {
+        let mut record = GameRecord::new(initial);
+        let mut players = Vec::new();
+        let mut result = None;
+        let mut termination = None;
+        let mut lines = s.lines().map(str::trim).peekable();
+        while let Some(line) = lines.next_if(|line| line.is_empty() || line.starts_with('[')) {
+            if line.is_empty() {
+                continue;
+            }
+            let (key, value) =
+                tag(line).ok_or_else(|| Error::Pgn(format!("invalid header {line}")))?;
+            match key {
+                "Date" => record.started = parse_date(&value),
+                "Result" => result = Some(value),
+                "Termination" => termination = Some(value),
+                _ => {
+                    if let Some(seat) = key.strip_prefix("Player") {
+                        let seat: usize = seat
+                            .parse()
+                            .map_err(|_| Error::Pgn(format!("invalid header {key}")))?;
+                        players.push((seat, value));
+                    }
+                }
+            }
+        }
+        players.sort_by_key(|(seat, _)| *seat);
+        record.players = players.into_iter().map(|(_, name)| name).collect();
+
+        // Drop the result which ends the turns
+        let mut tokens: Vec<_> = lines.flat_map(str::split_whitespace).collect();
+        if let Some(last) = tokens.last() {
+            if *last == UNFINISHED || Some(*last) == result.as_deref() {
+                tokens.pop();
+            }
+        }
+        let mut state = record.initial.clone();
+        for token in tokens.into_iter().filter(|token| !numbered(token)) {
+            let ply = record.len();
+            let turn = state
+                .parse_turn(token)
+                .map_err(|err| Error::Turn(ply, err))?;
+            if !state.play(turn.clone()) {
+                return Err(Error::Illegal(ply));
+            }
+            record.plies.push(Ply::new(turn));
+        }
+        record.result = outcome(&state);
+        if let Some(termination) = termination.filter(|_| record.result.is_none()) {
+            let winner = result.filter(|result| result != DRAW && result != UNFINISHED);
+            record
+                .terminate(&termination, winner.as_deref())
+                .ok_or_else(|| Error::Pgn(format!("invalid termination {termination}")))?;
+        }
+        Ok(record)
+    }
+}
+
+/// Write a header line, escaping its value.
+fn header(out: &mut String, key: &str, value: &str) {
+    let value = value.replace('\\', r"\\").replace('"', r#"\""#);
+    out.push_str(&format!("[{key} \"{value}\"]\n"));
+}
+
+/// Read a header line as its key and unescaped value.
+fn tag(line: &str) -> Option<(&str, String)> {
+    let inner = line.strip_prefix('[')?.strip_suffix(']')?;
+    let (key, value) = inner.split_once(char::is_whitespace)?;
+    let value = value.trim().strip_prefix('"')?.strip_suffix('"')?;
+    let mut out = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        out.push(if c == '\\' { chars.next()? } else { c });
+    }
+    Some((key, out))
+}
+
+/// Check if a token is a round number, such as `12.`.
+fn numbered(token: &str) -> bool {
+    let digits = token.trim_end_matches('.');
+    digits.len() < token.len() && !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Format the day of a time as `YYYY.MM.DD`, in UTC.
+fn date(time: SystemTime) -> String {
+    let (year, month, day) = civil(time);
+    format!("{year:04}.{month:02}.{day:02}")
+}
+
+/// Read a date written as `YYYY.MM.DD` as midnight of that day, in UTC.
+///
+/// Returns `None` for unknown dates, such as `????.??.??`.
+fn parse_date(s: &str) -> Option<SystemTime> {
+    let mut parts = s.splitn(3, '.');
+    let year = parts.next()?.parse().ok()?;
+    let month = parts
+        .next()?
+        .parse()
+        .ok()
+        .filter(|m| (1..=12).contains(m))?;
+    let day = parts
+        .next()?
+        .parse()
+        .ok()
+        .filter(|d| (1..=31).contains(d))?;
+    midnight(year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::*;
+    use crate::tests::Nim;
+    use crate::TurnError;
+
+    #[test]
+    fn pgn_round_trip() {
+        let mut record = GameRecord::new(Nim::new(10));
+        record.players = vec!["mcts".to_string(), "a \"b\"".to_string()];
+        record.started = Some(UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+        for turn in [2, 3, 1, 3, 1] {
+            record.push(turn, None);
+        }
+        record.finish();
+
+        let pgn = record.to_pgn();
+        assert_eq!(
+            pgn,
+            "[Player1 \"mcts\"]\n[Player2 \"a \\\"b\\\"\"]\n[Date \"2023.11.14\"]\n\
+             [Result \"0\"]\n\n1. -2 -3 2. -1 -3 3. -1 0\n"
+        );
+        let read = GameRecord::from_pgn(Nim::new(10), &pgn).unwrap();
+        assert_eq!(read.players, record.players);
+        assert_eq!(read.plies, record.plies);
+        assert_eq!(read.result, Some(Outcome::Win(0)));
+        assert_eq!(read.started.map(date), Some("2023.11.14".to_string()));
+
+        // Unfinished games end with an asterisk
+        record.plies.truncate(2);
+        record.finish();
+        assert!(record.to_pgn().ends_with("1. -2 -3 *\n"));
+        assert!(matches!(
+            GameRecord::from_pgn(Nim::new(4), &record.to_pgn()),
+            Err(Error::Turn(1, TurnError::Illegal(_)))
+        ));
+        assert!(matches!(
+            GameRecord::from_pgn(Nim::new(10), "1. -2 x *"),
+            Err(Error::Turn(1, TurnError::Invalid(_)))
+        ));
+        assert!(GameRecord::from_pgn(Nim::new(10), "[Result]\n").is_err());
+        assert_eq!(parse_date("????.??.??"), None);
+    }
+}
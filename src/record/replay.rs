@@ -0,0 +1,246 @@
+//! Stepping through recorded games.
+//!
+//! A [`Replay`] walks a [`GameRecord`] forward and backward one ply at a
+//! time, exposing the position at any ply. Playing a different turn from any
+//! point branches a new main line, keeping the old one as a variation, so a
+//! review can explore alternatives without losing the game as played.
+
+use std::mem;
+
+use super::{outcome, Error, GameRecord, Ply};
+use crate::Game;
+
+/// A cursor over the positions of a recorded game.
+#[derive(Clone, Debug)]
+pub struct Replay<G: Game> {
+    record: GameRecord<G>,
+    // Position after each ply of the main line, starting from the initial
+    states: Vec<G>,
+    ply: usize,
+}
+
+impl<G: Game> Replay<G> {
+    /// Create a new Replay at the start of a record.
+    ///
+    /// Fails with [`Error::Illegal`] if a turn of the main line cannot be
+    /// played.
+    pub fn new(record: GameRecord<G>) -> Result<Replay<G>, Error> {
+        let mut states = vec![record.initial.clone()];
+        for (ply, turn) in record.turns().enumerate() {
+            let mut state = states[ply].clone();
+            if !state.play(turn.clone()) {
+                return Err(Error::Illegal(ply));
+            }
+            states.push(state);
+        }
+        Ok(Replay {
+            record,
+            states,
+            ply: 0,
+        })
+    }
+
+    /// Get the record being replayed.
+    pub fn record(&self) -> &GameRecord<G> {
+        &self.record
+    }
+
+    /// Take back the record, with any branches played.
+    pub fn into_record(self) -> GameRecord<G> {
+        self.record
+    }
+
+    /// Get the number of plies since the start.
+    pub fn ply(&self) -> usize {
+        self.ply
+    }
+
+    /// Get the number of plies in the main line.
+    pub fn len(&self) -> usize {
+        self.record.len()
+    }
+
+    /// Check if the main line has no turns.
+    pub fn is_empty(&self) -> bool {
+        self.record.is_empty()
+    }
+
+    /// Get the current position.
+    pub fn state(&self) -> &G {
+        &self.states[self.ply]
+    }
+
+    /// Get the position after `ply` plies of the main line.
+    pub fn state_at(&self, ply: usize) -> Option<&G> {
+        self.states.get(ply)
+    }
+
+    /// Get the next turn of the main line, if any.
+    pub fn next(&self) -> Option<&Ply<G::Turn>> {
+        self.record.plies.get(self.ply)
+    }
+
+    /// Get the alternatives to the next turn, each as a line of turns.
+    pub fn variations(&self) -> &[Vec<Ply<G::Turn>>] {
+        self.next().map_or(&[], |ply| &ply.variations)
+    }
+
+    /// Step forward a ply, returning whether there was one.
+    pub fn forward(&mut self) -> bool {
+        self.seek(self.ply + 1)
+    }
+
+    /// Step back a ply, returning whether there was one.
+    pub fn back(&mut self) -> bool {
+        self.ply.checked_sub(1).is_some_and(|ply| self.seek(ply))
+    }
+
+    /// Jump to a ply of the main line, returning whether it exists.
+    pub fn seek(&mut self, ply: usize) -> bool {
+        let exists = ply <= self.len();
+        if exists {
+            self.ply = ply;
+        }
+        exists
+    }
+
+    /// Play a turn from the current position, moving forward a ply.
+    ///
+    /// If the turn is not the next one, it starts a new main line, and the
+    /// old continuation is kept as a variation. Playing the first turn of an
+    /// existing variation promotes it instead. Returns `false`, leaving the
+    /// replay untouched, if the turn cannot be played.
+    pub fn branch(&mut self, turn: G::Turn) -> bool {
+        let name = turn.to_string();
+        let same = |ply: &Ply<G::Turn>| ply.turn.to_string() == name;
+        if self.next().is_some_and(same) {
+            return self.forward();
+        }
+        if let Some(idx) = self
+            .variations()
+            .iter()
+            .position(|line| line.first().is_some_and(same))
+        {
+            return self.promote(idx) && self.forward();
+        }
+        if !self.state().clone().play(turn.clone()) {
+            return false;
+        }
+        match self.record.plies.get_mut(self.ply) {
+            Some(next) => {
+                next.variations.push(vec![Ply::new(turn)]);
+                let idx = next.variations.len() - 1;
+                self.promote(idx);
+            }
+            None => {
+                self.record.plies.push(Ply::new(turn));
+                self.replay();
+            }
+        }
+        self.forward()
+    }
+
+    /// Swap a variation of the next turn into the main line, keeping the old
+    /// continuation as a variation in its place.
+    ///
+    /// Returns `false`, leaving the replay untouched, if there is no such
+    /// variation or one of its turns cannot be played.
+    pub fn promote(&mut self, idx: usize) -> bool {
+        let Some(line) = self.variations().get(idx).filter(|line| !line.is_empty()) else {
+            return false;
+        };
+        let mut state = self.state().clone();
+        if !line.iter().all(|ply| state.play(ply.turn.clone())) {
+            return false;
+        }
+
+        let mut main = self.record.plies.split_off(self.ply);
+        let mut variations = mem::take(&mut main[0].variations);
+        let mut line = mem::replace(&mut variations[idx], main);
+        // Alternatives to the next turn stay with whichever line is main
+        variations.append(&mut line[0].variations);
+        line[0].variations = variations;
+        self.record.plies.append(&mut line);
+        self.replay();
+        true
+    }
+
+    /// Recompute the positions after the current ply, and the result.
+    fn replay(&mut self) {
+        self.states.truncate(self.ply + 1);
+        for turn in self.record.plies[self.ply..].iter().map(|ply| &ply.turn) {
+            let mut state = self.states[self.states.len() - 1].clone();
+            state.play(turn.clone());
+            self.states.push(state);
+        }
+        self.record.result = outcome(&self.states[self.states.len() - 1]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::Nim;
+
+    fn turns(plies: &[Ply<u32>]) -> Vec<u32> {
+        plies.iter().map(|ply| ply.turn).collect()
+    }
+
+    #[test]
+    fn replay_steps_through_positions() {
+        let mut record = GameRecord::new(Nim::new(6));
+        for turn in [2, 3, 1] {
+            record.push(turn, None);
+        }
+        let mut replay = Replay::new(record.clone()).unwrap();
+        assert_eq!(replay.state().stones, 6);
+        assert!(!replay.back());
+        assert!(replay.forward() && replay.forward());
+        assert_eq!(replay.state().stones, 1);
+        assert_eq!(replay.next().map(|ply| ply.turn), Some(1));
+        assert!(replay.seek(3) && !replay.forward());
+        assert!(replay.state().over());
+        assert_eq!(replay.state_at(1).map(|state| state.stones), Some(4));
+        assert_eq!(replay.state_at(4), None);
+
+        record.push(3, None);
+        assert_eq!(Replay::new(record).err(), Some(Error::Illegal(3)));
+    }
+
+    #[test]
+    fn branch_keeps_old_line() {
+        let mut record = GameRecord::new(Nim::new(6));
+        for turn in [2, 3, 1] {
+            record.push(turn, None);
+        }
+        record.finish();
+        let mut replay = Replay::new(record).unwrap();
+        replay.seek(1);
+        // Taking four stones is illegal
+        assert!(!replay.branch(4));
+        assert!(replay.branch(1));
+        assert_eq!(replay.ply(), 2);
+        assert_eq!(turns(&replay.record().plies), [2, 1]);
+        assert_eq!(replay.record().result, None);
+
+        replay.back();
+        assert_eq!(replay.variations().len(), 1);
+        assert_eq!(turns(&replay.variations()[0]), [3, 1]);
+        // Replaying the old turn promotes the old line back
+        assert!(replay.branch(3));
+        assert_eq!(turns(&replay.record().plies), [2, 3, 1]);
+        assert_eq!(replay.state().stones, 1);
+        replay.back();
+        assert_eq!(turns(&replay.variations()[0]), [1]);
+
+        // Branching from the end extends the main line
+        replay.seek(1);
+        replay.branch(2);
+        assert_eq!(replay.variations(), &[] as &[Vec<Ply<u32>>]);
+        assert!(replay.branch(2));
+        assert!(replay.state().over());
+        assert_eq!(turns(&replay.record().plies), [2, 2, 2]);
+        assert_eq!(replay.record().plies[1].variations.len(), 2);
+        assert!(replay.record().result.is_some());
+    }
+}
@@ -0,0 +1,167 @@
+//! Writing records in the Smart Game Format.
+//!
+//! [SGF](https://www.red-bean.com/sgf/) is the standard format for Go and
+//! related games, and most viewers can browse the variations it holds.
+//! Records of any [`TurnCoords`] game can be written, with each turn as the
+//! cell it is played on:
+//!
+//! ```text
+//! (;FF[4]GM[1]SZ[3:1]PB[mcts]PW[human]DT[2023-11-14]RE[W+]
+//! (;B[ba];W[ca]C[eval: 0.9])(;B[aa]))
+//! ```
+//!
+//! The player to move first plays black, and the other white. Turns without a
+//! cell, such as passes, are written as empty moves, tags as comments, and
+//! variations as branches beside the turn they replace.
+
+use super::{civil, GameRecord, Outcome, Ply};
+use crate::TurnCoords;
+
+impl<G: TurnCoords> GameRecord<G> {
+    /// Format the record as SGF.
+    ///
+    /// Each line is cut short at its first illegal turn.
+    pub fn to_sgf(&self) -> String {
+        let mut out = String::from("(;FF[4]GM[1]");
+        let size = match self.initial.dimensions() {
+            (rows, cols) if rows == cols => rows.to_string(),
+            (rows, cols) => format!("{cols}:{rows}"),
+        };
+        property(&mut out, "SZ", &size);
+        for (key, name) in ["PB", "PW"].into_iter().zip(&self.players) {
+            property(&mut out, key, name);
+        }
+        if let Some(started) = self.started {
+            let (year, month, day) = civil(started);
+            property(&mut out, "DT", &format!("{year:04}-{month:02}-{day:02}"));
+        }
+        let black = self.initial.player();
+        if let Some(result) = &self.result {
+            let reason = match result {
+                Outcome::Resignation(_) => "R",
+                Outcome::Timeout(_) => "T",
+                _ => "",
+            };
+            let result = match result.winner() {
+                Some(player) if *player == black => format!("B+{reason}"),
+                Some(_) => format!("W+{reason}"),
+                None => "0".to_string(),
+            };
+            property(&mut out, "RE", &result);
+        }
+        line(&mut out, &self.initial, &self.plies, &black);
+        out.push_str(")\n");
+        out
+    }
+}
+
+/// Write a line of turns from `state`, branching at the first variation.
+fn line<G: TurnCoords>(out: &mut String, state: &G, plies: &[Ply<G::Turn>], black: &G::Player) {
+    let Some((ply, rest)) = plies.split_first() else {
+        return;
+    };
+    if ply.variations.is_empty() {
+        return node(out, state, ply, rest, black);
+    }
+    // Branch into the main line and each variation
+    let mut branches = vec![(ply, rest)];
+    alternatives(ply, &mut branches);
+    for (ply, rest) in branches {
+        let mut branch = String::new();
+        node(&mut branch, state, ply, rest, black);
+        if !branch.is_empty() {
+            out.push('(');
+            out.push_str(&branch);
+            out.push(')');
+        }
+    }
+}
+
+/// Collect the variations of a turn, along with those of their own first
+/// turns, which are played from the same position.
+fn alternatives<'a, T>(ply: &'a Ply<T>, branches: &mut Vec<(&'a Ply<T>, &'a [Ply<T>])>) {
+    for (first, rest) in ply.variations.iter().filter_map(|line| line.split_first()) {
+        branches.push((first, rest));
+        alternatives(first, branches);
+    }
+}
+
+/// Write a turn from `state`, followed by the rest of its line.
+fn node<G: TurnCoords>(
+    out: &mut String,
+    state: &G,
+    ply: &Ply<G::Turn>,
+    rest: &[Ply<G::Turn>],
+    black: &G::Player,
+) {
+    let key = if state.player() == *black { "B" } else { "W" };
+    let cell = state
+        .coords(&ply.turn)
+        .map(|(row, col)| format!("{}{}", letter(col), letter(row)))
+        .unwrap_or_default();
+    let mut state = state.clone();
+    if !state.play(ply.turn.clone()) {
+        return;
+    }
+    out.push(';');
+    property(out, key, &cell);
+    if !ply.tags.is_empty() {
+        let comment: Vec<_> = ply
+            .tags
+            .iter()
+            .map(|(key, value)| format!("{key}: {value}"))
+            .collect();
+        property(out, "C", &comment.join("\n"));
+    }
+    line(out, &state, rest, black);
+}
+
+/// Write a property, escaping its value.
+fn property(out: &mut String, key: &str, value: &str) {
+    let value = value.replace('\\', r"\\").replace(']', r"\]");
+    out.push_str(&format!("{key}[{value}]"));
+}
+
+/// Get the letter of a row or column, from `a` to `z` then `A` to `Z`.
+fn letter(idx: usize) -> char {
+    match idx {
+        0..26 => char::from(b'a' + idx as u8),
+        _ => char::from(b'A' + (idx - 26).min(25) as u8),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::*;
+    use crate::tests::Nim;
+
+    #[test]
+    fn sgf_writes_variations() {
+        let mut record = GameRecord::new(Nim::new(5));
+        record.players = vec!["mcts".to_string(), "[human]".to_string()];
+        record.started = Some(UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+        record.push(2, None);
+        record.push(3, None);
+        record.plies[1]
+            .tags
+            .push(("eval".to_string(), "0.9".to_string()));
+        record.finish();
+        assert_eq!(
+            record.to_sgf(),
+            "(;FF[4]GM[1]SZ[3:1]PB[mcts]PW[[human\\]]DT[2023-11-14]RE[W+];B[ba];W[ca]C[eval: 0.9])\n"
+        );
+
+        // Variations branch beside the turn they replace, and illegal ones
+        // are dropped
+        let mut alternative = Ply::new(1);
+        alternative.variations.push(vec![Ply::new(2), Ply::new(1)]);
+        record.plies[0].variations = vec![vec![Ply::new(1)], vec![Ply::new(9)]];
+        record.plies[1].variations = vec![vec![alternative]];
+        record.plies[1].tags.clear();
+        assert!(record
+            .to_sgf()
+            .ends_with("RE[W+](;B[ba](;W[ca])(;W[aa])(;W[ba];B[aa]))(;B[aa]))\n"));
+    }
+}
@@ -0,0 +1,660 @@
+//! Games driven one step at a time.
+//!
+//! [`Game::main`] blocks until the game is over, which suits a terminal but
+//! not a frontend with its own event loop. A [`Session`] plays the same game
+//! one step at a time instead: each frame, the frontend calls
+//! [`Session::poll`] until it returns `None`, redrawing on each [`Event`],
+//! and hands over turns chosen by its users with [`Session::submit_turn`].
+//!
+//! Whichever way a game is played, [`Observer`]s added to its
+//! [`Config`](crate::Config::observe) see the same events, so spectators and
+//! recorders can follow along without involving the players. For debugging,
+//! a [`Pause`] added to a config holds the game before each turn until its
+//! [`Stepper`] signals to go on, and shows the position it is held at.
+//!
+//! ```
+//! # use gamesweet::session::{Event, Session};
+//! # fn frame<G: gamesweet::Game>(session: &mut Session<G>) {
+//! while let Some(event) = session.poll() {
+//!     match event {
+//!         Event::TurnStarted(player) => println!("{player} to move"),
+//!         Event::GameOver(_) => println!("{}", session.game()),
+//!         _ => (),
+//!     }
+//! }
+//! # }
+//! ```
+
+use std::collections::VecDeque;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use log::error;
+
+use crate::clock::Clock;
+use crate::{Config, Context, Decision, Game, Reversible};
+
+/// Something which happened during a session.
+#[derive(Clone, Debug)]
+pub enum Event<G: Game> {
+    /// A player's turn started, and their clock is running.
+    TurnStarted(G::Player),
+    /// A player played a turn, taking `elapsed` to choose it.
+    TurnPlayed {
+        player: G::Player,
+        turn: G::Turn,
+        elapsed: Duration,
+    },
+    /// A player tried to play an illegal turn, and must choose another.
+    IllegalTurn { player: G::Player, turn: G::Turn },
+    /// A turn a player played was taken back.
+    TakenBack { player: G::Player, turn: G::Turn },
+    /// A player's clock fell below a low-time warning with the turn they
+    /// played, leaving them `remaining`.
+    LowTime {
+        player: G::Player,
+        remaining: Duration,
+    },
+    /// A player offered a draw with the turn they played.
+    DrawOffered(G::Player),
+    /// The players agreed to a draw.
+    DrawAgreed,
+    /// A player resigned.
+    Resigned(G::Player),
+    /// A player ran out of time.
+    TimeForfeit(G::Player),
+    /// The game is over, with the winner if any.
+    GameOver(Option<G::Player>),
+}
+
+/// Something which watches a game as it is played.
+///
+/// Implemented for closures, and for channel senders, which pass each
+/// position and event to a receiver elsewhere, such as a thread streaming
+/// them to spectators. Events sent after the receiver is dropped are
+/// discarded.
+pub trait Observer<G: Game> {
+    /// See an event, along with the position after it.
+    fn observe(&mut self, game: &G, event: &Event<G>);
+}
+
+impl<G, F> Observer<G> for F
+where
+    G: Game,
+    F: FnMut(&G, &Event<G>),
+{
+    fn observe(&mut self, game: &G, event: &Event<G>) {
+        self(game, event);
+    }
+}
+
+impl<G: Game> Observer<G> for Sender<(G, Event<G>)> {
+    fn observe(&mut self, game: &G, event: &Event<G>) {
+        let _ = self.send((game.clone(), event.clone()));
+    }
+}
+
+/// A game played one step at a time.
+///
+/// Players are seated as in a [`Config`]. Turns for players marked as
+/// [`external`](Session::external) are submitted by the caller, and their
+/// turn functions are never called. Every other player's turn is chosen by
+/// its turn function, or [decided](Config::decider), when the session is
+/// polled. The config's hooks are called as they would be by [`Game::main`].
+pub struct Session<G: Game> {
+    game: G,
+    config: Config<G>,
+    external: Vec<G::Player>,
+    history: Vec<(G::Player, G::Turn)>,
+    events: VecDeque<Event<G>>,
+    start: Option<Instant>,
+    offered: bool,
+    ended: bool,
+}
+
+impl<G: Game> Session<G> {
+    /// Create a new Session.
+    pub fn new(game: G, mut config: Config<G>) -> Session<G> {
+        let mut events = VecDeque::new();
+        if game.over() {
+            config.finished(&game, game.winner());
+            events.push_back(Event::GameOver(game.winner()));
+        }
+        Session {
+            game,
+            config,
+            external: Vec::new(),
+            history: Vec::new(),
+            events,
+            start: None,
+            offered: false,
+            ended: false,
+        }
+    }
+
+    /// Have the caller submit the turns of a player.
+    pub fn external(mut self, player: G::Player) -> Session<G> {
+        self.external.push(player);
+        self
+    }
+
+    /// Get the current position.
+    pub fn game(&self) -> &G {
+        &self.game
+    }
+
+    /// Get the clock, if enabled.
+    pub fn clock(&self) -> Option<&Clock> {
+        self.config.clock.as_ref()
+    }
+
+    /// Check if the game is over, or ended by resignation or agreement.
+    pub fn over(&self) -> bool {
+        self.ended || self.game.over()
+    }
+
+    /// Check if the current player's opponent offered a draw with their last
+    /// turn.
+    pub fn offered(&self) -> bool {
+        self.offered
+    }
+
+    /// Get the context in which the current player decides their turn.
+    pub fn context(&self) -> Context {
+        self.config.context(&self.game, self.offered)
+    }
+
+    /// Check if the session is waiting for the caller to submit a turn.
+    pub fn waiting(&self) -> bool {
+        self.events.is_empty()
+            && self.start.is_some()
+            && self.external.contains(&self.game.player())
+    }
+
+    /// Advance the session a step, returning what happened.
+    ///
+    /// Queued events are returned first. Otherwise the current player's turn
+    /// is started, or, if it has already started and the player is not
+    /// external, chosen by their turn function. This blocks for as long as
+    /// the turn function does. Returns `None` once the game is over, or while
+    /// waiting for the caller to submit a turn.
+    pub fn poll(&mut self) -> Option<Event<G>> {
+        if let Some(event) = self.events.pop_front() {
+            return Some(event);
+        }
+        if self.over() || self.waiting() {
+            return None;
+        }
+        if self.start.is_none() {
+            return Some(self.begin());
+        }
+        match self.config.decide(&self.game, &self.context()) {
+            Decision::Play(turn) => self.submit_turn(turn),
+            Decision::OfferDraw(turn) => self.offer_draw(turn),
+            // Accepting a draw which was not offered resigns
+            Decision::AcceptDraw if !self.offered => self.resign(),
+            Decision::AcceptDraw => self.accept_draw(),
+            Decision::Resign => self.resign(),
+        };
+        self.events.pop_front()
+    }
+
+    /// Play a turn for the current player, returning whether it was legal.
+    ///
+    /// The resulting events are queued to be returned by
+    /// [`poll`](Session::poll). Illegal turns leave the clock running, and a
+    /// turn which runs out the clock forfeits the game once played.
+    pub fn submit_turn(&mut self, turn: G::Turn) -> bool {
+        self.play(turn, false)
+    }
+
+    /// Play a turn for the current player and offer a draw with it,
+    /// returning whether it was legal.
+    pub fn offer_draw(&mut self, turn: G::Turn) -> bool {
+        self.play(turn, true)
+    }
+
+    /// Accept the draw offered by the opponent, returning whether one was
+    /// offered.
+    pub fn accept_draw(&mut self) -> bool {
+        if self.over() || !self.offered {
+            error!("no draw was offered");
+            return false;
+        }
+        self.config.agreed(&self.game);
+        self.ended = true;
+        self.events.push_back(Event::DrawAgreed);
+        self.events.push_back(Event::GameOver(None));
+        true
+    }
+
+    /// Play a turn for the current player, which `offers` a draw or not.
+    fn play(&mut self, turn: G::Turn, offers: bool) -> bool {
+        if self.over() {
+            return false;
+        }
+        if self.start.is_none() {
+            let event = self.begin();
+            self.events.push_back(event);
+        }
+        let player = self.game.player();
+        let seat = self.config.seat(&self.game);
+        if !self.game.play(turn.clone()) {
+            self.config.illegal(&self.game, &turn);
+            self.events.push_back(Event::IllegalTurn { player, turn });
+            return false;
+        }
+        let elapsed = self
+            .start
+            .take()
+            .map_or(Duration::ZERO, |start| start.elapsed());
+        self.config
+            .played(&self.game, player.clone(), &turn, elapsed);
+        self.history.push((player.clone(), turn.clone()));
+        self.events.push_back(Event::TurnPlayed {
+            player: player.clone(),
+            turn,
+            elapsed,
+        });
+        if let Some(remaining) = self.config.punch(&self.game, seat, elapsed) {
+            self.events.push_back(Event::LowTime {
+                player: player.clone(),
+                remaining,
+            });
+        }
+        if offers {
+            self.config.offered(&self.game, player.clone());
+            self.events.push_back(Event::DrawOffered(player.clone()));
+        }
+        self.offered = offers;
+        if let Some(ending) = self.config.timed_out(&self.game, seat) {
+            self.ended = true;
+            self.events.push_back(Event::TimeForfeit(player));
+            self.events.push_back(Event::GameOver(ending.winner()));
+        } else if self.game.over() {
+            let winner = self.game.winner();
+            self.config.finished(&self.game, winner.clone());
+            self.events.push_back(Event::GameOver(winner));
+        }
+        true
+    }
+
+    /// Resign for the current player, returning whether the game was still
+    /// being played.
+    pub fn resign(&mut self) -> bool {
+        if self.over() {
+            return false;
+        }
+        let player = self.game.player();
+        let winner = self.config.resigned(&self.game);
+        self.ended = true;
+        self.events.push_back(Event::Resigned(player));
+        self.events.push_back(Event::GameOver(Some(winner)));
+        true
+    }
+
+    /// Start the current player's turn.
+    fn begin(&mut self) -> Event<G> {
+        self.config.started(&self.game);
+        self.start = Some(Instant::now());
+        Event::TurnStarted(self.game.player())
+    }
+}
+
+impl<G: Reversible> Session<G> {
+    /// Take back the last turn of `player`, and every turn played since,
+    /// returning whether there was one to take back.
+    ///
+    /// Each turn taken back is queued as an event. Clocks are not wound back,
+    /// and if the player to move changes their turn starts afresh. Games
+    /// which are over cannot be taken back.
+    pub fn take_back(&mut self, player: &G::Player) -> bool {
+        if self.over() {
+            return false;
+        }
+        let Some(idx) = self.history.iter().rposition(|(by, _)| by == player) else {
+            return false;
+        };
+        let mover = self.game.player();
+        for (player, turn) in self.history.drain(idx..).rev() {
+            self.game.unplay(&turn);
+            self.config.taken_back(&self.game, player.clone(), &turn);
+            self.events.push_back(Event::TakenBack { player, turn });
+        }
+        self.offered = false;
+        if self.game.player() != mover {
+            self.start = None;
+        }
+        true
+    }
+}
+
+/// Create a linked [`Stepper`] and [`Pause`], starting out paused.
+pub fn stepper<G: Game>() -> (Stepper<G>, Pause<G>) {
+    let (signals, receiver) = mpsc::channel();
+    let held = Arc::new(Mutex::new(Held {
+        ply: 0,
+        game: None,
+        waiting: false,
+    }));
+    let stepper = Stepper {
+        signals,
+        held: Arc::clone(&held),
+    };
+    let pause = Pause {
+        signals: receiver,
+        held,
+        stop: Some(0),
+    };
+    (stepper, pause)
+}
+
+/// A signal from a [`Stepper`].
+enum Signal {
+    Step,
+    Pause,
+    Resume,
+    RunTo(usize),
+}
+
+/// Where a paused game is held.
+struct Held<G> {
+    /// Turns started so far.
+    ply: usize,
+    /// Position the latest turn started from.
+    game: Option<G>,
+    /// Whether the game is waiting for a signal.
+    waiting: bool,
+}
+
+/// Controls a game held by a [`Pause`], usually from another thread.
+///
+/// Dropping the stepper lets the game run freely.
+pub struct Stepper<G> {
+    signals: Sender<Signal>,
+    held: Arc<Mutex<Held<G>>>,
+}
+
+impl<G: Game> Stepper<G> {
+    /// Let the game play one turn, then pause again.
+    pub fn step(&self) {
+        let _ = self.signals.send(Signal::Step);
+    }
+
+    /// Pause the game before its next turn.
+    pub fn pause(&self) {
+        let _ = self.signals.send(Signal::Pause);
+    }
+
+    /// Let the game run freely.
+    pub fn resume(&self) {
+        let _ = self.signals.send(Signal::Resume);
+    }
+
+    /// Let the game run until `ply` turns have been played, then pause.
+    pub fn run_to(&self, ply: usize) {
+        let _ = self.signals.send(Signal::RunTo(ply));
+    }
+
+    /// Get the position the latest turn started from.
+    pub fn state(&self) -> Option<G> {
+        self.held.lock().unwrap().game.clone()
+    }
+
+    /// Get the number of turns started so far.
+    pub fn ply(&self) -> usize {
+        self.held.lock().unwrap().ply
+    }
+
+    /// Check if the game is paused, waiting for a signal.
+    pub fn waiting(&self) -> bool {
+        self.held.lock().unwrap().waiting
+    }
+}
+
+/// Holds a game before each turn while paused by its [`Stepper`].
+///
+/// Added to a game with [`Config::pause`].
+pub struct Pause<G> {
+    signals: Receiver<Signal>,
+    held: Arc<Mutex<Held<G>>>,
+    /// Ply at which to pause, or `None` to run freely.
+    stop: Option<usize>,
+}
+
+impl<G: Game> Pause<G> {
+    /// Hold the game until a signal lets its next turn start.
+    pub(crate) fn wait(&mut self, game: &G) {
+        let ply = {
+            let mut held = self.held.lock().unwrap();
+            held.game = Some(game.clone());
+            held.ply
+        };
+        while let Ok(signal) = self.signals.try_recv() {
+            self.signal(signal, ply);
+        }
+        while self.stop.is_some_and(|stop| ply >= stop) {
+            self.held.lock().unwrap().waiting = true;
+            match self.signals.recv() {
+                Ok(signal) => self.signal(signal, ply),
+                // The stepper is gone
+                Err(_) => self.stop = None,
+            }
+        }
+        let mut held = self.held.lock().unwrap();
+        held.waiting = false;
+        held.ply += 1;
+    }
+
+    /// Follow a signal received at `ply`.
+    fn signal(&mut self, signal: Signal, ply: usize) {
+        self.stop = match signal {
+            Signal::Step => Some(ply + 1),
+            Signal::Pause => Some(0),
+            Signal::Resume => None,
+            Signal::RunTo(stop) => Some(stop),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::TimeControl;
+    use crate::tests::Nim;
+
+    fn take_one(_: &Nim) -> u32 {
+        1
+    }
+
+    #[test]
+    fn session_waits_for_external_turns() {
+        let mut session =
+            Session::new(Nim::new(3), Config::new((0, take_one), (1, take_one))).external(0);
+        assert!(matches!(session.poll(), Some(Event::TurnStarted(0))));
+        assert!(session.waiting());
+        assert!(session.poll().is_none());
+
+        assert!(!session.accept_draw());
+        assert!(!session.submit_turn(4));
+        assert!(matches!(
+            session.poll(),
+            Some(Event::IllegalTurn { player: 0, turn: 4 })
+        ));
+        assert!(session.submit_turn(2));
+        assert!(matches!(
+            session.poll(),
+            Some(Event::TurnPlayed {
+                player: 0,
+                turn: 2,
+                ..
+            })
+        ));
+
+        // The agent moves once its turn has started
+        assert!(matches!(session.poll(), Some(Event::TurnStarted(1))));
+        assert!(!session.waiting());
+        assert!(matches!(
+            session.poll(),
+            Some(Event::TurnPlayed {
+                player: 1,
+                turn: 1,
+                ..
+            })
+        ));
+        assert!(matches!(session.poll(), Some(Event::GameOver(Some(1)))));
+        assert!(session.poll().is_none());
+        assert!(!session.submit_turn(1));
+    }
+
+    #[test]
+    fn observers_see_every_event() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let mut config = Config::new((0, take_one), (1, take_one)).observe(tx);
+        assert_eq!(
+            Nim::new(2).play_out(&mut config, || Duration::ZERO),
+            Some(1)
+        );
+        drop(config);
+
+        let events: Vec<_> = rx.into_iter().collect();
+        assert_eq!(events.len(), 5);
+        assert!(matches!(
+            events[0],
+            (Nim { stones: 2, .. }, Event::TurnStarted(0))
+        ));
+        assert!(matches!(
+            events[1],
+            (
+                Nim { stones: 1, .. },
+                Event::TurnPlayed {
+                    player: 0,
+                    turn: 1,
+                    ..
+                }
+            )
+        ));
+        assert!(matches!(
+            events[4],
+            (Nim { stones: 0, .. }, Event::GameOver(Some(1)))
+        ));
+    }
+
+    #[test]
+    fn observers_see_low_time() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let control = crate::clock::TimeControl::new(Duration::from_secs(10), Duration::ZERO)
+            .warnings(vec![Duration::from_secs(5), Duration::from_secs(2)]);
+        let mut config = Config::new((0, take_one), (1, take_one))
+            .clock(control)
+            .observe(tx);
+        // Every turn takes three seconds
+        let mut ticks = 0;
+        let now = || {
+            ticks += 3;
+            Duration::from_secs(ticks)
+        };
+        assert_eq!(Nim::new(5).play_out(&mut config, now), Some(0));
+        drop(config);
+
+        // Each warning is crossed once per player
+        let low: Vec<_> = rx
+            .into_iter()
+            .filter_map(|(_, event)| match event {
+                Event::LowTime { player, remaining } => Some((player, remaining.as_secs())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(low, [(0, 4), (1, 4), (0, 1)]);
+    }
+
+    #[test]
+    fn external_players_agree_to_draws() {
+        let config = Config::new((0, take_one), (1, take_one));
+        let mut session = Session::new(Nim::new(5), config).external(0).external(1);
+        assert!(session.offer_draw(1));
+        assert!(matches!(session.poll(), Some(Event::TurnStarted(0))));
+        assert!(matches!(session.poll(), Some(Event::TurnPlayed { .. })));
+        assert!(matches!(session.poll(), Some(Event::DrawOffered(0))));
+        assert!(session.offered());
+        assert!(session.accept_draw());
+        assert!(matches!(session.poll(), Some(Event::DrawAgreed)));
+        assert!(matches!(session.poll(), Some(Event::GameOver(None))));
+        assert!(session.over() && !session.game().over());
+        assert!(!session.submit_turn(1));
+
+        // An agent accepting a draw which was never offered resigns
+        let accept = |_: &Nim, _: &Context| Decision::AcceptDraw;
+        let config = Config::new((0, take_one), (1, take_one)).decider(0, accept);
+        let mut session = Session::new(Nim::new(5), config);
+        assert!(matches!(session.poll(), Some(Event::TurnStarted(0))));
+        assert!(matches!(session.poll(), Some(Event::Resigned(0))));
+        assert!(matches!(session.poll(), Some(Event::GameOver(Some(1)))));
+    }
+
+    #[test]
+    fn take_back_undoes_replies() {
+        let mut session =
+            Session::new(Nim::new(5), Config::new((0, take_one), (1, take_one))).external(0);
+        assert!(!session.take_back(&0));
+        assert!(session.submit_turn(2));
+        while session.poll().is_some() {}
+        assert_eq!(session.game().stones, 2);
+
+        assert!(session.take_back(&0));
+        assert!(matches!(
+            session.poll(),
+            Some(Event::TakenBack { player: 1, turn: 1 })
+        ));
+        assert!(matches!(
+            session.poll(),
+            Some(Event::TakenBack { player: 0, turn: 2 })
+        ));
+        assert_eq!(session.game().stones, 5);
+        assert!(session.waiting() && !session.take_back(&1));
+    }
+
+    #[test]
+    fn pause_holds_game_between_turns() {
+        let (stepper, pause) = stepper();
+        let game = std::thread::spawn(move || {
+            let mut config = Config::new((0, take_one), (1, take_one)).pause(pause);
+            Nim::new(5).play_out(&mut config, || Duration::ZERO)
+        });
+        let held = |ply| {
+            while !stepper.waiting() || stepper.ply() != ply {
+                std::thread::yield_now();
+            }
+            stepper.state().unwrap().stones
+        };
+        assert_eq!(held(0), 5);
+        stepper.step();
+        assert_eq!(held(1), 4);
+        stepper.run_to(3);
+        assert_eq!(held(3), 2);
+        stepper.resume();
+        assert_eq!(game.join().unwrap(), Some(0));
+        assert_eq!(stepper.ply(), 5);
+    }
+
+    #[test]
+    fn pause_is_not_charged_to_the_clock() {
+        let (stepper, pause) = stepper();
+        let game = std::thread::spawn(move || {
+            let control = TimeControl::new(Duration::from_millis(100), Duration::ZERO);
+            let mut config = Config::new((0, take_one), (1, take_one))
+                .clock(control)
+                .pause(pause);
+            let origin = Instant::now();
+            Nim::new(1).play_out(&mut config, || origin.elapsed())
+        });
+        // Hold the first turn past the time control
+        while !stepper.waiting() {
+            std::thread::yield_now();
+        }
+        std::thread::sleep(Duration::from_millis(200));
+        stepper.resume();
+        assert_eq!(game.join().unwrap(), Some(0));
+    }
+}
@@ -0,0 +1,298 @@
+//! Endgame tablebases by retrograde analysis.
+//!
+//! For games with state spaces small enough to enumerate, a [`Tablebase`]
+//! solves every position as a win, loss, or draw for the player to move,
+//! along with the distance in plies to the end of perfect play. Agents probe
+//! it with [`Tablebase::run`] to play solved endgames perfectly.
+//!
+//! Like [books](crate::book), positions are keyed by a hash of their string
+//! form, which is stored with each entry so that colliding positions are
+//! never confused.
+
+use std::collections::hash_map::{Entry, HashMap};
+use std::io::{self, Read, Write};
+
+use log::debug;
+
+use crate::util::binary::{read_str, read_u32, write_str};
+use crate::util::hash;
+use crate::Game;
+
+/// Leading bytes of a saved tablebase.
+const MAGIC: &[u8; 4] = b"GSTB";
+
+/// The solved value of a position for the player to move.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Outcome {
+    /// Won in this many plies.
+    Win(u32),
+    /// Lost in this many plies.
+    Loss(u32),
+    /// Drawn with perfect play.
+    Draw,
+}
+
+impl Outcome {
+    /// Get the outcome of a position from the outcome of a child.
+    fn parent(self, same: bool) -> Outcome {
+        match (self, same) {
+            (Outcome::Win(n), true) | (Outcome::Loss(n), false) => Outcome::Win(n + 1),
+            (Outcome::Loss(n), true) | (Outcome::Win(n), false) => Outcome::Loss(n + 1),
+            (Outcome::Draw, _) => Outcome::Draw,
+        }
+    }
+
+    /// Rank outcomes from the view of the player to move: faster wins first,
+    /// slower losses last.
+    fn rank(self) -> (u8, i64) {
+        match self {
+            Outcome::Win(n) => (2, -i64::from(n)),
+            Outcome::Draw => (1, 0),
+            Outcome::Loss(n) => (0, i64::from(n)),
+        }
+    }
+}
+
+/// A table of solved positions.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Tablebase {
+    entries: HashMap<u64, (String, Outcome)>,
+}
+
+impl Tablebase {
+    /// Create a new, empty Tablebase.
+    pub fn new() -> Tablebase {
+        Tablebase::default()
+    }
+
+    /// Solve every position of an enumeration by retrograde analysis.
+    ///
+    /// Finished games are scored directly. Each pass then solves the
+    /// positions one ply further from the end: a position is won if some turn
+    /// leads to a lost position for the opponent, and lost once every turn
+    /// leads to a won one. Positions left unsolved are draws. Turns leading
+    /// outside the enumeration are never solved, so positions which depend on
+    /// them may be marked as draws.
+    pub fn generate<G, I>(positions: I) -> Tablebase
+    where
+        G: Game,
+        I: IntoIterator<Item = G>,
+    {
+        let mut table = Tablebase::new();
+        // Link each unfinished position to its children
+        let mut open = Vec::new();
+        for game in positions {
+            let key = game.to_string();
+            if game.over() {
+                let outcome = match game.winner() {
+                    Some(winner) if winner == game.player() => Outcome::Win(0),
+                    Some(_) => Outcome::Loss(0),
+                    None => Outcome::Draw,
+                };
+                table.insert(key, outcome);
+                continue;
+            }
+            let player = game.player();
+            let children: Vec<_> = game
+                .turns()
+                .into_iter()
+                .map(|turn| {
+                    let mut child = game.clone();
+                    child.play(turn);
+                    (child.to_string(), child.player() == player)
+                })
+                .collect();
+            open.push((key, children));
+        }
+
+        // Solve one ply further from the end each pass
+        let mut pass = 0;
+        loop {
+            let mut solved = Vec::new();
+            open.retain(|(key, children)| {
+                let outcomes: Vec<_> = children
+                    .iter()
+                    .filter_map(|(child, same)| Some(table.get(child)?.parent(*same)))
+                    .collect();
+                let best = outcomes
+                    .iter()
+                    .copied()
+                    .max_by_key(|outcome| outcome.rank());
+                let outcome = match best {
+                    Some(win @ Outcome::Win(_)) => win,
+                    Some(loss @ Outcome::Loss(_)) if outcomes.len() == children.len() => loss,
+                    _ => return true,
+                };
+                solved.push((key.clone(), outcome));
+                false
+            });
+            if solved.is_empty() {
+                break;
+            }
+            pass += 1;
+            for (key, outcome) in solved {
+                table.insert(key, outcome);
+            }
+        }
+        debug!("solved tablebase in {pass} passes, {} drawn", open.len());
+        for (key, _) in open {
+            table.insert(key, Outcome::Draw);
+        }
+        table
+    }
+
+    /// Look up the outcome of a position.
+    pub fn probe<G: Game>(&self, game: &G) -> Option<Outcome> {
+        self.get(&game.to_string())
+    }
+
+    /// Look up the outcome of a position by its string form.
+    fn get(&self, key: &str) -> Option<Outcome> {
+        let (entry, outcome) = self.entries.get(&hash(key))?;
+        Some(*outcome).filter(|_| entry == key)
+    }
+
+    /// Record the outcome of a position by its string form.
+    ///
+    /// Positions colliding with another already in the table are skipped, and
+    /// so are left unsolved.
+    fn insert(&mut self, key: String, outcome: Outcome) {
+        match self.entries.entry(hash(&key)) {
+            Entry::Vacant(entry) => {
+                entry.insert((key, outcome));
+            }
+            Entry::Occupied(mut entry) if entry.get().0 == key => entry.get_mut().1 = outcome,
+            Entry::Occupied(_) => {
+                debug!("skipping position colliding with another in the tablebase: {key}");
+            }
+        }
+    }
+
+    /// Get the best turn from a solved position.
+    ///
+    /// Wins are taken as fast as possible and losses delayed as long as
+    /// possible. Returns `None` if any of its children is unsolved.
+    pub fn best<G: Game>(&self, game: &G) -> Option<G::Turn> {
+        let player = game.player();
+        let mut best: Option<(Outcome, G::Turn)> = None;
+        for turn in game.turns() {
+            let mut child = game.clone();
+            child.play(turn.clone());
+            let outcome = self.probe(&child)?.parent(child.player() == player);
+            if best.as_ref().is_none_or(|(b, _)| outcome.rank() > b.rank()) {
+                best = Some((outcome, turn));
+            }
+        }
+        best.map(|(_, turn)| turn)
+    }
+
+    /// Play perfectly if the position is solved, otherwise select a turn with
+    /// `search`.
+    pub fn run<G, F>(&self, game: &G, search: F) -> G::Turn
+    where
+        G: Game,
+        F: FnOnce(&G) -> G::Turn,
+    {
+        match self.best(game) {
+            Some(turn) => {
+                debug!("playing tablebase turn {turn}");
+                turn
+            }
+            None => search(game),
+        }
+    }
+
+    /// Get the number of solved positions.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Check if no positions are solved.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Write the tablebase in its binary format.
+    ///
+    /// All values are little-endian. After the magic bytes `GSTB` comes the
+    /// `u32` number of positions, then for each position its string form as a
+    /// `u32` byte length and UTF-8 bytes, a `u8` of 0 for a loss, 1 for a
+    /// draw, or 2 for a win, and the `u32` distance in plies.
+    pub fn save(&self, mut output: impl Write) -> io::Result<()> {
+        let mut entries: Vec<_> = self.entries.values().collect();
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        output.write_all(MAGIC)?;
+        output.write_all(&(entries.len() as u32).to_le_bytes())?;
+        for (key, outcome) in entries {
+            let (kind, distance) = match outcome {
+                Outcome::Loss(n) => (0u8, *n),
+                Outcome::Draw => (1, 0),
+                Outcome::Win(n) => (2, *n),
+            };
+            write_str(&mut output, key)?;
+            output.write_all(&[kind])?;
+            output.write_all(&distance.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Read a tablebase written by [`save`](Tablebase::save).
+    pub fn load(mut input: impl Read) -> io::Result<Tablebase> {
+        let invalid = |msg| io::Error::new(io::ErrorKind::InvalidData, msg);
+        let mut magic = [0; 4];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("not a tablebase"));
+        }
+        let mut table = Tablebase::new();
+        for _ in 0..read_u32(&mut input)? {
+            let key = read_str(&mut input)?;
+            let mut entry = [0; 5];
+            input.read_exact(&mut entry)?;
+            let distance = u32::from_le_bytes(entry[1..].try_into().unwrap());
+            let outcome = match entry[0] {
+                0 => Outcome::Loss(distance),
+                1 => Outcome::Draw,
+                2 => Outcome::Win(distance),
+                _ => return Err(invalid("invalid outcome")),
+            };
+            table.insert(key, outcome);
+        }
+        Ok(table)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::Nim;
+
+    #[test]
+    fn solves_nim() {
+        let positions =
+            (0..=12).flat_map(|stones| (0..2).map(move |player| Nim { stones, player }));
+        let table = Tablebase::generate(positions);
+        assert_eq!(table.len(), 26);
+        assert_eq!(table.probe(&Nim::new(0)), Some(Outcome::Loss(0)));
+        assert_eq!(table.probe(&Nim::new(3)), Some(Outcome::Win(1)));
+        assert_eq!(table.probe(&Nim::new(8)), Some(Outcome::Loss(4)));
+        assert_eq!(table.probe(&Nim::new(9)), Some(Outcome::Win(5)));
+        assert_eq!(table.best(&Nim::new(9)), Some(1));
+        assert_eq!(table.best(&Nim::new(16)), None);
+        assert_eq!(table.run(&Nim::new(16), |_| 3), 3);
+
+        // Positions depending on unknown children are drawn
+        let mut table = Tablebase::generate((4..=6).map(Nim::new));
+        assert_eq!(table.probe(&Nim::new(5)), Some(Outcome::Draw));
+
+        let mut saved = Vec::new();
+        table.save(&mut saved).unwrap();
+        assert_eq!(Tablebase::load(&saved[..]).unwrap(), table);
+        assert!(Tablebase::load(&b"GSBK"[..]).is_err());
+
+        // Another position stored under the same hash is never mistaken for it
+        let key = hash(&Nim::new(5).to_string());
+        table.entries.get_mut(&key).unwrap().0 = Nim::new(9).to_string();
+        assert_eq!(table.probe(&Nim::new(5)), None);
+    }
+}
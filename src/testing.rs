@@ -0,0 +1,393 @@
+//! Conformance checks for [`Game`] implementations.
+//!
+//! Call [`check`] from a game's tests with a few starting positions to catch
+//! common mistakes in `turns`, `play`, `over`, and `winner`. Every failure
+//! panics with the offending position, so the checks work with any test
+//! harness. For a broader search, [`fuzz`] plays many random games and
+//! reports the seed of the first one to go wrong.
+
+use std::cell::Cell;
+use std::error;
+use std::fmt::{self, Display};
+use std::ops::Range;
+use std::panic::{self, AssertUnwindSafe};
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+use crate::{Game, SetupGame};
+
+const DEPTH: usize = 3;
+const PLAYOUTS: usize = 20;
+const SEED: u64 = 0;
+const MAX_PLIES: usize = 100_000;
+const PROBES: usize = 4;
+
+/// Check the invariants of a game from each position.
+///
+/// Every position within 3 plies is checked, followed by 20 random playouts
+/// to the end from each position. Playouts are seeded, so failures repeat.
+///
+/// # Panics
+///
+/// Panics if any invariant is broken.
+pub fn check<G, I>(positions: I)
+where
+    G: Game,
+    I: IntoIterator<Item = G>,
+{
+    check_with(positions, DEPTH, PLAYOUTS, &mut StdRng::seed_from_u64(SEED));
+}
+
+/// Check the invariants of a game from each position, exhaustively to
+/// `depth` plies and with `playouts` random playouts.
+///
+/// The invariants checked at every position reached are:
+///
+/// - There is at least one player.
+/// - An unfinished game has legal turns and no winner.
+/// - Every legal turn can be played.
+/// - Playing a turn on a clone leaves the original untouched.
+/// - Playing the same turn on two clones gives the same position.
+/// - Playouts finish within 100,000 plies.
+///
+/// # Panics
+///
+/// Panics if any invariant is broken.
+pub fn check_with<G, I, R>(positions: I, depth: usize, playouts: usize, rng: &mut R)
+where
+    G: Game,
+    I: IntoIterator<Item = G>,
+    R: Rng + ?Sized,
+{
+    for game in positions {
+        explore(&game, depth);
+        for _ in 0..playouts {
+            let mut state = game.clone();
+            for _ in 0..MAX_PLIES {
+                let Some(turn) = position(&state).choose(rng).cloned() else {
+                    break;
+                };
+                state = child(&state, turn);
+            }
+            assert!(state.over(), "playout did not finish: {state}");
+        }
+    }
+}
+
+/// Check that each position, and every position within 3 plies of it,
+/// survives being written and read back as a setup.
+///
+/// # Panics
+///
+/// Panics if a setup cannot be read, or reads back as a different position.
+pub fn check_setup<G, I>(positions: I)
+where
+    G: SetupGame,
+    I: IntoIterator<Item = G>,
+{
+    fn visit<G: SetupGame>(game: &G, depth: usize) {
+        let setup = game.to_setup();
+        let read = G::from_setup(&setup)
+            .unwrap_or_else(|err| panic!("setup {setup:?} cannot be read: {err}"));
+        assert_eq!(
+            format!("{read:?}"),
+            format!("{game:?}"),
+            "setup {setup:?} reads back as a different position"
+        );
+        if depth == 0 || game.over() {
+            return;
+        }
+        for turn in game.turns() {
+            let mut child = game.clone();
+            child.play(turn);
+            visit(&child, depth - 1);
+        }
+    }
+    for game in positions {
+        visit(&game, DEPTH);
+    }
+}
+
+/// A random game which went wrong while fuzzing.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Failure {
+    /// Seed which reproduces the game.
+    pub seed: u64,
+    /// Ply at which it went wrong.
+    pub ply: usize,
+    /// What went wrong.
+    pub reason: String,
+}
+
+impl Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "seed {} failed at ply {}: {}",
+            self.seed, self.ply, self.reason
+        )
+    }
+}
+
+impl error::Error for Failure {}
+
+/// Play random games from `game` with seeds `0..games`.
+///
+/// See [`fuzz_with`] for what is checked.
+pub fn fuzz<G: Game>(game: &G, games: u64) -> Result<(), Failure> {
+    fuzz_with(game, 0..games, MAX_PLIES)
+}
+
+/// Play a random game from `game` with each seed, up to `max_plies` plies.
+///
+/// Each game fails if it panics, if it does not finish in time, if it has a
+/// winner before it is over, or if `play` accepts a turn which is not legal.
+/// Illegal turns are taken from those legal earlier in the same game. Running
+/// again with the seed of a failure repeats it exactly.
+pub fn fuzz_with<G: Game>(game: &G, seeds: Range<u64>, max_plies: usize) -> Result<(), Failure> {
+    for seed in seeds {
+        let ply = Cell::new(0);
+        let result = panic::catch_unwind(AssertUnwindSafe(|| playout(game, seed, max_plies, &ply)));
+        let reason = match result {
+            Ok(Ok(())) => continue,
+            Ok(Err(reason)) => reason,
+            Err(payload) => {
+                let msg = payload
+                    .downcast_ref::<&str>()
+                    .map(ToString::to_string)
+                    .or_else(|| payload.downcast_ref::<String>().cloned())
+                    .unwrap_or_default();
+                format!("panicked: {msg}")
+            }
+        };
+        return Err(Failure {
+            seed,
+            ply: ply.get(),
+            reason,
+        });
+    }
+    Ok(())
+}
+
+/// Play a seeded random game, keeping track of the ply reached.
+fn playout<G: Game>(
+    game: &G,
+    seed: u64,
+    max_plies: usize,
+    ply: &Cell<usize>,
+) -> Result<(), String> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut state = game.clone();
+    let mut seen: Vec<G::Turn> = Vec::new();
+    for n in 0..max_plies {
+        ply.set(n);
+        if state.over() {
+            return Ok(());
+        }
+        if state.winner().is_some() {
+            return Err(format!("unfinished game has a winner: {state}"));
+        }
+        let turns = state.turns();
+        let Some(turn) = turns.choose(&mut rng).cloned() else {
+            return Err(format!("unfinished game has no turns: {state}"));
+        };
+
+        // Try turns which were legal earlier but are not now
+        let names: Vec<_> = turns.iter().map(ToString::to_string).collect();
+        let illegal: Vec<_> = seen
+            .iter()
+            .filter(|turn| !names.contains(&turn.to_string()))
+            .collect();
+        for probe in illegal.choose_multiple(&mut rng, PROBES) {
+            if state.clone().play((*probe).clone()) {
+                return Err(format!("illegal turn {probe} was played: {state}"));
+            }
+        }
+        seen.extend(turns);
+
+        if !state.play(turn.clone()) {
+            return Err(format!("legal turn {turn} cannot be played: {state}"));
+        }
+    }
+    ply.set(max_plies);
+    match state.over() {
+        true => Ok(()),
+        false => Err(format!("game did not finish: {state}")),
+    }
+}
+
+/// Check every position within `depth` plies.
+fn explore<G: Game>(game: &G, depth: usize) {
+    let turns = position(game);
+    if depth == 0 {
+        return;
+    }
+    for turn in turns {
+        explore(&child(game, turn), depth - 1);
+    }
+}
+
+/// Check the invariants of a single position, returning its legal turns.
+///
+/// Finished games have no legal turns to continue with.
+fn position<G: Game>(game: &G) -> Vec<G::Turn> {
+    assert!(game.players() > 0, "game has no players: {game}");
+    if game.over() {
+        return Vec::new();
+    }
+    assert!(
+        game.winner().is_none(),
+        "unfinished game has a winner: {game}"
+    );
+    let turns = game.turns();
+    assert!(!turns.is_empty(), "unfinished game has no turns: {game}");
+    turns
+}
+
+/// Play a legal turn, checking that it behaves.
+fn child<G: Game>(game: &G, turn: G::Turn) -> G {
+    let before = format!("{game:?}");
+    let mut first = game.clone();
+    let mut second = game.clone();
+    assert!(
+        first.play(turn.clone()),
+        "legal turn {turn} cannot be played: {game}"
+    );
+    second.play(turn.clone());
+    assert_eq!(
+        format!("{game:?}"),
+        before,
+        "playing {turn} on a clone changed the original"
+    );
+    assert_eq!(
+        format!("{first:?}"),
+        format!("{second:?}"),
+        "playing {turn} twice from {game} gave different positions"
+    );
+    first
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{Nim, Steps};
+    use crate::SetupError;
+
+    #[test]
+    fn check_passes_conforming_games() {
+        check((1..10).map(Nim::new));
+        check([Steps::default()]);
+    }
+
+    // Claims a winner before the game is over
+    #[derive(Clone, Debug)]
+    struct Eager(Nim);
+
+    impl Display for Eager {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            self.0.fmt(f)
+        }
+    }
+
+    impl Game for Eager {
+        type Player = u8;
+        type Turn = u32;
+
+        fn player(&self) -> u8 {
+            self.0.player()
+        }
+
+        fn turns(&self) -> Vec<u32> {
+            self.0.turns()
+        }
+
+        fn play(&mut self, turn: u32) -> bool {
+            self.0.play(turn)
+        }
+
+        fn over(&self) -> bool {
+            self.0.over()
+        }
+
+        fn winner(&self) -> Option<u8> {
+            Some(self.0.player ^ 1)
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "unfinished game has a winner")]
+    fn check_catches_early_winners() {
+        check([Eager(Nim::new(5))]);
+    }
+
+    // Accepts taking any number of stones
+    #[derive(Clone, Debug)]
+    struct Lax(Nim);
+
+    impl Display for Lax {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            self.0.fmt(f)
+        }
+    }
+
+    impl Game for Lax {
+        type Player = u8;
+        type Turn = u32;
+
+        fn player(&self) -> u8 {
+            self.0.player()
+        }
+
+        fn turns(&self) -> Vec<u32> {
+            self.0.turns()
+        }
+
+        fn play(&mut self, turn: u32) -> bool {
+            self.0.stones = self.0.stones.saturating_sub(turn);
+            self.0.player ^= 1;
+            true
+        }
+
+        fn over(&self) -> bool {
+            self.0.over()
+        }
+
+        fn winner(&self) -> Option<u8> {
+            self.0.winner()
+        }
+    }
+
+    #[test]
+    fn check_setup_round_trips() {
+        check_setup((0..10).map(Nim::new));
+        assert_eq!(
+            Nim::from_setup("3 2"),
+            Err(SetupError::Invalid("no player 2".to_string()))
+        );
+        assert!(matches!(
+            Nim::from_setup("3"),
+            Err(SetupError::Malformed(_))
+        ));
+    }
+
+    #[test]
+    fn fuzz_reports_seeds() {
+        assert_eq!(fuzz(&Nim::new(20), 200), Ok(()));
+        assert_eq!(fuzz(&Steps::default(), 50), Ok(()));
+
+        let failure = fuzz(&Eager(Nim::new(5)), 10).unwrap_err();
+        assert_eq!((failure.seed, failure.ply), (0, 0));
+
+        // Only fails once a longer turn has been seen
+        let failure = fuzz(&Lax(Nim::new(5)), 100).unwrap_err();
+        assert!(failure.reason.starts_with("illegal turn"));
+        let again = fuzz_with(&Lax(Nim::new(5)), failure.seed..failure.seed + 1, 10);
+        assert_eq!(again, Err(failure));
+
+        // Games which never end are capped
+        let failure = fuzz_with(&Nim::new(1000), 0..1, 10).unwrap_err();
+        assert_eq!(failure.ply, 10);
+    }
+}
@@ -0,0 +1,275 @@
+//! AlphaZero-style training through self-play.
+//!
+//! Each generation, the current [`Oracle`] plays games against itself with
+//! [`oracle_visits`], recording a [`Sample`] for every position. The samples
+//! are handed to a trainer which returns a candidate model, and the
+//! candidate replaces the current model only if it wins a gating match.
+//! Samples can also be kept in a [`replay::Buffer`] and exported for
+//! external training scripts, and self-play can be spread over machines with
+//! a [`remote::Coordinator`] and its workers.
+
+use std::sync::Arc;
+
+use log::debug;
+use rand::Rng;
+
+use crate::ai::mcts::{self, Budget, Hooks, MctsConfig, Noise, Oracle, Schedule, Selection};
+use crate::arena::{self, MatchConfig};
+use crate::Game;
+
+pub mod remote;
+pub mod replay;
+
+/// A training target recorded from self-play.
+#[derive(Clone, Debug)]
+pub struct Sample<G: Game> {
+    /// Position searched.
+    pub state: G,
+    /// Fraction of search visits for each turn.
+    pub policy: Vec<(G::Turn, f32)>,
+    /// Final outcome for the player to move, from -1 for a loss to 1 for a
+    /// win.
+    pub outcome: f32,
+}
+
+/// Configuration for a training run.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TrainConfig {
+    /// Configuration of each search.
+    pub search: MctsConfig,
+    /// Noise mixed into root priors during self-play.
+    pub noise: Noise,
+    /// Temperature for sampling turns by visit count in self-play.
+    pub temperature: Schedule,
+    /// Number of generations to train.
+    pub generations: usize,
+    /// Self-play games per generation.
+    pub games: usize,
+    /// Games in each gating match, alternating who moves first.
+    pub matches: usize,
+    /// Score needed by a candidate to replace the current model, counting
+    /// ties as half a win.
+    pub gate: f64,
+}
+
+impl Default for TrainConfig {
+    fn default() -> Self {
+        TrainConfig {
+            search: MctsConfig {
+                budget: Budget::Iterations(200),
+                threshold: 0,
+                ..Default::default()
+            },
+            noise: Noise::default(),
+            temperature: Schedule::default(),
+            generations: 10,
+            games: 25,
+            matches: 20,
+            gate: 0.55,
+        }
+    }
+}
+
+/// Play a game of self-play, recording a sample for each position.
+pub fn self_play<G, R>(
+    game: &G,
+    oracle: &Arc<dyn Oracle<G>>,
+    config: &TrainConfig,
+    rng: &mut R,
+) -> Vec<Sample<G>>
+where
+    G: Game,
+    G::Turn: PartialEq,
+    R: Rng + ?Sized,
+{
+    record(game, oracle, config, rng).0
+}
+
+/// Play a game of self-play, returning its samples and the turns played.
+fn record<G, R>(
+    game: &G,
+    oracle: &Arc<dyn Oracle<G>>,
+    config: &TrainConfig,
+    rng: &mut R,
+) -> (Vec<Sample<G>>, Vec<G::Turn>)
+where
+    G: Game,
+    G::Turn: PartialEq,
+    R: Rng + ?Sized,
+{
+    let search = MctsConfig {
+        noise: Some(config.noise),
+        ..config.search.clone()
+    };
+    let mut state = game.clone();
+    let mut history = Vec::new();
+    let mut turns = Vec::new();
+    while !state.over() {
+        let visits = mcts::oracle_visits(&state, &search, oracle.clone());
+        // Forced turns are not searched, so have no visits
+        let total = visits.iter().map(|(_, sims)| sims).sum::<u32>();
+        let policy: Vec<_> = visits
+            .iter()
+            .map(|(turn, sims)| match total {
+                0 => (turn.clone(), 1. / visits.len() as f32),
+                _ => (turn.clone(), *sims as f32 / total as f32),
+            })
+            .collect();
+
+        // Sample turns by temperature to vary the games
+        let temperature = config.temperature.temperature(history.len());
+        let turn = mcts::sample(&visits, temperature, rng);
+        history.push((state.clone(), policy));
+        turns.push(turn.clone());
+        state.play(turn);
+    }
+
+    // Label each position with the final outcome
+    let winner = state.winner();
+    let samples = history
+        .into_iter()
+        .map(|(state, policy)| {
+            let outcome = match &winner {
+                Some(winner) if *winner == state.player() => 1.,
+                Some(_) => -1.,
+                None => 0.,
+            };
+            Sample {
+                state,
+                policy,
+                outcome,
+            }
+        })
+        .collect();
+    (samples, turns)
+}
+
+/// Play a match between two models, returning the score of the first.
+///
+/// The match is played as by [`arena::run`], so the first model moves first
+/// in even games. Wins count 1 and ties count half.
+pub fn compare<G>(
+    game: &G,
+    first: &Arc<dyn Oracle<G>>,
+    second: &Arc<dyn Oracle<G>>,
+    config: &TrainConfig,
+) -> f64
+where
+    G: Game,
+    G::Turn: PartialEq,
+{
+    let matches = MatchConfig {
+        games: config.matches,
+        ..Default::default()
+    };
+    let search = MctsConfig {
+        selection: Selection::Puct,
+        ..config.search.clone()
+    };
+    let agent = |model: &Arc<dyn Oracle<G>>| {
+        let hooks = Hooks::new().oracle(model.clone()).same(PartialEq::eq);
+        let search = search.clone();
+        move |state: &G| mcts::run_with_hooks(state, &search, &hooks)
+    };
+    arena::run_with(game, agent(first), agent(second), &matches).score()
+}
+
+/// Train a model through self-play, starting from `model`.
+///
+/// Each generation, `trainer` is given the current model and the samples of
+/// its self-play games, and returns a candidate. Returns the final model.
+pub fn run<G, F, R>(
+    game: &G,
+    mut model: Arc<dyn Oracle<G>>,
+    config: &TrainConfig,
+    mut trainer: F,
+    rng: &mut R,
+) -> Arc<dyn Oracle<G>>
+where
+    G: Game,
+    G::Turn: PartialEq,
+    F: FnMut(&Arc<dyn Oracle<G>>, Vec<Sample<G>>) -> Arc<dyn Oracle<G>>,
+    R: Rng + ?Sized,
+{
+    for generation in 0..config.generations {
+        let samples: Vec<_> = (0..config.games)
+            .flat_map(|_| self_play(game, &model, config, rng))
+            .collect();
+        let candidate = trainer(&model, samples);
+
+        // Keep the candidate only if it beats the current model
+        let score = compare(game, &candidate, &model, config);
+        debug!("generation {generation}: candidate scored {score:.3}");
+        if score >= config.gate {
+            model = candidate;
+        }
+    }
+    model
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::tests::Nim;
+
+    // Knows nothing, or knows to leave a multiple of four stones
+    #[derive(Debug)]
+    struct Model(bool);
+
+    impl Oracle<Nim> for Model {
+        fn infer(&self, game: &Nim) -> (Vec<(u32, f32)>, f32) {
+            let good = game.stones % 4;
+            let priors = game
+                .turns()
+                .into_iter()
+                .map(|turn| (turn, if self.0 && turn == good { 0.9 } else { 0.05 }))
+                .collect();
+            let value = match (self.0, good) {
+                (false, _) => 0.,
+                (true, 0) => -1.,
+                (true, _) => 1.,
+            };
+            (priors, value)
+        }
+    }
+
+    #[test]
+    fn run_gates_candidates() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let config = TrainConfig {
+            search: MctsConfig {
+                budget: Budget::Iterations(10),
+                threshold: 0,
+                seed: Some(7),
+                ..Default::default()
+            },
+            generations: 1,
+            games: 2,
+            matches: 4,
+            ..Default::default()
+        };
+        let mut seen = 0;
+        let model = run(
+            &Nim::new(13),
+            Arc::new(Model(false)),
+            &config,
+            |_, samples| {
+                seen = samples.len();
+                for sample in &samples {
+                    let total: f32 = sample.policy.iter().map(|(_, p)| p).sum();
+                    assert!((total - 1.).abs() < 1e-4);
+                    assert!(sample.outcome.abs() == 1.);
+                }
+                Arc::new(Model(true))
+            },
+            &mut rng,
+        );
+        assert!(seen >= 4);
+        // The stronger candidate is accepted
+        assert_eq!(format!("{model:?}"), "Model(true)");
+        assert_eq!(compare(&Nim::new(13), &model, &model, &config), 0.5);
+    }
+}
@@ -0,0 +1,345 @@
+//! Spreading self-play over worker processes.
+//!
+//! A [`Coordinator`] listens for workers over TCP and hands each a [`Job`]
+//! naming the model and search configuration to play with. Each worker,
+//! running [`work`], plays its games and streams them back as they finish.
+//!
+//! The protocol is line-based text. The job is a single line of the number of
+//! games, the model name, and the search's [`SearchMeta`], separated by
+//! spaces. Each ply of a game is a line of tab-separated fields: the turn
+//! played, then `turn=probability` for each turn of the policy. A blank line
+//! ends each game, and `done` ends the stream. Positions are recovered by
+//! replaying the turns, so only the turns of games need to be sent.
+
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::thread;
+
+use log::{debug, warn};
+use rand::Rng;
+
+use crate::ai::mcts::{MctsConfig, Oracle};
+use crate::debug::SearchMeta;
+use crate::train::{self, Sample, TrainConfig};
+use crate::Game;
+
+/// Work handed to a self-play worker.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Job {
+    /// Name of the model to play with, which must not contain whitespace.
+    pub model: String,
+    /// Number of games to play.
+    pub games: usize,
+    /// Configuration of each search.
+    pub search: MctsConfig,
+}
+
+impl Job {
+    /// Format the job as a line of the protocol.
+    fn line(&self) -> String {
+        let meta = SearchMeta::mcts(self.search.clone());
+        format!("{} {} {meta}", self.games, self.model)
+    }
+
+    /// Parse a job from a line of the protocol.
+    fn parse(line: &str) -> io::Result<Job> {
+        let invalid = || invalid(format!("invalid job: {line}"));
+        let mut fields = line.trim_end().splitn(3, ' ');
+        let (Some(games), Some(model)) = (fields.next(), fields.next()) else {
+            return Err(invalid());
+        };
+        let games = games.parse().map_err(|_| invalid())?;
+        let meta: SearchMeta = fields.next().unwrap_or("").parse().map_err(|_| invalid())?;
+        Ok(Job {
+            model: model.to_string(),
+            games,
+            search: meta.config,
+        })
+    }
+}
+
+/// A game streamed by a worker, as the turn and policy of each ply.
+type Record = Vec<(String, Vec<(String, f32)>)>;
+
+/// Listens for self-play workers and collects their games.
+#[derive(Debug)]
+pub struct Coordinator {
+    listener: TcpListener,
+}
+
+impl Coordinator {
+    /// Listen for workers on an address.
+    pub fn bind(addr: impl ToSocketAddrs) -> io::Result<Coordinator> {
+        Ok(Coordinator {
+            listener: TcpListener::bind(addr)?,
+        })
+    }
+
+    /// Get the address workers should connect to.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Wait for `workers` workers, hand each the job, and collect the samples
+    /// of every game they finish.
+    ///
+    /// Games are replayed from `game` to recover their positions. A worker
+    /// which fails partway is logged and skipped, keeping the games it had
+    /// already finished, as is any game which cannot be replayed.
+    pub fn collect<G>(&self, game: &G, job: &Job, workers: usize) -> io::Result<Vec<Sample<G>>>
+    where
+        G: Game,
+        G::Turn: FromStr,
+    {
+        let mut streams = Vec::with_capacity(workers);
+        for _ in 0..workers {
+            let (stream, addr) = self.listener.accept()?;
+            debug!("worker connected from {addr}");
+            streams.push((stream, addr));
+        }
+
+        // Read from every worker at once
+        let results: Vec<_> = thread::scope(|scope| {
+            let handles: Vec<_> = streams
+                .into_iter()
+                .map(|(stream, addr)| scope.spawn(move || (addr, receive(stream, job))))
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+
+        let mut samples = Vec::new();
+        for (addr, (records, result)) in results {
+            if let Err(err) = result {
+                warn!("worker {addr} failed: {err}");
+            }
+            for record in records {
+                match replay(game, record) {
+                    Ok(game) => samples.extend(game),
+                    Err(err) => warn!("skipping game from worker {addr}: {err}"),
+                }
+            }
+        }
+        Ok(samples)
+    }
+}
+
+/// Send a job to a worker and read the games it streams back.
+///
+/// Games finished before an error are still returned.
+fn receive(stream: TcpStream, job: &Job) -> (Vec<Record>, io::Result<()>) {
+    let mut records = Vec::new();
+    let result = (|| {
+        writeln!(&stream, "{}", job.line())?;
+        let mut record = Record::new();
+        for line in BufReader::new(&stream).lines() {
+            let line = line?;
+            match line.as_str() {
+                "done" => return Ok(()),
+                "" => records.push(std::mem::take(&mut record)),
+                _ => record.push(parse_ply(&line)?),
+            }
+        }
+        Err(io::Error::from(io::ErrorKind::UnexpectedEof))
+    })();
+    (records, result)
+}
+
+/// Parse the turn and policy of a ply.
+fn parse_ply(line: &str) -> io::Result<(String, Vec<(String, f32)>)> {
+    let invalid = || invalid(format!("invalid ply: {line}"));
+    let mut fields = line.split('\t');
+    let turn = fields.next().ok_or_else(invalid)?.to_string();
+    let policy = fields
+        .map(|field| {
+            let (turn, p) = field.rsplit_once('=').ok_or_else(invalid)?;
+            Ok((turn.to_string(), p.parse().map_err(|_| invalid())?))
+        })
+        .collect::<io::Result<_>>()?;
+    Ok((turn, policy))
+}
+
+/// Recover the samples of a streamed game by replaying its turns.
+fn replay<G>(game: &G, record: Record) -> io::Result<Vec<Sample<G>>>
+where
+    G: Game,
+    G::Turn: FromStr,
+{
+    let turn = |turn: &str| {
+        turn.parse::<G::Turn>()
+            .map_err(|_| invalid(format!("invalid turn: {turn}")))
+    };
+    let mut state = game.clone();
+    let mut history = Vec::with_capacity(record.len());
+    for (played, policy) in record {
+        let policy = policy
+            .iter()
+            .map(|(name, p)| Ok((turn(name)?, *p)))
+            .collect::<io::Result<_>>()?;
+        history.push((state.clone(), policy));
+        if !state.play(turn(&played)?) {
+            return Err(invalid(format!("illegal turn: {played}")));
+        }
+    }
+    if !state.over() {
+        return Err(invalid("unfinished game".to_string()));
+    }
+
+    // Label each position with the final outcome
+    let winner = state.winner();
+    Ok(history
+        .into_iter()
+        .map(|(state, policy)| {
+            let outcome = match &winner {
+                Some(winner) if *winner == state.player() => 1.,
+                Some(_) => -1.,
+                None => 0.,
+            };
+            Sample {
+                state,
+                policy,
+                outcome,
+            }
+        })
+        .collect())
+}
+
+/// Connect to a coordinator and play the games of the job it hands over.
+///
+/// `model` looks up the model named by the job. Self-play uses the noise and
+/// temperature of `config`, but the job's search configuration. Returns the
+/// number of games played.
+pub fn work<G, F, R>(
+    addr: impl ToSocketAddrs,
+    game: &G,
+    config: &TrainConfig,
+    mut model: F,
+    rng: &mut R,
+) -> io::Result<usize>
+where
+    G: Game,
+    G::Turn: PartialEq,
+    F: FnMut(&str) -> Arc<dyn Oracle<G>>,
+    R: Rng + ?Sized,
+{
+    let stream = TcpStream::connect(addr)?;
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line)?;
+    let job = Job::parse(&line)?;
+    let oracle = model(&job.model);
+    let config = TrainConfig {
+        search: job.search,
+        ..config.clone()
+    };
+
+    let mut output = BufWriter::new(&stream);
+    for _ in 0..job.games {
+        let (samples, turns) = train::record(game, &oracle, &config, rng);
+        for (sample, turn) in samples.iter().zip(turns) {
+            write!(output, "{turn}")?;
+            for (turn, p) in &sample.policy {
+                write!(output, "\t{turn}={p}")?;
+            }
+            writeln!(output)?;
+        }
+        // Stream each game as it finishes
+        writeln!(output)?;
+        output.flush()?;
+    }
+    writeln!(output, "done")?;
+    output.flush()?;
+    Ok(job.games)
+}
+
+/// Create an error for invalid data from the other end.
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::ai::mcts::Budget;
+    use crate::tests::Nim;
+
+    #[derive(Debug)]
+    struct Uniform;
+
+    impl Oracle<Nim> for Uniform {
+        fn infer(&self, game: &Nim) -> (Vec<(u32, f32)>, f32) {
+            let turns = game.turns();
+            let p = 1. / turns.len() as f32;
+            (turns.into_iter().map(|turn| (turn, p)).collect(), 0.)
+        }
+    }
+
+    #[test]
+    fn workers_stream_games() {
+        let coordinator = Coordinator::bind("127.0.0.1:0").unwrap();
+        let addr = coordinator.local_addr().unwrap();
+        let job = Job {
+            model: "uniform".to_string(),
+            games: 2,
+            search: MctsConfig {
+                budget: Budget::Iterations(10),
+                threshold: 0,
+                seed: Some(7),
+                ..Default::default()
+            },
+        };
+        assert_eq!(Job::parse(&job.line()).unwrap(), job);
+
+        let workers: Vec<_> = (0..2)
+            .map(|seed| {
+                thread::spawn(move || {
+                    let mut rng = StdRng::seed_from_u64(seed);
+                    let model = |name: &str| -> Arc<dyn Oracle<Nim>> {
+                        assert_eq!(name, "uniform");
+                        Arc::new(Uniform)
+                    };
+                    work(addr, &Nim::new(7), &TrainConfig::default(), model, &mut rng)
+                })
+            })
+            .collect();
+        let samples = coordinator.collect(&Nim::new(7), &job, 2).unwrap();
+        for worker in workers {
+            assert_eq!(worker.join().unwrap().unwrap(), 2);
+        }
+
+        // Four games of at least three plies each
+        assert!(samples.len() >= 12);
+        assert_eq!(samples.iter().filter(|s| s.state.stones == 7).count(), 4);
+        for sample in &samples {
+            let total: f32 = sample.policy.iter().map(|(_, p)| p).sum();
+            assert!((total - 1.).abs() < 1e-4);
+            assert!(sample.outcome.abs() == 1.);
+        }
+        assert!(parse_ply("1\t2").is_err());
+    }
+
+    #[test]
+    fn malformed_games_are_skipped() {
+        let coordinator = Coordinator::bind("127.0.0.1:0").unwrap();
+        let addr = coordinator.local_addr().unwrap();
+        let worker = thread::spawn(move || {
+            let stream = TcpStream::connect(addr).unwrap();
+            let mut line = String::new();
+            BufReader::new(&stream).read_line(&mut line).unwrap();
+            // Taking nine stones is illegal, but the game after it is fine
+            write!(&stream, "9\n\n3\t3=1\n3\n1\n\ndone\n").unwrap();
+        });
+        let job = Job {
+            model: "uniform".to_string(),
+            games: 2,
+            search: MctsConfig::default(),
+        };
+        let samples = coordinator.collect(&Nim::new(7), &job, 1).unwrap();
+        worker.join().unwrap();
+        assert_eq!(samples.len(), 3);
+        assert_eq!(samples[0].policy, [(3, 1.)]);
+    }
+}
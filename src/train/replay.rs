@@ -0,0 +1,164 @@
+//! Storing self-play samples and exporting them for training.
+
+use std::collections::VecDeque;
+use std::io::{self, Write};
+
+use crate::train::Sample;
+use crate::util::json;
+use crate::Game;
+
+/// A self-play sample with its position encoded for a model.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+    /// Encoded position.
+    pub state: Vec<f32>,
+    /// Fraction of search visits for each turn, by turn name.
+    pub policy: Vec<(String, f32)>,
+    /// Final outcome for the player to move, from -1 for a loss to 1 for a
+    /// win.
+    pub outcome: f32,
+}
+
+/// A bounded buffer of the most recent self-play samples.
+#[derive(Clone, Debug, Default)]
+pub struct Buffer {
+    capacity: usize,
+    entries: VecDeque<Entry>,
+}
+
+impl Buffer {
+    /// Create a new, empty Buffer holding at most `capacity` entries.
+    pub fn new(capacity: usize) -> Buffer {
+        Buffer {
+            capacity,
+            entries: VecDeque::new(),
+        }
+    }
+
+    /// Add an entry, dropping the oldest if the buffer is full.
+    pub fn push(&mut self, entry: Entry) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+
+    /// Add samples, encoding each position with `encode`.
+    pub fn extend<G: Game>(&mut self, samples: &[Sample<G>], encode: impl Fn(&G) -> Vec<f32>) {
+        for sample in samples {
+            self.push(Entry {
+                state: encode(&sample.state),
+                policy: sample
+                    .policy
+                    .iter()
+                    .map(|(turn, p)| (turn.to_string(), *p))
+                    .collect(),
+                outcome: sample.outcome,
+            });
+        }
+    }
+
+    /// Iterate over the entries, oldest first.
+    pub fn iter(&self) -> impl Iterator<Item = &Entry> {
+        self.entries.iter()
+    }
+
+    /// Get the number of entries.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Check if there are no entries.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Write the entries as newline-delimited JSON, oldest first.
+    ///
+    /// Each line is an object such as
+    /// `{"state":[0,1],"policy":[["a",0.75],["b",0.25]],"outcome":1}`.
+    pub fn write_json(&self, mut output: impl Write) -> io::Result<()> {
+        for entry in &self.entries {
+            let state: Vec<_> = entry
+                .state
+                .iter()
+                .map(|&x| json::number(x.into()))
+                .collect();
+            let policy: Vec<_> = entry
+                .policy
+                .iter()
+                .map(|(turn, p)| format!("[{},{}]", json::string(turn), json::number((*p).into())))
+                .collect();
+            writeln!(
+                output,
+                r#"{{"state":[{}],"policy":[{}],"outcome":{}}}"#,
+                state.join(","),
+                policy.join(","),
+                json::number(entry.outcome.into()),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Write the entries in a compact binary format, oldest first.
+    ///
+    /// All values are little-endian. Each entry is the `u32` length of the
+    /// state followed by its `f32` values, the `u32` number of turns in the
+    /// policy followed by each turn as a `u32` byte length, UTF-8 bytes, and
+    /// `f32` probability, then the `f32` outcome.
+    pub fn write_binary(&self, mut output: impl Write) -> io::Result<()> {
+        for entry in &self.entries {
+            output.write_all(&(entry.state.len() as u32).to_le_bytes())?;
+            for x in &entry.state {
+                output.write_all(&x.to_le_bytes())?;
+            }
+            output.write_all(&(entry.policy.len() as u32).to_le_bytes())?;
+            for (turn, p) in &entry.policy {
+                output.write_all(&(turn.len() as u32).to_le_bytes())?;
+                output.write_all(turn.as_bytes())?;
+                output.write_all(&p.to_le_bytes())?;
+            }
+            output.write_all(&entry.outcome.to_le_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::Nim;
+
+    #[test]
+    fn buffer_keeps_recent_and_exports() {
+        let sample = |stones| Sample {
+            state: Nim::new(stones),
+            policy: vec![(1, 0.75), (2, 0.25)],
+            outcome: 1.,
+        };
+        let mut buffer = Buffer::new(2);
+        buffer.extend(&[sample(3), sample(4), sample(5)], |game| {
+            vec![game.stones as f32]
+        });
+        assert_eq!(buffer.len(), 2);
+        assert_eq!(buffer.iter().next().unwrap().state, [4.]);
+
+        let mut output = Vec::new();
+        buffer.write_json(&mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(
+            output.lines().next(),
+            Some(r#"{"state":[4],"policy":[["1",0.75],["2",0.25]],"outcome":1}"#)
+        );
+
+        let mut output = Vec::new();
+        buffer.write_binary(&mut output).unwrap();
+        // Per entry: 4 + 4 for the state, 4 + 2 * (4 + 1 + 4) for the
+        // policy, and 4 for the outcome
+        assert_eq!(output.len(), 2 * 34);
+        assert_eq!(output[..4], 1u32.to_le_bytes());
+    }
+}
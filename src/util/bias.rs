@@ -0,0 +1,200 @@
+use std::collections::BTreeMap;
+use std::fmt::{self, Display};
+
+/// Two-sided z-score for a 95% confidence interval.
+pub const Z95: f64 = 1.96;
+
+/// Result of a game as seen from the seats.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    /// The first player won.
+    First,
+    /// The second player won.
+    Second,
+    /// Neither player won.
+    Draw,
+}
+
+/// A point estimate with a confidence interval.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Interval {
+    /// Point estimate, or NaN without any samples.
+    pub estimate: f64,
+    /// Lower bound of the interval.
+    pub lower: f64,
+    /// Upper bound of the interval.
+    pub upper: f64,
+}
+
+impl Interval {
+    /// Wilson score interval for a binomial proportion.
+    fn wilson(hits: usize, n: usize, z: f64) -> Interval {
+        if n == 0 {
+            return Interval {
+                estimate: f64::NAN,
+                lower: 0.,
+                upper: 1.,
+            };
+        }
+        let n = n as f64;
+        let p = hits as f64 / n;
+        let z2 = z * z;
+        let center = (p + z2 / (2. * n)) / (1. + z2 / n);
+        let margin = z * (p * (1. - p) / n + z2 / (4. * n * n)).sqrt() / (1. + z2 / n);
+        Interval {
+            estimate: p,
+            lower: (center - margin).max(0.),
+            upper: (center + margin).min(1.),
+        }
+    }
+
+    /// Check if `x` lies within the interval.
+    pub fn contains(&self, x: f64) -> bool {
+        (self.lower..=self.upper).contains(&x)
+    }
+}
+
+impl Display for Interval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:.3} [{:.3}, {:.3}]",
+            self.estimate, self.lower, self.upper
+        )
+    }
+}
+
+/// Seat bias estimated from a set of results.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Estimate {
+    /// Number of games won by the first player.
+    pub first: usize,
+    /// Number of games won by the second player.
+    pub second: usize,
+    /// Number of drawn games.
+    pub draws: usize,
+    /// Expected score of the first player, counting draws as half.
+    pub score: Interval,
+    /// Proportion of drawn games.
+    pub draw_rate: Interval,
+}
+
+impl Estimate {
+    /// Estimate seat bias from a collection of outcomes.
+    pub fn new(outcomes: impl IntoIterator<Item = Outcome>, z: f64) -> Estimate {
+        let (mut first, mut second, mut draws) = (0, 0, 0);
+        for outcome in outcomes {
+            match outcome {
+                Outcome::First => first += 1,
+                Outcome::Second => second += 1,
+                Outcome::Draw => draws += 1,
+            }
+        }
+        let games = first + second + draws;
+
+        // Normal approximation using the sample variance of the score
+        let score = if games == 0 {
+            Interval {
+                estimate: f64::NAN,
+                lower: 0.,
+                upper: 1.,
+            }
+        } else {
+            let n = games as f64;
+            let mean = (first as f64 + 0.5 * draws as f64) / n;
+            let var = (first as f64 * (1. - mean).powi(2)
+                + draws as f64 * (0.5 - mean).powi(2)
+                + second as f64 * mean.powi(2))
+                / n;
+            let margin = z * (var / n).sqrt();
+            Interval {
+                estimate: mean,
+                lower: (mean - margin).max(0.),
+                upper: (mean + margin).min(1.),
+            }
+        };
+
+        Estimate {
+            first,
+            second,
+            draws,
+            score,
+            draw_rate: Interval::wilson(draws, games, z),
+        }
+    }
+
+    /// Get the number of games.
+    pub fn games(&self) -> usize {
+        self.first + self.second + self.draws
+    }
+
+    /// Get the advantaged seat, if the score differs significantly from even.
+    pub fn advantage(&self) -> Option<Outcome> {
+        match self.score {
+            s if s.lower > 0.5 => Some(Outcome::First),
+            s if s.upper < 0.5 => Some(Outcome::Second),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Estimate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} games (+{} ={} -{}), score: {}, draws: {}",
+            self.games(),
+            self.first,
+            self.draws,
+            self.second,
+            self.score,
+            self.draw_rate,
+        )
+    }
+}
+
+/// Estimate seat bias for each game or variant in a result set.
+pub fn estimate<K, I>(results: I, z: f64) -> BTreeMap<K, Estimate>
+where
+    K: Ord,
+    I: IntoIterator<Item = (K, Outcome)>,
+{
+    let mut groups: BTreeMap<K, Vec<Outcome>> = BTreeMap::new();
+    for (key, outcome) in results {
+        groups.entry(key).or_default().push(outcome);
+    }
+    groups
+        .into_iter()
+        .map(|(key, outcomes)| (key, Estimate::new(outcomes, z)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn balanced_results_unbiased() {
+        let outcomes = [Outcome::First, Outcome::Second, Outcome::Draw].repeat(100);
+        let est = Estimate::new(outcomes, Z95);
+        assert_eq!(est.advantage(), None);
+        assert!(est.draw_rate.contains(1. / 3.));
+    }
+
+    #[test]
+    fn first_move_advantage_detected() {
+        let results = (0..400).map(|i| {
+            let variant = if i % 2 == 0 { "open" } else { "komi" };
+            let outcome = match (variant, i / 2 % 10) {
+                ("open", 0..=6) => Outcome::First,
+                ("open", _) => Outcome::Second,
+                (_, 0..=4) => Outcome::First,
+                _ => Outcome::Second,
+            };
+            (variant, outcome)
+        });
+        let est = estimate(results, Z95);
+        assert_eq!(est["open"].advantage(), Some(Outcome::First));
+        assert_eq!(est["komi"].advantage(), None);
+    }
+}
@@ -0,0 +1,30 @@
+//! Helpers for writing and reading little-endian binary tables.
+
+use std::io::{self, Read, Write};
+
+/// Write a string as its `u32` byte length and UTF-8 bytes.
+pub(crate) fn write_str(output: &mut impl Write, value: &str) -> io::Result<()> {
+    output.write_all(&(value.len() as u32).to_le_bytes())?;
+    output.write_all(value.as_bytes())
+}
+
+/// Read a string written by [`write_str`].
+///
+/// The bytes are read as they arrive rather than allocated up front, so a
+/// corrupt length cannot exhaust memory.
+pub(crate) fn read_str(input: &mut impl Read) -> io::Result<String> {
+    let len = read_u32(input)?;
+    let mut bytes = Vec::new();
+    input.take(u64::from(len)).read_to_end(&mut bytes)?;
+    if bytes.len() != len as usize {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    String::from_utf8(bytes).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+/// Read a little-endian `u32`.
+pub(crate) fn read_u32(input: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    input.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
@@ -0,0 +1,178 @@
+//! ANSI styling for board display.
+//!
+//! Games can wrap pieces, highlights, and coordinates in [`Styled`] text in
+//! their `Display` impls. Styles are only written when standard output is a
+//! terminal and `NO_COLOR` is unset, so piped output stays plain text.
+
+use std::env;
+use std::fmt::{self, Display};
+use std::io::{self, IsTerminal};
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::OnceLock;
+
+// Styling forced on or off, or detected automatically
+const AUTO: u8 = 0;
+const ON: u8 = 1;
+const OFF: u8 = 2;
+static MODE: AtomicU8 = AtomicU8::new(AUTO);
+
+/// One of the basic terminal colors, in ANSI order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Color {
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+}
+
+/// Text which is styled when styling is enabled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Styled<T> {
+    value: T,
+    fg: Option<Color>,
+    bg: Option<Color>,
+    bold: bool,
+    dim: bool,
+    reverse: bool,
+}
+
+impl<T> Styled<T> {
+    /// Create a new, unstyled Styled.
+    pub fn new(value: T) -> Styled<T> {
+        Styled {
+            value,
+            fg: None,
+            bg: None,
+            bold: false,
+            dim: false,
+            reverse: false,
+        }
+    }
+
+    /// Set the text color.
+    pub fn fg(mut self, color: Color) -> Styled<T> {
+        self.fg = Some(color);
+        self
+    }
+
+    /// Set the background color.
+    pub fn bg(mut self, color: Color) -> Styled<T> {
+        self.bg = Some(color);
+        self
+    }
+
+    /// Make the text bold.
+    pub fn bold(mut self) -> Styled<T> {
+        self.bold = true;
+        self
+    }
+
+    /// Make the text faint.
+    pub fn dim(mut self) -> Styled<T> {
+        self.dim = true;
+        self
+    }
+
+    /// Swap the text and background colors.
+    pub fn reverse(mut self) -> Styled<T> {
+        self.reverse = true;
+        self
+    }
+
+    /// Get the ANSI codes of the style.
+    fn codes(&self) -> Vec<String> {
+        let flags = [(self.bold, 1), (self.dim, 2), (self.reverse, 7)];
+        let mut codes: Vec<_> = flags
+            .iter()
+            .filter(|(set, _)| *set)
+            .map(|(_, code)| code.to_string())
+            .collect();
+        codes.extend(self.fg.map(|color| (30 + color as u8).to_string()));
+        codes.extend(self.bg.map(|color| (40 + color as u8).to_string()));
+        codes
+    }
+}
+
+impl<T: Display> Display for Styled<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let codes = self.codes();
+        if codes.is_empty() || !enabled() {
+            return self.value.fmt(f);
+        }
+        write!(f, "\x1b[{}m", codes.join(";"))?;
+        self.value.fmt(f)?;
+        write!(f, "\x1b[0m")
+    }
+}
+
+/// Style a piece in its player's color.
+pub fn piece<T>(value: T, color: Color) -> Styled<T> {
+    Styled::new(value).fg(color).bold()
+}
+
+/// Highlight a cell, such as the one last played on.
+pub fn highlight<T>(value: T) -> Styled<T> {
+    Styled::new(value).reverse()
+}
+
+/// Dim text which should stay out of the way, such as coordinates.
+pub fn dim<T>(value: T) -> Styled<T> {
+    Styled::new(value).dim()
+}
+
+/// Check if styles are written.
+///
+/// Unless forced with [`set_enabled`], styles are written only when standard
+/// output is a terminal and `NO_COLOR` is unset.
+pub fn enabled() -> bool {
+    static DETECTED: OnceLock<bool> = OnceLock::new();
+    match MODE.load(Ordering::Relaxed) {
+        ON => true,
+        OFF => false,
+        _ => *DETECTED.get_or_init(|| {
+            io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+        }),
+    }
+}
+
+/// Force styles on or off, or detect whether to write them with `None`.
+pub fn set_enabled(enabled: Option<bool>) {
+    let mode = match enabled {
+        Some(true) => ON,
+        Some(false) => OFF,
+        None => AUTO,
+    };
+    MODE.store(mode, Ordering::Relaxed);
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    /// Held by tests which force styles on or off.
+    pub(crate) static LOCK: Mutex<()> = Mutex::new(());
+
+    #[test]
+    fn styles_fall_back_to_plain_text() {
+        let _lock = LOCK.lock().unwrap();
+        set_enabled(Some(true));
+        assert_eq!(piece('X', Color::Red).to_string(), "\x1b[1;31mX\x1b[0m");
+        assert_eq!(
+            highlight("o").bg(Color::Blue).to_string(),
+            "\x1b[7;44mo\x1b[0m"
+        );
+        assert_eq!(format!("{:>3}", dim(5)), "\x1b[2m  5\x1b[0m");
+        assert_eq!(Styled::new("plain").to_string(), "plain");
+
+        set_enabled(Some(false));
+        assert_eq!(piece('X', Color::Red).to_string(), "X");
+        assert_eq!(format!("{:>3}", dim(5)), "  5");
+        set_enabled(None);
+    }
+}
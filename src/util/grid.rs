@@ -0,0 +1,159 @@
+//! Drawing rectangular boards with box-drawing characters.
+//!
+//! A [`Grid`] renders any board given what to show in each cell, with
+//! borders, optional rank and file labels, and highlighted cells, so games
+//! can use it in their `Display` impls:
+//!
+//! ```
+//! use gamesweet::util::grid::Grid;
+//!
+//! let board = Grid::new(2, 2, |row, col| if row == col { "X" } else { "" }).labels();
+//! assert_eq!(
+//!     board.to_string(),
+//!     "    a   b
+//!   ┌───┬───┐
+//! 2 │ X │   │
+//!   ├───┼───┤
+//! 1 │   │ X │
+//!   └───┴───┘
+//! "
+//! );
+//! ```
+
+use std::fmt::{self, Display, Write};
+
+use crate::util::display;
+
+/// A board of cells drawn with box-drawing borders.
+#[derive(Clone, Debug)]
+pub struct Grid<F> {
+    rows: usize,
+    cols: usize,
+    cell: F,
+    labels: bool,
+    highlights: Vec<(usize, usize)>,
+}
+
+impl<F, T> Grid<F>
+where
+    F: Fn(usize, usize) -> T,
+    T: Display,
+{
+    /// Create a new Grid showing `cell(row, col)` in each cell, with row 0 at
+    /// the top.
+    pub fn new(rows: usize, cols: usize, cell: F) -> Grid<F> {
+        Grid {
+            rows,
+            cols,
+            cell,
+            labels: false,
+            highlights: Vec::new(),
+        }
+    }
+
+    /// Label the files with letters from the left, and the ranks with
+    /// numbers from the bottom.
+    pub fn labels(mut self) -> Grid<F> {
+        self.labels = true;
+        self
+    }
+
+    /// Highlight a cell, such as the one last played on.
+    pub fn highlight(mut self, row: usize, col: usize) -> Grid<F> {
+        self.highlights.push((row, col));
+        self
+    }
+}
+
+impl<F, T> Display for Grid<F>
+where
+    F: Fn(usize, usize) -> T,
+    T: Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let cells: Vec<Vec<String>> = (0..self.rows)
+            .map(|row| {
+                (0..self.cols)
+                    .map(|col| (self.cell)(row, col).to_string())
+                    .collect()
+            })
+            .collect();
+        let width = cells
+            .iter()
+            .flatten()
+            .map(|cell| cell.chars().count())
+            .max()
+            .unwrap_or(0)
+            .max(1);
+        let margin = match self.labels {
+            true => self.rows.to_string().len() + 1,
+            false => 0,
+        };
+
+        let border = |left: char, join: char, right: char| {
+            let mut line = format!("{:margin$}{left}", "");
+            for col in 0..self.cols {
+                line.push_str(&"─".repeat(width + 2));
+                line.push(if col + 1 < self.cols { join } else { right });
+            }
+            line
+        };
+        if self.labels {
+            let mut files = format!("{:margin$} ", "");
+            for col in 0..self.cols {
+                write!(files, " {:^width$}  ", file(col))?;
+            }
+            writeln!(f, "{}", files.trim_end())?;
+        }
+        writeln!(f, "{}", border('┌', '┬', '┐'))?;
+        for (row, line) in cells.iter().enumerate() {
+            if self.labels {
+                write!(f, "{:>w$} ", self.rows - row, w = margin - 1)?;
+            }
+            write!(f, "│")?;
+            for (col, cell) in line.iter().enumerate() {
+                let body = format!(" {cell:^width$} ");
+                match self.highlights.contains(&(row, col)) {
+                    true => write!(f, "{}│", display::highlight(body))?,
+                    false => write!(f, "{body}│")?,
+                }
+            }
+            writeln!(f)?;
+            if row + 1 < self.rows {
+                writeln!(f, "{}", border('├', '┼', '┤'))?;
+            }
+        }
+        writeln!(f, "{}", border('└', '┴', '┘'))
+    }
+}
+
+/// Get the label of a file, from `a` to `z`, then numbered.
+fn file(col: usize) -> String {
+    match col {
+        0..26 => char::from(b'a' + col as u8).to_string(),
+        _ => (col + 1).to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grid_draws_borders() {
+        let grid = Grid::new(1, 3, |_, col| ["X", "", "10"][col]);
+        assert_eq!(
+            grid.to_string(),
+            "┌────┬────┬────┐\n│ X  │    │ 10 │\n└────┴────┴────┘\n"
+        );
+
+        let _lock = display::tests::LOCK.lock().unwrap();
+        display::set_enabled(Some(true));
+        let grid = Grid::new(1, 2, |_, _| "o").highlight(0, 1);
+        assert_eq!(
+            grid.to_string().lines().nth(1),
+            Some("│ o │\x1b[7m o \x1b[0m│")
+        );
+        display::set_enabled(None);
+    }
+}
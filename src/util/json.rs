@@ -0,0 +1,255 @@
+//! Minimal helpers for writing and reading JSON by hand.
+
+use std::fmt::Write;
+#[cfg(feature = "record")]
+use std::iter::Peekable;
+#[cfg(feature = "record")]
+use std::str::Chars;
+
+/// Quote and escape a string as a JSON string literal.
+pub(crate) fn string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Format a list of strings as a JSON array.
+#[cfg(any(feature = "batch", feature = "record"))]
+pub(crate) fn strings<I, T>(items: I) -> String
+where
+    I: IntoIterator<Item = T>,
+    T: AsRef<str>,
+{
+    let items: Vec<_> = items.into_iter().map(|s| string(s.as_ref())).collect();
+    format!("[{}]", items.join(","))
+}
+
+/// Format a number as JSON, using `null` for non-finite values.
+#[cfg(any(feature = "batch", feature = "train"))]
+pub(crate) fn number(x: f64) -> String {
+    if x.is_finite() {
+        x.to_string()
+    } else {
+        "null".to_string()
+    }
+}
+
+/// A parsed JSON value.
+#[cfg(feature = "record")]
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    /// Members in the order written.
+    Object(Vec<(String, Value)>),
+}
+
+#[cfg(feature = "record")]
+impl Value {
+    /// Look up a member of an object.
+    pub(crate) fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Object(members) => members
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    /// Get the contents of a string.
+    pub(crate) fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Get the value of a number.
+    pub(crate) fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Number(x) => Some(*x),
+            _ => None,
+        }
+    }
+
+    /// Get the items of an array.
+    pub(crate) fn as_array(&self) -> Option<&[Value]> {
+        match self {
+            Value::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+}
+
+/// Parse a JSON document.
+#[cfg(feature = "record")]
+pub(crate) fn parse(s: &str) -> Result<Value, String> {
+    let mut chars = s.chars().peekable();
+    let value = value(&mut chars)?;
+    skip(&mut chars);
+    match chars.next() {
+        None => Ok(value),
+        Some(c) => Err(format!("unexpected {c:?} after value")),
+    }
+}
+
+/// Skip whitespace.
+#[cfg(feature = "record")]
+fn skip(chars: &mut Peekable<Chars>) {
+    while chars.next_if(|c| c.is_ascii_whitespace()).is_some() {}
+}
+
+/// Parse a value at the start of the input.
+#[cfg(feature = "record")]
+fn value(chars: &mut Peekable<Chars>) -> Result<Value, String> {
+    skip(chars);
+    match chars.peek().copied() {
+        Some('n') => literal(chars, "null", Value::Null),
+        Some('t') => literal(chars, "true", Value::Bool(true)),
+        Some('f') => literal(chars, "false", Value::Bool(false)),
+        Some('"') => Ok(Value::String(quoted(chars)?)),
+        Some('[') => {
+            chars.next();
+            let mut items = Vec::new();
+            skip(chars);
+            if chars.next_if_eq(&']').is_some() {
+                return Ok(Value::Array(items));
+            }
+            loop {
+                items.push(value(chars)?);
+                skip(chars);
+                match chars.next() {
+                    Some(',') => continue,
+                    Some(']') => return Ok(Value::Array(items)),
+                    _ => return Err("expected , or ] in array".to_string()),
+                }
+            }
+        }
+        Some('{') => {
+            chars.next();
+            let mut members = Vec::new();
+            skip(chars);
+            if chars.next_if_eq(&'}').is_some() {
+                return Ok(Value::Object(members));
+            }
+            loop {
+                skip(chars);
+                if chars.peek() != Some(&'"') {
+                    return Err("expected member name".to_string());
+                }
+                let name = quoted(chars)?;
+                skip(chars);
+                if chars.next() != Some(':') {
+                    return Err("expected : after member name".to_string());
+                }
+                members.push((name, value(chars)?));
+                skip(chars);
+                match chars.next() {
+                    Some(',') => continue,
+                    Some('}') => return Ok(Value::Object(members)),
+                    _ => return Err("expected , or } in object".to_string()),
+                }
+            }
+        }
+        Some(c) if c == '-' || c.is_ascii_digit() => {
+            let mut number = String::new();
+            while let Some(c) = chars.next_if(|c| "+-.eE".contains(*c) || c.is_ascii_digit()) {
+                number.push(c);
+            }
+            number
+                .parse()
+                .map(Value::Number)
+                .map_err(|_| format!("invalid number {number}"))
+        }
+        Some(c) => Err(format!("unexpected {c:?}")),
+        None => Err("unexpected end of input".to_string()),
+    }
+}
+
+/// Parse a keyword at the start of the input as `value`.
+#[cfg(feature = "record")]
+fn literal(chars: &mut Peekable<Chars>, word: &str, value: Value) -> Result<Value, String> {
+    for want in word.chars() {
+        if chars.next() != Some(want) {
+            return Err(format!("expected {word}"));
+        }
+    }
+    Ok(value)
+}
+
+/// Parse a string literal at the start of the input.
+#[cfg(feature = "record")]
+fn quoted(chars: &mut Peekable<Chars>) -> Result<String, String> {
+    chars.next();
+    let mut out = String::new();
+    loop {
+        match chars.next() {
+            Some('"') => return Ok(out),
+            Some('\\') => match chars.next() {
+                Some('n') => out.push('\n'),
+                Some('r') => out.push('\r'),
+                Some('t') => out.push('\t'),
+                Some('b') => out.push('\u{8}'),
+                Some('f') => out.push('\u{c}'),
+                Some('u') => {
+                    let hex: String = chars.by_ref().take(4).collect();
+                    let code = u32::from_str_radix(&hex, 16).map_err(|_| "invalid escape")?;
+                    out.push(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER));
+                }
+                Some(c @ ('"' | '\\' | '/')) => out.push(c),
+                _ => return Err("invalid escape".to_string()),
+            },
+            Some(c) => out.push(c),
+            None => return Err("unterminated string".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_strings() {
+        assert_eq!(string("a\"b\\c\n"), r#""a\"b\\c\n""#);
+        #[cfg(any(feature = "batch", feature = "record"))]
+        assert_eq!(strings(["x", "y"]), r#"["x","y"]"#);
+        #[cfg(any(feature = "batch", feature = "train"))]
+        assert_eq!(number(f64::NAN), "null");
+    }
+
+    #[test]
+    #[cfg(feature = "record")]
+    fn parses_documents() {
+        let value = parse(r#" {"a": [1, -2.5e1, null], "b": "x\"\u0041", "c": {}} "#).unwrap();
+        assert_eq!(
+            value.get("a").and_then(Value::as_array),
+            Some(&[Value::Number(1.), Value::Number(-25.), Value::Null][..])
+        );
+        assert_eq!(value.get("b").and_then(Value::as_str), Some("x\"A"));
+        assert_eq!(value.get("c"), Some(&Value::Object(Vec::new())));
+        // Anything written can be read back
+        assert_eq!(
+            parse(&string("é\n\u{1}")).unwrap().as_str(),
+            Some("é\n\u{1}")
+        );
+        assert!(parse("[1,]").is_err());
+        assert!(parse("{} x").is_err());
+    }
+}
@@ -0,0 +1,47 @@
+#[cfg(feature = "analysis")]
+pub mod bias;
+#[cfg(any(feature = "book", feature = "tablebase"))]
+pub(crate) mod binary;
+#[cfg(feature = "std")]
+pub mod display;
+#[cfg(feature = "std")]
+pub mod grid;
+#[cfg(any(feature = "batch", feature = "record", feature = "train"))]
+pub(crate) mod json;
+mod perft;
+#[cfg(feature = "analysis")]
+pub mod symmetry;
+
+pub use perft::{divide, perft};
+
+#[cfg(any(feature = "arena", feature = "mcts"))]
+use rand::rngs::StdRng;
+#[cfg(any(feature = "arena", feature = "mcts"))]
+use rand::SeedableRng;
+
+/// Create a random number generator from a seed, or from entropy without
+/// one.
+///
+/// Without the `entropy` feature there is no entropy source, so a missing
+/// seed falls back to a fixed one.
+#[cfg(any(feature = "arena", feature = "mcts"))]
+pub(crate) fn rng(seed: Option<u64>) -> StdRng {
+    match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        #[cfg(feature = "entropy")]
+        None => StdRng::from_entropy(),
+        #[cfg(not(feature = "entropy"))]
+        None => StdRng::seed_from_u64(0),
+    }
+}
+
+/// Hash the string form of a position to key a table.
+///
+/// This is 64-bit FNV-1a, which unlike the standard library's hasher is the
+/// same across Rust versions and platforms, so saved tables stay valid.
+#[cfg(any(feature = "book", feature = "qlearn", feature = "tablebase"))]
+pub(crate) fn hash(key: &str) -> u64 {
+    key.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
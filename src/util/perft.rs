@@ -0,0 +1,72 @@
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+use crate::Game;
+
+/// Count the positions reached after exactly `depth` plies.
+///
+/// Games which finish sooner are not counted. Comparing the counts against
+/// known values is a quick check of a game's `turns` and `play`.
+///
+/// # Panics
+///
+/// Panics if a turn returned by `turns` cannot be played.
+pub fn perft<G: Game>(game: &G, depth: usize) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    if game.over() {
+        return 0;
+    }
+    game.turns()
+        .into_iter()
+        .map(|turn| perft(&child(game, turn), depth - 1))
+        .sum()
+}
+
+/// Count the positions reached after exactly `depth` plies through each root
+/// turn.
+///
+/// Splitting the count of [`perft`] by root turn narrows down where a move
+/// generator goes wrong.
+pub fn divide<G: Game>(game: &G, depth: usize) -> Vec<(G::Turn, u64)> {
+    if game.over() {
+        return Vec::new();
+    }
+    game.turns()
+        .into_iter()
+        .map(|turn| {
+            let count = perft(&child(game, turn.clone()), depth.saturating_sub(1));
+            (turn, count)
+        })
+        .collect()
+}
+
+/// Play a turn from a position, which must be legal.
+fn child<G: Game>(game: &G, turn: G::Turn) -> G {
+    let mut state = game.clone();
+    let name = turn.to_string();
+    assert!(
+        state.play(turn),
+        "turn {name} was listed but cannot be played"
+    );
+    state
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::Nim;
+
+    #[test]
+    fn counts_nim() {
+        assert_eq!(perft(&Nim::new(5), 0), 1);
+        assert_eq!(perft(&Nim::new(5), 1), 3);
+        // 5 → {4, 3, 2} → {3, 2, 1, 2, 1, 0, 1, 0}
+        assert_eq!(perft(&Nim::new(5), 2), 8);
+        // Games finished early are not counted
+        assert_eq!(perft(&Nim::new(2), 2), 1);
+        assert_eq!(perft(&Nim::new(2), 3), 0);
+        assert_eq!(divide(&Nim::new(5), 2), [(1, 3), (2, 3), (3, 2)]);
+    }
+}
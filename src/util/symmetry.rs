@@ -0,0 +1,211 @@
+use std::fmt::{self, Display};
+
+use crate::Game;
+
+/// A game whose positions can be mirrored with the sides swapped.
+pub trait Swap: Game {
+    /// Get the position with both sides' roles exchanged.
+    fn swap(&self) -> Self;
+
+    /// Map a turn onto its equivalent in the swapped position.
+    fn swap_turn(&self, turn: &Self::Turn) -> Self::Turn;
+}
+
+/// Swap-test diagnostic for engines and evaluators.
+///
+/// Each position is compared against its side-swapped mirror. A correct
+/// engine plays the mirrored move and a correct evaluator (scoring from the
+/// side to move) returns the same value, so any difference points at a
+/// perspective bug.
+#[derive(Clone, Debug)]
+pub struct SwapTest {
+    trials: usize,
+    tolerance: f64,
+}
+
+impl SwapTest {
+    /// Create a new SwapTest.
+    pub fn new(trials: usize, tolerance: f64) -> SwapTest {
+        assert!(trials > 0);
+        SwapTest { trials, tolerance }
+    }
+
+    /// Compare an engine's move choices against those in the mirror.
+    ///
+    /// Each sample is the total variation distance between the engine's
+    /// move distribution over `trials` runs in both positions.
+    pub fn moves<G, F>(&self, positions: &[G], mut engine: F) -> Report
+    where
+        G: Swap,
+        G::Turn: PartialEq,
+        F: FnMut(&G) -> G::Turn,
+    {
+        let samples = positions
+            .iter()
+            .map(|game| {
+                let mirror = game.swap();
+                let mut tally: Vec<(G::Turn, usize, usize)> = Vec::new();
+                for _ in 0..self.trials {
+                    // Map the original choice into the mirror's frame
+                    let turn = game.swap_turn(&engine(game));
+                    Self::count(&mut tally, turn, |entry| entry.1 += 1);
+                    let turn = engine(&mirror);
+                    Self::count(&mut tally, turn, |entry| entry.2 += 1);
+                }
+                let trials = self.trials as f64;
+                tally
+                    .iter()
+                    .map(|(_, a, b)| (*a as f64 - *b as f64).abs() / trials)
+                    .sum::<f64>()
+                    / 2.
+            })
+            .collect();
+
+        Report {
+            samples,
+            tolerance: self.tolerance,
+        }
+    }
+
+    /// Compare an evaluator's scores against those in the mirror.
+    ///
+    /// Each sample is the signed difference between the evaluation of the
+    /// position and of its mirror, both from the side to move.
+    pub fn evals<G, F>(&self, positions: &[G], mut eval: F) -> Report
+    where
+        G: Swap,
+        F: FnMut(&G) -> f64,
+    {
+        let samples = positions
+            .iter()
+            .map(|game| eval(game) - eval(&game.swap()))
+            .collect();
+
+        Report {
+            samples,
+            tolerance: self.tolerance,
+        }
+    }
+
+    /// Increment the tally for a turn.
+    fn count<T: PartialEq>(
+        tally: &mut Vec<(T, usize, usize)>,
+        turn: T,
+        f: impl FnOnce(&mut (T, usize, usize)),
+    ) {
+        match tally.iter_mut().find(|(other, ..)| *other == turn) {
+            Some(entry) => f(entry),
+            None => {
+                tally.push((turn, 0, 0));
+                f(tally.last_mut().unwrap());
+            }
+        }
+    }
+}
+
+impl Default for SwapTest {
+    fn default() -> Self {
+        SwapTest::new(1, 1e-6)
+    }
+}
+
+/// Per-position asymmetries found by a [`SwapTest`].
+#[derive(Clone, Debug)]
+pub struct Report {
+    /// Asymmetry measured at each position, in input order.
+    pub samples: Vec<f64>,
+    tolerance: f64,
+}
+
+impl Report {
+    /// Get indices of positions whose asymmetry exceeds the tolerance.
+    pub fn flagged(&self) -> Vec<usize> {
+        self.samples
+            .iter()
+            .enumerate()
+            .filter(|(_, x)| x.abs() > self.tolerance)
+            .map(|(idx, _)| idx)
+            .collect()
+    }
+
+    /// Get the mean asymmetry; far from zero indicates a systematic bias.
+    pub fn bias(&self) -> f64 {
+        if self.samples.is_empty() {
+            return 0.;
+        }
+        self.samples.iter().sum::<f64>() / self.samples.len() as f64
+    }
+
+    /// Get the position with the largest asymmetry.
+    pub fn worst(&self) -> Option<(usize, f64)> {
+        self.samples
+            .iter()
+            .copied()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.abs().total_cmp(&b.abs()))
+    }
+
+    /// Check if no position exceeded the tolerance.
+    pub fn is_symmetric(&self) -> bool {
+        self.flagged().is_empty()
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} of {} positions asymmetric (bias {:+.4})",
+            self.flagged().len(),
+            self.samples.len(),
+            self.bias(),
+        )?;
+        for idx in self.flagged() {
+            writeln!(f, "{:03}: {:+.4}", idx, self.samples[idx])?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::Nim;
+
+    impl Swap for Nim {
+        fn swap(&self) -> Self {
+            Nim {
+                stones: self.stones,
+                player: self.player ^ 1,
+            }
+        }
+
+        fn swap_turn(&self, turn: &Self::Turn) -> Self::Turn {
+            *turn
+        }
+    }
+
+    fn positions() -> Vec<Nim> {
+        (1..=10).map(Nim::new).collect()
+    }
+
+    #[test]
+    fn symmetric_engine_passes() {
+        let report = SwapTest::default().moves(&positions(), |game: &Nim| game.stones.min(3));
+        assert!(report.is_symmetric());
+    }
+
+    #[test]
+    fn biased_evaluator_flagged() {
+        let report = SwapTest::default().evals(&positions(), |game: &Nim| {
+            // Scores from a fixed side rather than the side to move
+            if game.player == 0 {
+                game.stones as f64
+            } else {
+                -(game.stones as f64)
+            }
+        });
+        assert_eq!(report.flagged().len(), 10);
+        assert!(report.bias() > 0.);
+    }
+}